@@ -0,0 +1,101 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+#[proc_macro_derive(AssetCollection, attributes(asset))]
+pub fn asset_collection(input: TokenStream) -> TokenStream {
+    parse_asset_collection(input, quote! { winny::asset })
+}
+
+#[proc_macro_derive(WinnyAssetCollection, attributes(asset))]
+pub fn winny_asset_collection(input: TokenStream) -> TokenStream {
+    parse_asset_collection(input, quote! { ::asset })
+}
+
+/// Source of a field, from its `#[asset(...)]` attribute.
+enum AssetField {
+    Path(LitStr),
+    Folder(LitStr),
+    Manifest(LitStr),
+}
+
+fn asset_field(field: &syn::Field) -> syn::Result<Option<AssetField>> {
+    let mut asset = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("asset"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("path") {
+                asset = Some(AssetField::Path(meta.value()?.parse()?));
+                Ok(())
+            } else if meta.path.is_ident("folder") {
+                asset = Some(AssetField::Folder(meta.value()?.parse()?));
+                Ok(())
+            } else if meta.path.is_ident("manifest") {
+                asset = Some(AssetField::Manifest(meta.value()?.parse()?));
+                Ok(())
+            } else {
+                Err(meta.error("expected `path`, `folder` or `manifest`"))
+            }
+        })?;
+    }
+
+    Ok(asset)
+}
+
+fn parse_asset_collection(
+    input: TokenStream,
+    path_to_asset: proc_macro2::TokenStream,
+) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let generics = &input.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let Data::Struct(data) = &input.data else {
+        panic!("AssetCollection may only be derived for structs");
+    };
+    let Fields::Named(fields) = &data.fields else {
+        panic!("AssetCollection may only be derived for structs with named fields");
+    };
+
+    let mut load = Vec::new();
+    let mut handles = Vec::new();
+    for field in fields.named.iter() {
+        let field_name = &field.ident;
+        match asset_field(field) {
+            Ok(Some(AssetField::Path(path))) => {
+                load.push(quote! { #field_name: server.load(#path), });
+                handles.push(quote! { (&self.#field_name).into(), });
+            }
+            Ok(Some(AssetField::Folder(path))) => {
+                load.push(quote! { #field_name: server.load_folder(#path), });
+                handles.push(quote! { (&self.#field_name).into(), });
+            }
+            Ok(Some(AssetField::Manifest(path))) => {
+                load.push(quote! { #field_name: server.load_manifest(#path), });
+                handles.push(quote! { (&self.#field_name).into(), });
+            }
+            Ok(None) => load.push(quote! { #field_name: Default::default(), }),
+            Err(e) => return e.to_compile_error().into(),
+        }
+    }
+
+    quote! {
+        impl #impl_generics #path_to_asset::AssetCollection for #name #ty_generics #where_clause {
+            fn load(server: &#path_to_asset::AssetServer) -> Self {
+                Self {
+                    #(#load)*
+                }
+            }
+
+            fn handles(&self) -> Vec<#path_to_asset::ErasedHandle> {
+                vec![#(#handles)*]
+            }
+        }
+    }
+    .into()
+}
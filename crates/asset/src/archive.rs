@@ -0,0 +1,578 @@
+//! Packed asset archives.
+//!
+//! ```text
+//! "WPAK" | version: u32 | entry count: u32
+//! entries: path len: u16 | path: utf8 | compression: u8 | offset: u64 | size: u64 | unpacked size: u64
+//! data
+//! ```
+//!
+//! All integers are little endian. Offsets are relative to the start of the data.
+
+use crate::source::{AssetFile, AssetReader, AssetReaderError, BoxedFuture};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const MAGIC: &[u8; 4] = b"WPAK";
+const VERSION: u32 = 1;
+/// Size of an index entry with an empty path.
+const MIN_ENTRY_SIZE: u64 = 2 + 1 + 24;
+
+/// Compression of an archive entry.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveCompression {
+    #[default]
+    None,
+    Deflate,
+}
+
+impl ArchiveCompression {
+    fn from_u8(value: u8) -> Result<Self, ArchiveError> {
+        match value {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Deflate),
+            _ => Err(ArchiveError::InvalidIndex),
+        }
+    }
+
+    fn as_u8(&self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Deflate => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ArchiveEntry {
+    compression: ArchiveCompression,
+    offset: u64,
+    size: u64,
+    unpacked_size: u64,
+}
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(std::io::Error),
+    /// The file is not an archive, or was written by an incompatible version.
+    InvalidHeader,
+    InvalidIndex,
+    /// Paths are stored with a `u16` length.
+    PathTooLong(String),
+    /// Entries are counted with a `u32`.
+    TooManyFiles,
+}
+
+impl From<std::io::Error> for ArchiveError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "IO error: {e}"),
+            Self::InvalidHeader => write!(f, "Invalid archive header"),
+            Self::InvalidIndex => write!(f, "Invalid archive index"),
+            Self::PathTooLong(path) => write!(f, "Archive path is too long: {path}"),
+            Self::TooManyFiles => write!(f, "Too many files for an archive"),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+/// Paths are stored with `/` separators, regardless of platform.
+fn archive_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| match c {
+            std::path::Component::Normal(c) => c.to_str(),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Builds an archive. Produced by the `winny_pack` binary.
+#[derive(Debug, Default)]
+pub struct ArchiveWriter {
+    compression: ArchiveCompression,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl ArchiveWriter {
+    pub fn new(compression: ArchiveCompression) -> Self {
+        Self {
+            compression,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn add<P: AsRef<Path>>(&mut self, path: P, bytes: Vec<u8>) {
+        self.files.push((archive_path(path.as_ref()), bytes));
+    }
+
+    /// Adds every file in `dir`, recursively. Paths are stored as given, so that files are
+    /// read from the archive with the same path as from the filesystem.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.add_dir(&path)?;
+            } else {
+                let bytes = std::fs::read(&path)?;
+                self.add(&path, bytes);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn write<W: Write>(self, mut writer: W) -> Result<(), ArchiveError> {
+        let len = u32::try_from(self.files.len()).map_err(|_| ArchiveError::TooManyFiles)?;
+        let mut index = Vec::new();
+        let mut data = Vec::new();
+        for (path, bytes) in self.files.into_iter() {
+            let unpacked_size = bytes.len() as u64;
+            let (compression, bytes) = match self.compression {
+                ArchiveCompression::None => (ArchiveCompression::None, bytes),
+                ArchiveCompression::Deflate => {
+                    let mut encoder =
+                        DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                    encoder.write_all(&bytes)?;
+                    let compressed = encoder.finish()?;
+                    // Already compressed formats, such as PNG, may grow
+                    if compressed.len() < bytes.len() {
+                        (ArchiveCompression::Deflate, compressed)
+                    } else {
+                        (ArchiveCompression::None, bytes)
+                    }
+                }
+            };
+
+            let path_len =
+                u16::try_from(path.len()).map_err(|_| ArchiveError::PathTooLong(path.clone()))?;
+            index.extend_from_slice(&path_len.to_le_bytes());
+            index.extend_from_slice(path.as_bytes());
+            index.push(compression.as_u8());
+            index.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            index.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+            index.extend_from_slice(&unpacked_size.to_le_bytes());
+            data.extend_from_slice(&bytes);
+        }
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&len.to_le_bytes())?;
+        writer.write_all(&index)?;
+        writer.write_all(&data)?;
+
+        Ok(())
+    }
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> Result<u16, ArchiveError> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, ArchiveError> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, ArchiveError> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Reads the header and index of an archive of `archive_len` bytes. Returns the entries and the
+/// length of the header and index.
+fn read_index<R: Read>(
+    reader: &mut R,
+    archive_len: u64,
+) -> Result<(HashMap<String, ArchiveEntry>, u64), ArchiveError> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC || read_u32(reader)? != VERSION {
+        return Err(ArchiveError::InvalidHeader);
+    }
+
+    let len = read_u32(reader)?;
+    let mut header_len = 12;
+    // A corrupt count cannot allocate more entries than fit in the archive
+    let capacity = (len as u64).min(archive_len / MIN_ENTRY_SIZE);
+    let mut entries = HashMap::with_capacity(capacity as usize);
+    for _ in 0..len {
+        let path_len = read_u16(reader)?;
+        let mut path = vec![0; path_len as usize];
+        reader.read_exact(&mut path)?;
+        let path = String::from_utf8(path).map_err(|_| ArchiveError::InvalidIndex)?;
+
+        let mut compression = [0];
+        reader.read_exact(&mut compression)?;
+        let entry = ArchiveEntry {
+            compression: ArchiveCompression::from_u8(compression[0])?,
+            offset: read_u64(reader)?,
+            size: read_u64(reader)?,
+            unpacked_size: read_u64(reader)?,
+        };
+
+        header_len += MIN_ENTRY_SIZE + path_len as u64;
+        entries.insert(path, entry);
+    }
+
+    Ok((entries, header_len))
+}
+
+#[derive(Debug)]
+enum ArchiveStorage {
+    #[cfg(not(target_arch = "wasm32"))]
+    File(std::path::PathBuf),
+    Memory(Arc<[u8]>),
+}
+
+/// Reads files from an archive written by an [`ArchiveWriter`].
+///
+/// On native, only the index is kept in memory.
+#[derive(Debug)]
+pub struct ArchiveAssetReader {
+    storage: ArchiveStorage,
+    entries: HashMap<String, ArchiveEntry>,
+    data_offset: u64,
+}
+
+impl ArchiveAssetReader {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ArchiveError> {
+        let file = std::fs::File::open(path.as_ref())?;
+        let archive_len = file.metadata()?.len();
+        let (entries, data_offset) = read_index(&mut std::io::BufReader::new(file), archive_len)?;
+
+        Ok(Self {
+            storage: ArchiveStorage::File(path.as_ref().to_owned()),
+            entries,
+            data_offset,
+        })
+    }
+
+    pub fn from_bytes<B: Into<Arc<[u8]>>>(bytes: B) -> Result<Self, ArchiveError> {
+        let bytes = bytes.into();
+        let (entries, data_offset) = read_index(&mut bytes.as_ref(), bytes.len() as u64)?;
+
+        Ok(Self {
+            storage: ArchiveStorage::Memory(bytes),
+            entries,
+            data_offset,
+        })
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.entries.contains_key(&archive_path(path.as_ref()))
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|path| path.as_str())
+    }
+
+    /// Start and end of the entry's data in the archive.
+    fn entry_range(&self, entry: &ArchiveEntry) -> Result<(u64, u64), ArchiveError> {
+        self.data_offset
+            .checked_add(entry.offset)
+            .and_then(|start| Some((start, start.checked_add(entry.size)?)))
+            .ok_or(ArchiveError::InvalidIndex)
+    }
+
+    fn read_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>, ArchiveError> {
+        let (start, end) = self.entry_range(entry)?;
+        let packed = match &self.storage {
+            #[cfg(not(target_arch = "wasm32"))]
+            ArchiveStorage::File(path) => {
+                let mut file = std::fs::File::open(path)?;
+                if end > file.metadata()?.len() {
+                    return Err(ArchiveError::InvalidIndex);
+                }
+                file.seek(SeekFrom::Start(start))?;
+                let mut packed = vec![0; entry.size as usize];
+                file.read_exact(&mut packed)?;
+                packed
+            }
+            ArchiveStorage::Memory(bytes) => usize::try_from(start)
+                .ok()
+                .zip(usize::try_from(end).ok())
+                .and_then(|(start, end)| bytes.get(start..end))
+                .ok_or(ArchiveError::InvalidIndex)?
+                .to_vec(),
+        };
+
+        match entry.compression {
+            ArchiveCompression::None => Ok(packed),
+            ArchiveCompression::Deflate => {
+                // Deflate expands data by at most ~1032:1, so a corrupt size cannot allocate more
+                let capacity = entry.unpacked_size.min(packed.len() as u64 * 1032);
+                let mut unpacked = Vec::with_capacity(capacity as usize);
+                DeflateDecoder::new(packed.as_slice()).read_to_end(&mut unpacked)?;
+                Ok(unpacked)
+            }
+        }
+    }
+
+    /// Opens an uncompressed entry without reading it. Compressed entries are read whole.
+    fn open_entry(&self, entry: &ArchiveEntry) -> Result<Box<dyn AssetFile>, ArchiveError> {
+        if entry.compression != ArchiveCompression::None {
+            return Ok(Box::new(Cursor::new(self.read_entry(entry)?)));
+        }
+
+        let (start, end) = self.entry_range(entry)?;
+        match &self.storage {
+            #[cfg(not(target_arch = "wasm32"))]
+            ArchiveStorage::File(path) => {
+                let file = std::fs::File::open(path)?;
+                if end > file.metadata()?.len() {
+                    return Err(ArchiveError::InvalidIndex);
+                }
+                Ok(Box::new(EntryFile::new(file, start, entry.size)?))
+            }
+            ArchiveStorage::Memory(bytes) => {
+                if end > bytes.len() as u64 {
+                    return Err(ArchiveError::InvalidIndex);
+                }
+                let bytes = Cursor::new(bytes.clone());
+                Ok(Box::new(EntryFile::new(bytes, start, entry.size)?))
+            }
+        }
+    }
+
+    fn entry(&self, path: &Path) -> Result<&ArchiveEntry, AssetReaderError> {
+        self.entries
+            .get(&archive_path(path))
+            .ok_or_else(|| AssetReaderError::NotFound(path.to_owned()))
+    }
+}
+
+fn reader_error(error: ArchiveError) -> AssetReaderError {
+    match error {
+        ArchiveError::Io(e) => AssetReaderError::Io(e),
+        e => AssetReaderError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+    }
+}
+
+/// Uncompressed entry of an archive, read without the rest of the archive.
+struct EntryFile<R> {
+    inner: R,
+    start: u64,
+    len: u64,
+    /// Within the entry.
+    position: u64,
+}
+
+impl<R: Seek> EntryFile<R> {
+    fn new(mut inner: R, start: u64, len: u64) -> std::io::Result<Self> {
+        inner.seek(SeekFrom::Start(start))?;
+
+        Ok(Self {
+            inner,
+            start,
+            len,
+            position: 0,
+        })
+    }
+}
+
+impl<R: Read> Read for EntryFile<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.position);
+        let len = (buf.len() as u64).min(remaining) as usize;
+        let read = self.inner.read(&mut buf[..len])?;
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for EntryFile<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        }
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "seek before the start of the entry",
+            )
+        })?;
+        self.inner.seek(SeekFrom::Start(self.start + position))?;
+        self.position = position;
+
+        Ok(position)
+    }
+}
+
+impl AssetReader for ArchiveAssetReader {
+    fn read<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetReaderError>> {
+        Box::pin(async move { self.read_entry(self.entry(path)?).map_err(reader_error) })
+    }
+
+    fn open<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Box<dyn AssetFile>, AssetReaderError>> {
+        Box::pin(async move { self.open_entry(self.entry(path)?).map_err(reader_error) })
+    }
+
+    fn read_directory<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Vec<PathBuf>, AssetReaderError>> {
+        Box::pin(async move {
+            let dir = format!("{}/", archive_path(path));
+            let files = self
+                .entries
+                .keys()
+                .filter_map(|entry| entry.strip_prefix(&dir))
+                .map(PathBuf::from)
+                .collect::<Vec<_>>();
+
+            if files.is_empty() {
+                Err(AssetReaderError::NotFound(path.to_owned()))
+            } else {
+                Ok(files)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for compression in [ArchiveCompression::None, ArchiveCompression::Deflate] {
+            let mut writer = ArchiveWriter::new(compression);
+            writer.add("res/a.txt", b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_vec());
+            writer.add("res/sub/b.bin", vec![0, 1, 2, 3]);
+
+            let mut archive = Vec::new();
+            writer.write(&mut archive).unwrap();
+            let reader = ArchiveAssetReader::from_bytes(archive).unwrap();
+
+            let read = |path: &str| pollster::block_on(reader.read(Path::new(path)));
+            assert_eq!(
+                read("res/a.txt").unwrap(),
+                b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+            );
+            assert_eq!(read("res/sub/b.bin").unwrap(), vec![0, 1, 2, 3]);
+            assert!(matches!(
+                read("res/c.txt"),
+                Err(AssetReaderError::NotFound(_))
+            ));
+
+            let mut file = pollster::block_on(reader.open(Path::new("res/sub/b.bin"))).unwrap();
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes).unwrap();
+            assert_eq!(bytes, vec![0, 1, 2, 3]);
+            assert_eq!(file.seek(SeekFrom::End(-2)).unwrap(), 2);
+            bytes.clear();
+            file.read_to_end(&mut bytes).unwrap();
+            assert_eq!(bytes, vec![2, 3]);
+            assert!(file.seek(SeekFrom::Current(-5)).is_err());
+        }
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert!(matches!(
+            ArchiveAssetReader::from_bytes(b"PNG!".as_slice()),
+            Err(ArchiveError::InvalidHeader | ArchiveError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn corrupt_entry_count() {
+        let mut archive = MAGIC.to_vec();
+        archive.extend_from_slice(&VERSION.to_le_bytes());
+        archive.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            ArchiveAssetReader::from_bytes(archive),
+            Err(ArchiveError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn path_too_long() {
+        let mut writer = ArchiveWriter::default();
+        writer.add("a".repeat(u16::MAX as usize + 1), Vec::new());
+        assert!(matches!(
+            writer.write(Vec::new()),
+            Err(ArchiveError::PathTooLong(_))
+        ));
+    }
+
+    #[test]
+    fn out_of_bounds_entry() {
+        let mut writer = ArchiveWriter::new(ArchiveCompression::Deflate);
+        writer.add("a.txt", b"aaaa".to_vec());
+        let mut archive = Vec::new();
+        writer.write(&mut archive).unwrap();
+        let reader = ArchiveAssetReader::from_bytes(archive.clone()).unwrap();
+        let entry = &reader.entries["a.txt"];
+
+        for corrupt in [
+            ArchiveEntry {
+                size: u64::MAX,
+                unpacked_size: u64::MAX,
+                ..*entry
+            },
+            ArchiveEntry {
+                offset: u64::MAX,
+                ..*entry
+            },
+            ArchiveEntry {
+                size: archive.len() as u64,
+                ..*entry
+            },
+        ] {
+            assert!(matches!(
+                reader.read_entry(&corrupt),
+                Err(ArchiveError::InvalidIndex)
+            ));
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let path = std::env::temp_dir().join("winny_out_of_bounds_entry.pak");
+            std::fs::write(&path, &archive).unwrap();
+            let reader = ArchiveAssetReader::open(&path).unwrap();
+            let corrupt = ArchiveEntry {
+                size: u64::MAX / 2,
+                ..reader.entries["a.txt"]
+            };
+            assert!(matches!(
+                reader.read_entry(&corrupt),
+                Err(ArchiveError::InvalidIndex)
+            ));
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Packs asset directories into an archive read by [`asset::archive::ArchiveAssetReader`].
+//!
+//! ```text
+//! winny_pack [--compress] <output> <dir>...
+//! ```
+//!
+//! Paths are stored as given, so run the packer from the directory the game is run from.
+
+use asset::archive::{ArchiveCompression, ArchiveWriter};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let mut compression = ArchiveCompression::None;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--compress" => compression = ArchiveCompression::Deflate,
+            "-h" | "--help" => {
+                println!("usage: winny_pack [--compress] <output> <dir>...");
+                return ExitCode::SUCCESS;
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.len() < 2 {
+        eprintln!("usage: winny_pack [--compress] <output> <dir>...");
+        return ExitCode::FAILURE;
+    }
+
+    let output = paths.remove(0);
+    let mut writer = ArchiveWriter::new(compression);
+    for dir in paths.iter() {
+        if let Err(e) = writer.add_dir(dir) {
+            eprintln!("Failed to read {dir}: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let len = writer.len();
+    let file = match std::fs::File::create(&output) {
+        Ok(file) => std::io::BufWriter::new(file),
+        Err(e) => {
+            eprintln!("Failed to create {output}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(e) = writer.write(file) {
+        eprintln!("Failed to write {output}: {e}");
+        return ExitCode::FAILURE;
+    }
+
+    println!("Packed {len} files into {output}");
+    ExitCode::SUCCESS
+}
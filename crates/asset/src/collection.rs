@@ -0,0 +1,184 @@
+use crate::{handle::ErasedHandle, server::AssetServer, LoadState};
+use ecs::{EventWriter, Res, ResMut, Resource, WinnyEvent, WinnyResource};
+
+/// Resource of [`crate::Handle`]s loaded together. Registered with
+/// [`crate::AssetApp::register_asset_collection`], which loads it before
+/// [`ecs::Schedule::StartUp`] and tracks it in [`AssetCollections`].
+///
+/// Derived with `#[derive(AssetCollection)]`. Fields are loaded from the path in their
+/// `#[asset(path = "...")]`, `#[asset(folder = "...")]` or `#[asset(manifest = "...")]`
+/// attribute, all other fields are [`Default`].
+///
+/// ```ignore
+/// #[derive(Resource, AssetCollection)]
+/// struct LevelAssets {
+///     #[asset(path = "sprites/player.png")]
+///     player: Handle<Image>,
+///     #[asset(folder = "sounds/level_1")]
+///     sounds: Handle<LoadedFolder>,
+///     #[asset(manifest = "levels/level_1.manifest")]
+///     props: Handle<LoadedFolder>,
+/// }
+/// ```
+pub trait AssetCollection: Resource + Sized {
+    fn load(server: &AssetServer) -> Self;
+    fn handles(&self) -> Vec<ErasedHandle>;
+}
+
+/// Progress of every registered [`AssetCollection`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CollectionState {
+    #[default]
+    Loading,
+    /// Every [`crate::Asset`] and its dependencies are loaded.
+    Loaded,
+    Failed,
+}
+
+/// Tracks the [`CollectionState`] of the registered [`AssetCollection`]s.
+///
+/// There are no app states to transition. Systems wait on the collections with the
+/// [`collections_loading`] and [`collections_loaded`] run conditions, or read
+/// [`AssetCollectionsLoaded`].
+#[derive(WinnyResource, Debug, Default)]
+pub struct AssetCollections {
+    handles: Vec<ErasedHandle>,
+    state: CollectionState,
+}
+
+impl AssetCollections {
+    pub(crate) fn track(&mut self, handles: Vec<ErasedHandle>) {
+        self.handles.extend(handles);
+        self.state = CollectionState::Loading;
+    }
+
+    pub fn state(&self) -> CollectionState {
+        self.state
+    }
+}
+
+/// Sent once every registered [`AssetCollection`] is loaded.
+#[derive(WinnyEvent, Debug)]
+pub struct AssetCollectionsLoaded;
+
+/// Run condition for systems that wait on the registered [`AssetCollection`]s.
+pub fn collections_loaded(collections: Res<AssetCollections>) -> bool {
+    collections.state == CollectionState::Loaded
+}
+
+/// Run condition for loading screens.
+pub fn collections_loading(collections: Res<AssetCollections>) -> bool {
+    collections.state == CollectionState::Loading
+}
+
+pub(crate) fn update_collections(
+    server: Res<AssetServer>,
+    mut collections: ResMut<AssetCollections>,
+    mut loaded: EventWriter<AssetCollectionsLoaded>,
+) {
+    if collections.state != CollectionState::Loading {
+        return;
+    }
+
+    let mut state = CollectionState::Loaded;
+    for handle in collections.handles.iter() {
+        match server.erased_recursive_dependency_load_state(handle) {
+            LoadState::Loaded => (),
+            // Paths without a loader or source are never loaded
+            LoadState::Failed | LoadState::NotLoaded => {
+                util::tracing::error!("Failed to load asset collection");
+                state = CollectionState::Failed;
+                break;
+            }
+            LoadState::Loading => state = CollectionState::Loading,
+        }
+    }
+
+    collections.state = state;
+    if state == CollectionState::Loaded {
+        loaded.send(AssetCollectionsLoaded);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        folder::LoadedFolder,
+        handle::Handle,
+        test_util::{Text, TextLoader},
+        AssetHandleCreator, MemoryAssetReader, WinnyAssetCollection,
+    };
+    use ecs::{Events, World};
+    use std::sync::Arc;
+
+    #[derive(WinnyResource, WinnyAssetCollection)]
+    struct LevelAssets {
+        #[asset(path = "sprites/player.txt")]
+        player: Handle<Text>,
+        #[asset(folder = "sounds")]
+        sounds: Handle<LoadedFolder>,
+        #[asset(manifest = "levels/one.manifest")]
+        props: Handle<LoadedFolder>,
+        score: u32,
+    }
+
+    #[test]
+    fn derive() {
+        // Nothing is registered, so every path fails to load
+        let level = LevelAssets::load(&AssetServer::default());
+        assert!(level.player.is_dangling());
+        assert!(level.sounds.is_dangling());
+        assert!(level.props.is_dangling());
+        assert_eq!(level.score, 0);
+        assert_eq!(level.handles().len(), 3);
+    }
+
+    #[test]
+    fn update_collection_state() {
+        let mut reader = MemoryAssetReader::default();
+        reader.insert("player.txt", b"".as_slice());
+        let server = AssetServer::default();
+        server.add_reader("memory", reader);
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        let handler = Arc::new(AssetHandleCreator::new::<Text>());
+        server.register_loader::<Text>(TextLoader, &["txt"], tx, handler);
+
+        let mut world = World::default();
+        world.insert_resource(server.clone());
+        world.insert_resource(AssetCollections::default());
+        world.register_event::<AssetCollectionsLoaded>();
+        let update = world.register_system(update_collections);
+
+        let player = server.load::<Text, _>("memory://player.txt");
+        world
+            .resource_mut::<AssetCollections>()
+            .track(vec![(&player).into()]);
+        world.run_system(update);
+        assert_eq!(
+            world.resource::<AssetCollections>().state(),
+            CollectionState::Loading
+        );
+
+        server.loaded((&player).into(), Vec::new());
+        world.run_system(update);
+        assert_eq!(
+            world.resource::<AssetCollections>().state(),
+            CollectionState::Loaded
+        );
+        assert_eq!(world.resource::<Events<AssetCollectionsLoaded>>().len(), 1);
+
+        // Tracking another collection loads again, and fails with any of its handles
+        let missing = server.load::<Text, _>("memory://missing.txt");
+        world
+            .resource_mut::<AssetCollections>()
+            .track(vec![(&missing).into()]);
+        server.failed((&missing).into());
+        world.run_system(update);
+        assert_eq!(
+            world.resource::<AssetCollections>().state(),
+            CollectionState::Failed
+        );
+        assert_eq!(world.resource::<Events<AssetCollectionsLoaded>>().len(), 1);
+    }
+}
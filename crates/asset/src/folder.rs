@@ -0,0 +1,222 @@
+use crate::{
+    handle::{ErasedHandle, Handle, UntypedHandle},
+    Asset, AssetEvent, AssetLoadError, AssetLoadErrorKind, AssetMeta, ErasedAssetLoader,
+    LoadContext, LoadRequest,
+};
+use crossbeam_channel::Sender;
+use std::path::{Component, Path};
+
+/// Every file in a folder, or listed in a manifest, for which an [`crate::AssetLoader`] is
+/// registered. Loaded with [`crate::AssetServer::load_folder`] or
+/// [`crate::AssetServer::load_manifest`].
+///
+/// Keeps the files loaded for as long as it is loaded itself.
+#[derive(Debug)]
+pub struct LoadedFolder {
+    files: Vec<(String, UntypedHandle)>,
+}
+
+impl Asset for LoadedFolder {}
+
+impl LoadedFolder {
+    /// Asset paths and handles of the files, sorted by path for folders and in listed order for
+    /// manifests.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &UntypedHandle)> {
+        self.files
+            .iter()
+            .map(|(path, handle)| (path.as_str(), handle))
+    }
+
+    pub fn handles(&self) -> impl Iterator<Item = &UntypedHandle> {
+        self.files.iter().map(|(_, handle)| handle)
+    }
+
+    /// Handles of the files loaded as `A`.
+    pub fn typed<A: Asset>(&self) -> impl Iterator<Item = Handle<A>> + '_ {
+        self.handles().filter_map(|handle| handle.typed())
+    }
+
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&UntypedHandle> {
+        let path = path.as_ref().to_str()?;
+        self.files
+            .iter()
+            .find(|(file, _)| file == path)
+            .map(|(_, handle)| handle)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+/// Extension of manifest files loaded as a [`LoadedFolder`].
+pub const MANIFEST_EXTENSION: &str = "manifest";
+
+/// Lists a folder, or reads a manifest, and loads its files as the dependencies of a
+/// [`LoadedFolder`].
+pub(crate) struct FolderLoader;
+
+impl ErasedAssetLoader for FolderLoader {
+    fn load(
+        &self,
+        handle: ErasedHandle,
+        sender: Sender<AssetEvent>,
+        context: LoadContext,
+        request: LoadRequest,
+    ) {
+        let LoadRequest {
+            source, path, ext, ..
+        } = request;
+        let pools = context.pools.clone();
+        let token = context.token.clone();
+        pools.spawn_load(token.clone(), move || async move {
+            let files = if ext == MANIFEST_EXTENSION {
+                source
+                    .read(Path::new(&path))
+                    .await
+                    .map(|manifest| manifest_paths(&String::from_utf8_lossy(&manifest)))
+            } else {
+                source.read_directory(Path::new(&path)).await.map(|files| {
+                    files
+                        .iter()
+                        .filter(|file| !AssetMeta::is_meta_path(file))
+                        .map(|file| asset_path(context.path(), file))
+                        .collect()
+                })
+            };
+
+            let event = match files {
+                _ if token.is_cancelled() => return,
+                Ok(files) => {
+                    let mut folder = LoadedFolder { files: Vec::new() };
+                    for file in files {
+                        if let Some(handle) = context.server.load_untyped(&file) {
+                            folder.files.push((file, handle));
+                        }
+                    }
+                    let dependencies = folder.handles().cloned().collect();
+
+                    AssetEvent::Loaded {
+                        path: path.into(),
+                        handle,
+                        asset: folder.into(),
+                        dependencies,
+                    }
+                }
+                Err(e) => AssetEvent::Err {
+                    handle,
+                    error: AssetLoadError {
+                        path,
+                        loader: std::any::type_name::<Self>(),
+                        kind: AssetLoadErrorKind::Read(e),
+                    },
+                },
+            };
+
+            if let Err(e) = sender.send(event) {
+                util::tracing::error!("Asset sender error: {}", e);
+            }
+        });
+    }
+}
+
+/// Asset paths listed in a manifest, one per line. Blank lines and lines starting with `#` are
+/// ignored.
+fn manifest_paths(manifest: &str) -> Vec<String> {
+    manifest
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Joins a listed file onto the asset path of its folder, with `/` separators.
+fn asset_path(folder: &str, file: &Path) -> String {
+    let mut path = folder.trim_end_matches('/').to_owned();
+    for component in file.components() {
+        if let Component::Normal(component) = component {
+            if !path.is_empty() {
+                path.push('/');
+            }
+            path.push_str(&component.to_string_lossy());
+        }
+    }
+
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{Text, TextLoader},
+        AssetHandleCreator, AssetServer, MemoryAssetReader,
+    };
+    use std::{sync::Arc, time::Duration};
+
+    #[test]
+    fn load_folder() {
+        let mut reader = MemoryAssetReader::default();
+        for file in [
+            "level/b.txt",
+            "level/a.txt",
+            "level/a.txt.meta",
+            "level/c.bin",
+        ] {
+            reader.insert(file, b"".as_slice());
+        }
+        reader.insert(
+            "one.manifest",
+            b"memory://level/b.txt\n# Skipped\n\n  memory://level/a.txt\nmemory://level/c.bin\n"
+                .as_slice(),
+        );
+        let server = AssetServer::default();
+        server.add_reader("memory", reader);
+
+        let (text_tx, _text_rx) = crossbeam_channel::unbounded();
+        let text_handler = Arc::new(AssetHandleCreator::new::<Text>());
+        server.register_loader::<Text>(TextLoader, &["txt"], text_tx, text_handler);
+        let (folder_tx, folder_rx) = crossbeam_channel::unbounded();
+        let folder_handler = Arc::new(AssetHandleCreator::new::<LoadedFolder>());
+        server.register_loader::<LoadedFolder>(FolderLoader, &[], folder_tx, folder_handler);
+
+        let handle = server.load_folder("memory://level");
+        let Ok(AssetEvent::Loaded {
+            handle: loaded,
+            asset,
+            dependencies,
+            ..
+        }) = folder_rx.recv_timeout(Duration::from_secs(5))
+        else {
+            panic!("expected the folder to load");
+        };
+        assert_eq!(loaded, (&handle).into());
+
+        let folder = asset.into_asset::<LoadedFolder>();
+        let paths = folder.iter().map(|(path, _)| path).collect::<Vec<_>>();
+        assert_eq!(paths, ["memory://level/a.txt", "memory://level/b.txt"]);
+        assert_eq!(folder.typed::<Text>().count(), 2);
+        assert_eq!(dependencies.len(), 2);
+
+        // Manifests keep their listed order
+        let handle = server.load_manifest("memory://one.manifest");
+        let Ok(AssetEvent::Loaded {
+            handle: loaded,
+            asset,
+            ..
+        }) = folder_rx.recv_timeout(Duration::from_secs(5))
+        else {
+            panic!("expected the manifest to load");
+        };
+        assert_eq!(loaded, (&handle).into());
+
+        let folder = asset.into_asset::<LoadedFolder>();
+        let paths = folder.iter().map(|(path, _)| path).collect::<Vec<_>>();
+        assert_eq!(paths, ["memory://level/b.txt", "memory://level/a.txt"]);
+    }
+}
@@ -1,20 +1,43 @@
 use crate::Asset;
-use cereal::{WinnyDeserialize, WinnySerialize};
+use cereal::{Deserialize, Deserializer, Serialize, Serializer, WinnyDeserialize, WinnySerialize};
+use crossbeam_channel::Sender;
 use ecs::{SparseArrayIndex, WinnyAsEgui, WinnyComponent};
-use std::{hash::Hash, marker::PhantomData};
-
-// TODO: could become enum with strong and weak variants which determine
-// dynamic loading behaviour
+use std::{any::TypeId, hash::Hash, marker::PhantomData, sync::Arc};
 
 /// Handle to an [`LoadedAsset`] stored within the appropriate [`Assets`] resource.
 ///
-/// Aquired from [`AssetServer::load`].
-#[derive(WinnySerialize, WinnyDeserialize, WinnyComponent, Debug)]
-pub struct Handle<A: Asset>(AssetId, HandleGeneration, PhantomData<A>);
+/// Aquired from [`AssetServer::load`] or [`Assets::add`] as a strong handle. The [`Asset`] is
+/// unloaded once every strong handle is dropped. Weak handles, created with
+/// [`Handle::clone_weak`], do not keep the [`Asset`] loaded.
+#[derive(WinnyComponent, Debug)]
+pub struct Handle<A: Asset> {
+    id: AssetId,
+    strong: Option<Arc<StrongHandle>>,
+    generation: HandleGeneration,
+    _phantom: PhantomData<A>,
+}
 
 impl<A: Asset> Clone for Handle<A> {
     fn clone(&self) -> Self {
-        Handle::new(self.id())
+        Self {
+            id: self.id,
+            strong: self.strong.clone(),
+            generation: HandleGeneration::default(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Only the [`AssetId`] is serialized. Deserialized handles are weak.
+impl<A: Asset> Serialize for Handle<A> {
+    fn serialize(&self, serializer: &mut Serializer<'_>) {
+        self.id.serialize(serializer);
+    }
+}
+
+impl<A: Asset> Deserialize for Handle<A> {
+    fn deserialize(deserializer: &mut Deserializer<'_>) -> Option<Self> {
+        AssetId::deserialize(deserializer).map(Self::new)
     }
 }
 
@@ -33,67 +56,231 @@ impl<A: Asset> Hash for Handle<A> {
 }
 
 impl<A: Asset> Handle<A> {
+    /// Creates a weak handle.
     pub fn new(id: AssetId) -> Self {
-        Self(id, HandleGeneration::default(), PhantomData)
+        Self {
+            id,
+            strong: None,
+            generation: HandleGeneration::default(),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub(crate) fn from_strong(strong: Arc<StrongHandle>) -> Self {
+        Self {
+            id: strong.id,
+            strong: Some(strong),
+            generation: HandleGeneration::default(),
+            _phantom: PhantomData,
+        }
     }
 
     pub fn id(&self) -> AssetId {
-        self.0
+        self.id
     }
 
     /// Indicates that the handle does not point to a valid [`LoadedAsset`].
     ///
     /// This will always return [`None`] from [`Assets::get`].
     pub fn dangling() -> Self {
-        Self(AssetId(u32::MAX), HandleGeneration::default(), PhantomData)
+        Self::new(AssetId::new(u32::MAX))
     }
 
     pub fn is_dangling(&self) -> bool {
-        self.0 .0 == u32::MAX
+        self.id.index == u32::MAX
+    }
+
+    pub fn is_strong(&self) -> bool {
+        self.strong.is_some()
+    }
+
+    pub fn is_weak(&self) -> bool {
+        self.strong.is_none()
+    }
+
+    /// Creates a handle to the same [`Asset`] that does not keep it loaded.
+    pub fn clone_weak(&self) -> Self {
+        Self::new(self.id)
     }
 
     pub fn point_to(&mut self, other: &Handle<A>) {
-        self.0 = other.id();
+        self.id = other.id();
+        self.strong = other.strong.clone();
         self.mark_changed();
     }
 
     pub fn mark_changed(&mut self) {
-        self.1.increment();
+        self.generation.increment();
     }
 
     pub fn is_changed(&mut self) -> bool {
-        self.1.is_changed()
+        self.generation.is_changed()
+    }
+}
+
+/// Reference counted by strong [`Handle`]s. Notifies the [`crate::AssetHandleCreator`] when the
+/// last strong [`Handle`] is dropped.
+#[derive(Debug)]
+pub struct StrongHandle {
+    id: AssetId,
+    drop_tx: Sender<AssetId>,
+}
+
+impl StrongHandle {
+    pub(crate) fn new(id: AssetId, drop_tx: Sender<AssetId>) -> Self {
+        Self { id, drop_tx }
+    }
+}
+
+impl Drop for StrongHandle {
+    fn drop(&mut self) {
+        if let Err(e) = self.drop_tx.send(self.id) {
+            util::tracing::error!("Dropped handle tx error: {e}");
+        }
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-pub struct ErasedHandle(AssetId);
+/// Type-erased [`Handle`]. Identifies an [`Asset`] of any type.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ErasedHandle {
+    id: AssetId,
+    type_id: TypeId,
+}
 
 impl ErasedHandle {
-    pub fn new(id: AssetId) -> Self {
-        Self(id)
+    pub fn new(id: AssetId, type_id: TypeId) -> Self {
+        Self { id, type_id }
     }
 
     pub fn id(&self) -> AssetId {
-        self.0
+        self.id
+    }
+
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
     }
 }
 
 impl<A: Asset> Into<Handle<A>> for ErasedHandle {
     fn into(self) -> Handle<A> {
-        Handle::new(self.0)
+        Handle::new(self.id)
+    }
+}
+
+impl<A: Asset> From<&Handle<A>> for ErasedHandle {
+    fn from(value: &Handle<A>) -> Self {
+        Self::new(value.id(), TypeId::of::<A>())
+    }
+}
+
+/// Type-erased [`Handle`] which keeps its [`Asset`] loaded, like a strong [`Handle`].
+#[derive(Debug, Clone)]
+pub struct UntypedHandle {
+    handle: ErasedHandle,
+    strong: Option<Arc<StrongHandle>>,
+}
+
+impl UntypedHandle {
+    pub(crate) fn from_strong(type_id: TypeId, strong: Arc<StrongHandle>) -> Self {
+        Self {
+            handle: ErasedHandle::new(strong.id, type_id),
+            strong: Some(strong),
+        }
+    }
+
+    pub fn id(&self) -> AssetId {
+        self.handle.id()
+    }
+
+    pub fn type_id(&self) -> TypeId {
+        self.handle.type_id()
+    }
+
+    pub fn erased(&self) -> ErasedHandle {
+        self.handle
+    }
+
+    pub fn is<A: Asset>(&self) -> bool {
+        self.type_id() == TypeId::of::<A>()
+    }
+
+    /// Returns a [`Handle`] sharing the reference count, or [`None`] if the [`Asset`] is not an
+    /// `A`.
+    pub fn typed<A: Asset>(&self) -> Option<Handle<A>> {
+        if !self.is::<A>() {
+            return None;
+        }
+
+        Some(match &self.strong {
+            Some(strong) => Handle::from_strong(strong.clone()),
+            None => Handle::new(self.id()),
+        })
+    }
+}
+
+impl<A: Asset> From<Handle<A>> for UntypedHandle {
+    fn from(value: Handle<A>) -> Self {
+        Self {
+            handle: (&value).into(),
+            strong: value.strong,
+        }
+    }
+}
+
+impl PartialEq for UntypedHandle {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle == other.handle
+    }
+}
+
+impl Eq for UntypedHandle {}
+
+impl Hash for UntypedHandle {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.handle.hash(state);
     }
 }
 
 /// Index into an [`Assets`] resource.
+///
+/// Indices of unloaded assets are reused with the next generation, so old ids and weak handles
+/// never refer to the asset that takes their place.
 #[derive(
-    WinnyAsEgui, WinnySerialize, WinnyDeserialize, Debug, Clone, Copy, PartialEq, Eq, Hash,
+    WinnyAsEgui, WinnySerialize, WinnyDeserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash,
 )]
-pub struct AssetId(pub(crate) u32);
+pub struct AssetId {
+    pub(crate) index: u32,
+    pub(crate) generation: u32,
+}
+
+impl AssetId {
+    pub(crate) const fn new(index: u32) -> Self {
+        Self {
+            index,
+            generation: 0,
+        }
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Id of the next asset stored at this index.
+    pub(crate) fn next_generation(self) -> Self {
+        Self {
+            index: self.index,
+            generation: self.generation.wrapping_add(1),
+        }
+    }
+}
 
 impl SparseArrayIndex for AssetId {
     fn index(&self) -> usize {
-        self.0 as usize
+        self.index as usize
     }
 }
 
@@ -121,3 +308,60 @@ impl HandleGeneration {
         self.current = self.current.wrapping_add(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::Text, AssetHandleCreator};
+
+    #[test]
+    fn serialize_round_trip() {
+        let handler = AssetHandleCreator::new::<Text>();
+        handler.reserve();
+        let mut handle: Handle<Text> = handler.strong(handler.reserve());
+        handle.mark_changed();
+
+        let mut bytes = Vec::new();
+        handle.serialize(&mut Serializer::new(&mut bytes));
+        let mut id_bytes = Vec::new();
+        handle.id().serialize(&mut Serializer::new(&mut id_bytes));
+        assert_eq!(bytes, id_bytes);
+
+        let deserialized = Handle::<Text>::deserialize(&mut Deserializer::new(&mut bytes)).unwrap();
+        assert_eq!(deserialized, handle);
+        assert!(deserialized.is_weak());
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn strong_and_weak_handles() {
+        let handler = AssetHandleCreator::new::<Text>();
+        let erased = handler.reserve();
+
+        let strong: Handle<Text> = handler.strong(erased);
+        let shared: Handle<Text> = handler.strong(erased);
+        let cloned = strong.clone();
+        let weak = strong.clone_weak();
+        assert!(strong.is_strong() && shared.is_strong() && cloned.is_strong());
+        assert!(weak.is_weak());
+        assert!(handler.is_referenced(erased.id()));
+
+        drop(strong);
+        drop(shared);
+        assert!(handler.is_referenced(erased.id()));
+        assert!(handler.dropped().is_empty());
+
+        // Weak handles do not keep the asset referenced
+        drop(cloned);
+        assert!(!handler.is_referenced(erased.id()));
+        assert_eq!(handler.dropped(), [erased.id()].into());
+        assert_eq!(weak.id(), erased.id());
+
+        let untyped = UntypedHandle::from(handler.strong::<Text>(erased));
+        let typed = untyped.typed::<Text>().unwrap();
+        drop(untyped);
+        assert!(handler.is_referenced(erased.id()));
+        drop(typed);
+        assert!(!handler.is_referenced(erased.id()));
+    }
+}
@@ -1,23 +1,46 @@
 use app::prelude::*;
+use cereal::FromValue;
 use crossbeam_channel::{Sender, TryRecvError};
-use ecs::{DumbVec, EventReader, EventWriter, Res, ResMut, SparseArray, WinnyEvent, WinnyResource};
-use server::{AssetHandleCreator, AssetServer};
-use std::path::PathBuf;
+use ecs::{
+    Commands, DumbVec, EventReader, EventWriter, Local, Res, ResMut, SparseArray, WinnyEvent,
+    WinnyResource,
+};
+use std::any::Any;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::{
     fmt::{Debug, Display},
     io::{BufReader, Cursor},
 };
-use util::tracing::{error, info};
+use task::{AssetTaskPools, LoadToken};
+use util::tracing::{error, info, trace};
 
+pub mod archive;
+pub mod collection;
+pub mod folder;
 pub mod handle;
+pub mod meta;
+pub mod processor;
 pub mod reader;
+pub mod saver;
 pub mod server;
+pub mod source;
+pub mod task;
 pub mod toml;
 pub mod watcher;
 
+#[cfg(test)]
+mod test_util;
+
 #[allow(unused)]
-pub use crate::{handle::*, reader::*, server::*, toml::*, watcher::*};
+pub use crate::{
+    archive::*, collection::*, folder::*, handle::*, meta::*, processor::*, reader::*, saver::*,
+    server::*, source::*, task::*, toml::*, watcher::*,
+};
+pub use asset_macro::*;
+
+extern crate self as asset;
 
 #[derive(Debug)]
 pub struct AssetLoaderPlugin;
@@ -25,8 +48,14 @@ pub struct AssetLoaderPlugin;
 impl Plugin for AssetLoaderPlugin {
     fn build(&mut self, app: &mut App) {
         app.insert_resource(AssetServer::default())
+            .insert_resource(AssetProcessors::default())
+            .insert_resource(AssetCollections::default())
             .register_event::<ReloadAsset>()
-            .add_systems(Schedule::PostUpdate, reload_assets);
+            .register_event::<AssetCollectionsLoaded>()
+            .add_systems(Schedule::PostUpdate, reload_assets)
+            .add_systems(Schedule::PreUpdate, collection::update_collections)
+            .register_asset::<LoadedFolder>();
+        register_erased_asset_loader::<LoadedFolder>(app, folder::FolderLoader, &[]);
     }
 }
 
@@ -37,9 +66,33 @@ impl Plugin for AssetLoaderPlugin {
 #[derive(WinnyEvent, Debug)]
 pub struct ReloadAsset(PathBuf);
 
-fn reload_assets(server: Res<AssetServer>, reader: EventReader<ReloadAsset>) {
+fn reload_assets(
+    server: Res<AssetServer>,
+    processors: Res<AssetProcessors>,
+    reader: EventReader<ReloadAsset>,
+) {
     for event in reader.read() {
-        server.reload(&event.0);
+        if server.is_unchanged_save(&event.0) {
+            continue;
+        }
+
+        // A modified meta file reloads the asset it describes
+        let path = if AssetMeta::is_meta_path(&event.0) {
+            event.0.with_extension("")
+        } else {
+            event.0
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let AssetMode::Processed(destination) = server.mode() {
+            if let Err(e) = processors.process_file(&path, &destination) {
+                error!("Failed to process asset {:?}: {}", path, e);
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = &processors;
+
+        server.reload(&path);
     }
 }
 
@@ -50,15 +103,23 @@ pub trait Asset: Send + Sync + 'static {}
 /// Created by [`AssetApp::register_asset_loader`].
 #[derive(WinnyResource)]
 pub struct Assets<A: Asset> {
-    storage: SparseArray<AssetId, A>,
+    /// Assets with the generation of their id.
+    storage: SparseArray<AssetId, (u32, A)>,
     handler: Arc<AssetHandleCreator>,
+    retain_unreferenced: bool,
+    fallback: Option<A>,
+    /// Ids whose last load failed.
+    failed: HashSet<AssetId>,
 }
 
 impl<A: Asset> Default for Assets<A> {
     fn default() -> Self {
         Self {
             storage: SparseArray::default(),
-            handler: Arc::new(AssetHandleCreator::default()),
+            handler: Arc::new(AssetHandleCreator::new::<A>()),
+            retain_unreferenced: false,
+            fallback: None,
+            failed: HashSet::new(),
         }
     }
 }
@@ -71,117 +132,269 @@ impl<A: Asset> Debug for Assets<A> {
 
 impl<A: Asset> Assets<A> {
     pub(crate) fn insert(&mut self, asset: A, id: AssetId) {
-        self.storage.insert(id.0 as usize, asset);
+        self.storage
+            .insert(id.index as usize, (id.generation, asset));
     }
 
+    /// Ignores assets of another generation stored at the same index.
+    fn stored(&self, id: AssetId) -> Option<&A> {
+        self.storage
+            .get(&id)
+            .filter(|(generation, _)| *generation == id.generation)
+            .map(|(_, asset)| asset)
+    }
+
+    fn take(&mut self, id: AssetId) -> Option<A> {
+        self.stored(id)?;
+        self.storage.take(id.index as usize).map(|(_, asset)| asset)
+    }
+
+    /// Returns the fallback [`Asset`] if the [`Handle`] failed to load. See
+    /// [`Assets::set_fallback`].
     pub fn get(&self, handle: &Handle<A>) -> Option<&A> {
-        self.storage.get(&handle.id())
+        self.stored(handle.id()).or_else(|| {
+            self.failed
+                .contains(&handle.id())
+                .then_some(self.fallback.as_ref())
+                .flatten()
+        })
     }
 
     pub fn get_mut(&mut self, handle: &Handle<A>) -> Option<&mut A> {
-        self.storage.get_mut(&handle.id())
+        let id = handle.id();
+        self.storage
+            .get_mut(&id)
+            .filter(|(generation, _)| *generation == id.generation)
+            .map(|(_, asset)| asset)
     }
 
     pub fn remove(&mut self, handle: &Handle<A>) -> A {
+        let asset = self.take(handle.id()).unwrap();
         self.handler.remove(handle.id());
-        self.storage.take(handle.id().0 as usize).unwrap()
+
+        asset
+    }
+
+    /// Sets the [`Asset`] returned by [`Assets::get`] for [`Handle`]s which failed to load, such
+    /// as a placeholder texture.
+    pub fn set_fallback(&mut self, asset: A) {
+        self.fallback = Some(asset);
+    }
+
+    pub fn fallback(&self) -> Option<&A> {
+        self.fallback.as_ref()
+    }
+
+    /// Returns true if the last load of the [`Handle`] failed.
+    pub fn is_failed(&self, handle: &Handle<A>) -> bool {
+        self.failed.contains(&handle.id())
+    }
+
+    /// Keeps [`Asset`]s loaded after their last strong [`Handle`] is dropped.
+    ///
+    /// Useful for assets that are requested by path every frame, such as shaders.
+    pub fn retain_unreferenced(&mut self, retain: bool) {
+        self.retain_unreferenced = retain;
     }
 
     pub fn add(&mut self, asset: A) -> Handle<A> {
         let handle = self.handler.reserve();
         self.insert(asset, handle.id());
 
-        handle.into()
+        self.handler.strong(handle)
     }
 }
 
 pub trait AssetApp {
     fn register_asset<A: Asset>(&mut self) -> &mut Self;
     fn register_asset_loader<A: Asset>(&mut self, loader: impl AssetLoader) -> &mut Self;
+    /// Processors only run with the [`AssetProcessorPlugin`].
+    fn register_asset_processor(&mut self, processor: impl AssetProcessor) -> &mut Self;
+    /// Enables [`AssetServer::save`] for [`AssetSaver::Asset`].
+    fn register_asset_saver<S: AssetSaver>(&mut self, saver: S) -> &mut Self;
+    /// Loads `.toml` files as [`TomlAsset<T>`]. Reloaded with the other assets when watched.
+    fn register_toml_asset<T: FromValue + Send + Sync + 'static>(&mut self) -> &mut Self;
+    /// Loads `C` during [`AppSchedule::PreStartUp`] and inserts it as a resource. Its progress
+    /// is tracked by [`AssetCollections`].
+    fn register_asset_collection<C: AssetCollection>(&mut self) -> &mut Self;
 }
 
 impl AssetApp for App {
     fn register_asset<A: Asset>(&mut self) -> &mut Self {
         let assets: Assets<A> = Assets::default();
         self.insert_resource(assets)
-            .register_event::<AssetLoaderEvent<A>>();
+            .register_event::<AssetLoaderEvent<A>>()
+            .add_systems(Schedule::PostUpdate, free_unused_assets::<A>);
 
         self
     }
 
     fn register_asset_loader<A: Asset>(&mut self, loader: impl AssetLoader) -> &mut Self {
-        let (asset_result_tx, asset_result_rx) = crossbeam_channel::unbounded();
+        let extensions = loader.extensions();
+        register_erased_asset_loader::<A>(self, loader, extensions);
+
+        self
+    }
+
+    fn register_asset_processor(&mut self, processor: impl AssetProcessor) -> &mut Self {
+        self.world_mut()
+            .resource_mut::<AssetProcessors>()
+            .register_processor(processor);
 
-        self.add_systems(Schedule::PostUpdate, flush_errored_handles::<A>);
+        self
+    }
 
+    fn register_asset_saver<S: AssetSaver>(&mut self, saver: S) -> &mut Self {
         self.add_systems(
-            AppSchedule::Platform,
-            move |mut assets: ResMut<Assets<A>>,
-                  mut asset_loader_events: EventWriter<AssetLoaderEvent<A>>| {
-                match asset_result_rx.try_recv() {
-                    Ok(event) => match event {
-                        AssetEvent::Err {
-                            error,
-                            path,
-                            handle,
-                        } => {
-                            error!(
-                                "Failed to load asset [{}]: {:?}: {}",
-                                std::any::type_name::<A>(),
-                                path,
-                                error,
-                            );
-
-                            asset_loader_events.send(AssetLoaderEvent::Err {
-                                error,
-                                path,
-                                handle: handle.into(),
-                            })
-                        }
-                        AssetEvent::Loaded {
+            Schedule::PostUpdate,
+            move |assets: Res<Assets<S::Asset>>, server: Res<AssetServer>| {
+                for (id, path) in server.take_saves::<S::Asset>() {
+                    match saver::save_asset(&saver, &server, assets.stored(id), &path) {
+                        Ok(()) => info!(
+                            "Saved asset [{}]: {:?}",
+                            std::any::type_name::<S::Asset>(),
+                            path
+                        ),
+                        Err(e) => error!(
+                            "Failed to save asset [{}]: {:?}: {}",
+                            std::any::type_name::<S::Asset>(),
                             path,
-                            handle,
-                            asset,
-                        } => {
-                            info!("Loaded asset [{}]: {:?}", std::any::type_name::<A>(), path);
+                            e
+                        ),
+                    }
+                }
+            },
+        )
+    }
 
-                            asset_loader_events.send(AssetLoaderEvent::Loaded {
-                                handle: handle.into(),
-                            });
+    fn register_toml_asset<T: FromValue + Send + Sync + 'static>(&mut self) -> &mut Self {
+        self.register_asset::<TomlAsset<T>>()
+            .register_asset_loader::<TomlAsset<T>>(toml::TypedTomlAssetLoader::<T>::default())
+    }
 
-                            assets.insert(asset.into_asset(), handle.id());
-                        }
-                    },
-                    Err(e) => match e {
-                        TryRecvError::Empty => (),
-                        TryRecvError::Disconnected => {
-                            error!("Asset sender channel closed");
-                            panic!();
-                        }
-                    },
-                }
+    fn register_asset_collection<C: AssetCollection>(&mut self) -> &mut Self {
+        self.add_systems(
+            AppSchedule::PreStartUp,
+            |mut commands: Commands,
+             server: Res<AssetServer>,
+             mut collections: ResMut<AssetCollections>| {
+                let collection = C::load(&server);
+                collections.track(collection.handles());
+                commands.insert_resource(collection);
             },
-        );
+        )
+    }
+}
 
-        {
-            let handler = self.world().resource::<Assets<A>>().handler.clone();
-            let server = self.world_mut().resource_mut::<AssetServer>();
-            server.register_loader::<A>(loader, asset_result_tx, handler);
-        }
+/// Receives the results of `loader` into [`Assets<A>`].
+fn register_erased_asset_loader<A: Asset>(
+    app: &mut App,
+    loader: impl ErasedAssetLoader,
+    extensions: &'static [&'static str],
+) {
+    let (asset_result_tx, asset_result_rx) = crossbeam_channel::unbounded();
+
+    app.add_systems(
+        AppSchedule::Platform,
+        move |mut assets: ResMut<Assets<A>>,
+              mut asset_loader_events: EventWriter<AssetLoaderEvent<A>>,
+              server: Res<AssetServer>,
+              mut pending: Local<Vec<Handle<A>>>| {
+            match asset_result_rx.try_recv() {
+                Ok(event) => match event {
+                    AssetEvent::Err { error, handle } => {
+                        error!(
+                            "Failed to load asset [{}]: {}",
+                            std::any::type_name::<A>(),
+                            error,
+                        );
 
-        self
+                        server.failed(handle);
+                        assets.failed.insert(handle.id());
+                        asset_loader_events.send(AssetLoaderEvent::Failed {
+                            error,
+                            handle: handle.into(),
+                        })
+                    }
+                    AssetEvent::Loaded {
+                        path,
+                        handle,
+                        asset,
+                        dependencies,
+                    } => {
+                        info!("Loaded asset [{}]: {:?}", std::any::type_name::<A>(), path);
+
+                        asset_loader_events.send(AssetLoaderEvent::Loaded {
+                            handle: handle.into(),
+                        });
+
+                        assets.failed.remove(&handle.id());
+                        assets.insert(asset.into_asset(), handle.id());
+                        server.loaded(handle, dependencies);
+                        pending.push(handle.into());
+                    }
+                },
+                Err(e) => match e {
+                    TryRecvError::Empty => (),
+                    TryRecvError::Disconnected => {
+                        error!("Asset sender channel closed");
+                        panic!();
+                    }
+                },
+            }
+
+            pending.retain(
+                |handle| match server.recursive_dependency_load_state(handle) {
+                    LoadState::Loaded => {
+                        asset_loader_events.send(AssetLoaderEvent::LoadedWithDependencies {
+                            handle: handle.clone(),
+                        });
+                        false
+                    }
+                    LoadState::Failed => {
+                        error!(
+                            "Failed to load dependencies of asset [{}]",
+                            std::any::type_name::<A>()
+                        );
+                        false
+                    }
+                    LoadState::NotLoaded | LoadState::Loading => true,
+                },
+            );
+        },
+    );
+
+    {
+        let handler = app.world().resource::<Assets<A>>().handler.clone();
+        let server = app.world_mut().resource_mut::<AssetServer>();
+        server.register_loader::<A>(loader, extensions, asset_result_tx, handler);
     }
 }
 
-fn flush_errored_handles<A: Asset>(
-    reader: EventReader<AssetLoaderEvent<A>>,
+/// Removes [`Asset`]s once their last strong [`Handle`] is dropped.
+fn free_unused_assets<A: Asset>(
+    mut assets: ResMut<Assets<A>>,
+    mut asset_loader_events: EventWriter<AssetLoaderEvent<A>>,
     server: Res<AssetServer>,
 ) {
-    for event in reader.peak_read() {
-        match event {
-            AssetLoaderEvent::Err { path, .. } => server.remove::<A, &String>(path),
-            _ => (),
+    let handler = assets.handler.clone();
+    let dropped = handler.dropped();
+    if assets.retain_unreferenced {
+        return;
+    }
+
+    for id in dropped {
+        let handle = Handle::<A>::new(id);
+        if !server.release((&handle).into(), &handler) {
+            continue;
         }
+
+        let _ = assets.take(id);
+        assets.failed.remove(&id);
+        handler.remove(id);
+
+        trace!("Unloaded asset [{}]: {:?}", std::any::type_name::<A>(), id);
+        asset_loader_events.send(AssetLoaderEvent::Unloaded { handle });
     }
 }
 
@@ -190,10 +403,21 @@ pub enum AssetLoaderEvent<A: Asset> {
     Loaded {
         handle: Handle<A>,
     },
-    Err {
+    /// Sent once the [`Asset`] and all of the dependencies requested through its
+    /// [`LoadContext`] are loaded.
+    LoadedWithDependencies {
         handle: Handle<A>,
-        path: String,
-        error: AssetLoaderError,
+    },
+    /// Sent once the [`Asset`] is removed from [`Assets`] after its last strong [`Handle`] was
+    /// dropped. The [`Handle`] is weak.
+    Unloaded {
+        handle: Handle<A>,
+    },
+    /// Sent when loading fails. [`Assets::get`] returns the fallback for the [`Handle`], if one
+    /// is set. Loading is only attempted again with [`AssetServer::retry`].
+    Failed {
+        handle: Handle<A>,
+        error: AssetLoadError,
     },
 }
 
@@ -227,17 +451,97 @@ enum AssetEvent {
         path: PathBuf,
         handle: ErasedHandle,
         asset: ErasedAsset,
+        dependencies: Vec<UntypedHandle>,
     },
     Err {
         handle: ErasedHandle,
-        path: String,
-        error: AssetLoaderError,
+        error: AssetLoadError,
     },
 }
 
+/// Passed to an [`AssetLoader`] to request the dependencies of the [`Asset`] being loaded.
+pub struct LoadContext {
+    server: AssetServer,
+    dependencies: Vec<UntypedHandle>,
+    pools: Arc<AssetTaskPools>,
+    token: Arc<LoadToken>,
+    path: String,
+    file: AssetFilePath,
+}
+
+impl LoadContext {
+    pub(crate) fn new(
+        server: AssetServer,
+        pools: Arc<AssetTaskPools>,
+        token: Arc<LoadToken>,
+        path: String,
+        file: AssetFilePath,
+    ) -> Self {
+        Self {
+            server,
+            dependencies: Vec::new(),
+            pools,
+            token,
+            path,
+            file,
+        }
+    }
+
+    /// Path passed to [`AssetServer::load`], before the path prefix is applied.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// File of the loading [`Asset`], which [`AssetLoader::streamed`] loaders read from.
+    pub fn file(&self) -> &AssetFilePath {
+        &self.file
+    }
+
+    /// Loads a dependency with the [`LoadPriority`] of the loading [`Asset`]. The loading
+    /// [`Asset`] is only considered fully loaded by
+    /// [`AssetServer::recursive_dependency_load_state`] once all dependencies are loaded.
+    ///
+    /// Dependencies are kept loaded while the loading [`Asset`] is, even if the returned
+    /// [`Handle`] is dropped.
+    pub fn load<A: Asset, P: AsRef<Path>>(&mut self, path: P) -> Handle<A> {
+        let handle = self.server.load::<A, P>(path);
+        self.server.set_priority(&handle, self.token.priority());
+        self.dependencies.push(handle.clone().into());
+
+        handle
+    }
+
+    /// Runs CPU heavy work, such as decoding, on the compute pool, keeping the IO workers free
+    /// to read files.
+    ///
+    /// Returns [`AssetLoaderError::Cancelled`] if the load is cancelled before `f` runs, such as
+    /// when the [`Asset`] is reloaded or released. The loader should return the error.
+    pub async fn compute<T: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, AssetLoaderError> {
+        self.pools
+            .compute(self.token.clone(), f)
+            .await
+            .ok_or(AssetLoaderError::Cancelled)
+    }
+
+    pub fn dependencies(&self) -> &[UntypedHandle] {
+        &self.dependencies
+    }
+
+    pub(crate) fn into_dependencies(self) -> Vec<UntypedHandle> {
+        self.dependencies
+    }
+}
+
+/// Code-side override of an [`AssetLoader::Settings`], applied after the `.meta` file.
+pub(crate) type SettingsOverride = Arc<dyn Fn(&mut dyn Any) + Send + Sync>;
+
 pub trait AssetLoader: Send + Sync + 'static {
     type Asset: Asset;
-    type Settings: Default + Send + Sync;
+    /// Read from the `[loader]` table of the asset's [`AssetMeta`] file, if it exists.
+    type Settings: Default + FromValue + Send + Sync + 'static;
 
     #[allow(async_fn_in_trait)]
     async fn load(
@@ -245,110 +549,151 @@ pub trait AssetLoader: Send + Sync + 'static {
         settings: Self::Settings,
         path: String,
         ext: &str,
+        context: &mut LoadContext,
     ) -> Result<Self::Asset, AssetLoaderError>;
     fn extensions(&self) -> &'static [&'static str];
+    /// Settings used for assets without a `[loader]` table in their [`AssetMeta`] file.
     fn settings(&self) -> Self::Settings {
         Self::Settings::default()
     }
+    /// Streamed loaders are passed an empty reader, rather than the whole file. They read the
+    /// file as it is used, through [`LoadContext::file`].
+    fn streamed(&self) -> bool {
+        false
+    }
 }
 
-trait ErasedAssetLoader: Send + Sync + 'static {
-    fn load(
-        &self,
-        handler: &AssetHandleCreator,
-        sender: Sender<AssetEvent>,
-        path: String,
-        ext: String,
-    ) -> ErasedHandle;
+/// Resolves the [`AssetLoader::Settings`] for the file at `path`.
+async fn load_settings<L: AssetLoader>(
+    loader_settings: L::Settings,
+    source: &AssetSource,
+    path: &str,
+    settings: Option<SettingsOverride>,
+) -> Result<L::Settings, AssetLoadErrorKind> {
+    let meta = source
+        .read(&AssetMeta::path(path))
+        .await
+        .map(|meta| String::from_utf8_lossy(&meta).into_owned());
+    let mut loader_settings = match meta {
+        Ok(meta) => match AssetMeta::from_toml(&meta) {
+            Ok(AssetMeta {
+                loader: Some(loader),
+                ..
+            }) => L::Settings::from_value(&loader)
+                .map_err(|e| AssetLoadErrorKind::Meta(e.to_string()))?,
+            Ok(_) => loader_settings,
+            Err(e) => return Err(AssetLoadErrorKind::Meta(e.to_string())),
+        },
+        Err(_) => loader_settings,
+    };
+
+    if let Some(settings) = settings {
+        settings(&mut loader_settings);
+    }
+
+    Ok(loader_settings)
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-impl<L: AssetLoader> ErasedAssetLoader for L {
-    fn load(
-        &self,
-        handler: &AssetHandleCreator,
-        sender: Sender<AssetEvent>,
-        path: String,
-        ext: String,
-    ) -> ErasedHandle {
-        let handle = handler.reserve();
-
-        let settings = self.settings();
-        std::thread::spawn(move || {
-            let binary = match pollster::block_on(load_binary(path.as_str())) {
-                Ok(f) => f,
-                Err(_) => {
-                    error!("Could not find file: {:?}", path);
-                    if let Err(e) = sender.send(AssetEvent::Err {
-                        handle,
-                        path,
-                        error: AssetLoaderError::FileNotFound,
-                    }) {
-                        error!("Asset sender error: {}", e);
-                    }
+/// File to load into an [`Asset`], passed to an [`ErasedAssetLoader`].
+pub(crate) struct LoadRequest {
+    pub source: AssetSource,
+    /// Path within the [`AssetSource`], including the path prefix.
+    pub path: String,
+    pub ext: String,
+    pub settings: Option<SettingsOverride>,
+}
 
-                    return;
-                }
-            };
-            let reader = ByteReader::new(BufReader::new(Cursor::new(binary)));
+/// Reads, configures and loads the file of `request` with `L`. Returns `None` if the load was
+/// cancelled.
+///
+/// The file is not read for [`AssetLoader::streamed`] loaders.
+async fn load_asset<L: AssetLoader>(
+    loader_settings: L::Settings,
+    streamed: bool,
+    mut context: LoadContext,
+    request: &LoadRequest,
+) -> Option<Result<(L::Asset, Vec<UntypedHandle>), AssetLoadErrorKind>> {
+    let LoadRequest {
+        source,
+        path,
+        ext,
+        settings,
+    } = request;
+    let binary = if streamed {
+        Vec::new()
+    } else {
+        match source.read(Path::new(path)).await {
+            Ok(binary) => binary,
+            Err(e) => return Some(Err(AssetLoadErrorKind::Read(e))),
+        }
+    };
+    if context.token.is_cancelled() {
+        return None;
+    }
 
-            let result = pollster::block_on(L::load(reader, settings, path.clone(), ext.as_str()));
-            if let Err(e) = sender.send(match result {
-                Ok(a) => AssetEvent::Loaded {
-                    path: path.into(),
-                    handle: handle.clone(),
-                    asset: a.into(),
-                },
-                Err(e) => AssetEvent::Err {
-                    error: e,
-                    handle,
-                    path,
-                },
-            }) {
-                error!("Asset sender error: {}", e);
-            }
-        });
+    let reader = ByteReader::new(BufReader::new(Cursor::new(binary)));
+    let settings = match load_settings::<L>(loader_settings, source, path, settings.clone()).await {
+        Ok(settings) => settings,
+        Err(e) => return Some(Err(e)),
+    };
 
-        handle
-    }
+    Some(
+        L::load(reader, settings, path.to_owned(), ext, &mut context)
+            .await
+            .map(|asset| (asset, context.into_dependencies()))
+            .map_err(AssetLoadErrorKind::Loader),
+    )
+}
+
+trait ErasedAssetLoader: Send + Sync + 'static {
+    /// Queues a load of `request` into `handle`, sending the result to `sender`.
+    fn load(
+        &self,
+        handle: ErasedHandle,
+        sender: Sender<AssetEvent>,
+        context: LoadContext,
+        request: LoadRequest,
+    );
 }
 
-#[cfg(target_arch = "wasm32")]
 impl<L: AssetLoader> ErasedAssetLoader for L {
     fn load(
         &self,
-        handler: &AssetHandleCreator,
+        handle: ErasedHandle,
         sender: Sender<AssetEvent>,
-        path: String,
-        ext: String,
-    ) -> ErasedHandle {
-        let handle = handler.reserve();
-
-        let settings = self.settings();
-        wasm_bindgen_futures::spawn_local(async move {
-            let binary = match load_binary(path.as_str()).await {
-                Ok(b) => b,
-                Err(_) => panic!("Failed to load binary"),
-            };
-            let reader = ByteReader::new(BufReader::new(Cursor::new(binary)));
-            let result = L::load(reader, settings, path.clone(), ext.as_str()).await;
-            if let Err(e) = sender.send(match result {
-                Ok(a) => AssetEvent::Loaded {
+        context: LoadContext,
+        request: LoadRequest,
+    ) {
+        let loader_settings = self.settings();
+        let streamed = self.streamed();
+        let pools = context.pools.clone();
+        let token = context.token.clone();
+        pools.spawn_load(token.clone(), move || async move {
+            let result = load_asset::<L>(loader_settings, streamed, context, &request).await;
+            let path = request.path;
+            let event = match result {
+                _ if token.is_cancelled() => return,
+                None => return,
+                Some(Ok((asset, dependencies))) => AssetEvent::Loaded {
                     path: path.into(),
-                    handle: handle.clone(),
-                    asset: a.into(),
+                    handle,
+                    asset: asset.into(),
+                    dependencies,
                 },
-                Err(e) => AssetEvent::Err {
-                    error: e,
+                Some(Err(kind)) => AssetEvent::Err {
                     handle,
-                    path,
+                    error: AssetLoadError {
+                        path,
+                        loader: std::any::type_name::<L>(),
+                        kind,
+                    },
                 },
-            }) {
+            };
+
+            if let Err(e) = sender.send(event) {
                 error!("Asset sender error: {}", e);
             }
         });
-
-        handle
     }
 }
 
@@ -364,6 +709,7 @@ pub async fn load_string(file_name: &str) -> Result<String, ()> {
         let url = format_url(file_name);
         Ok(reqwest::get(url)
             .await
+            .and_then(|r| r.error_for_status())
             .map_err(|_| ())?
             .text()
             .await
@@ -383,6 +729,7 @@ pub async fn load_binary(file_name: &str) -> Result<Vec<u8>, ()> {
         let url = format_url(file_name);
         Ok(reqwest::get(url)
             .await
+            .and_then(|r| r.error_for_status())
             .map_err(|_| ())?
             .bytes()
             .await
@@ -405,6 +752,17 @@ pub enum AssetLoaderError {
     FailedToBuild,
     SyntaxError,
     SemanticError,
+    Io(std::io::Error),
+    /// Loader specific failure, such as a decoder error.
+    Failed(String),
+    /// The load was cancelled. Returned by [`LoadContext::compute`].
+    Cancelled,
+}
+
+impl From<std::io::Error> for AssetLoaderError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
 }
 
 impl Display for AssetLoaderError {
@@ -428,8 +786,130 @@ impl Display for AssetLoaderError {
             Self::SemanticError => {
                 write!(f, "Semantic error in file type")
             }
+            Self::Io(e) => {
+                write!(f, "IO error: {e}")
+            }
+            Self::Failed(e) => {
+                write!(f, "{e}")
+            }
+            Self::Cancelled => {
+                write!(f, "Load was cancelled")
+            }
         }
     }
 }
 
-impl std::error::Error for AssetLoaderError {}
+impl std::error::Error for AssetLoaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Failure to load an [`Asset`], sent with [`AssetLoaderEvent::Failed`].
+#[derive(Debug)]
+pub struct AssetLoadError {
+    /// Path of the file read, including the path prefix.
+    pub path: String,
+    /// Type name of the [`AssetLoader`].
+    pub loader: &'static str,
+    pub kind: AssetLoadErrorKind,
+}
+
+#[derive(Debug)]
+pub enum AssetLoadErrorKind {
+    /// The file could not be read from its [`AssetSource`].
+    Read(AssetReaderError),
+    /// The `.meta` file or its `[loader]` settings are invalid.
+    Meta(String),
+    /// Returned by the [`AssetLoader`].
+    Loader(AssetLoaderError),
+}
+
+impl Display for AssetLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} [{}]: ", self.path, self.loader)?;
+        match &self.kind {
+            AssetLoadErrorKind::Read(e) => write!(f, "{e}"),
+            AssetLoadErrorKind::Meta(e) => write!(f, "Invalid meta file: {e}"),
+            AssetLoadErrorKind::Loader(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for AssetLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            AssetLoadErrorKind::Read(e) => Some(e),
+            AssetLoadErrorKind::Meta(_) => None,
+            AssetLoadErrorKind::Loader(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::{Events, World};
+
+    impl Asset for &'static str {}
+
+    #[test]
+    fn fallback() {
+        let mut assets = Assets::<&'static str>::default();
+        let loaded = assets.add("loaded");
+        let failed = Handle::new(assets.handler.reserve().id());
+        assets.set_fallback("fallback");
+
+        assert_eq!(assets.get(&loaded), Some(&"loaded"));
+        assert_eq!(assets.get(&failed), None);
+        assets.failed.insert(failed.id());
+        assert_eq!(assets.get(&failed), Some(&"fallback"));
+        assert!(assets.is_failed(&failed));
+    }
+
+    #[test]
+    fn reuse_unloaded_ids() {
+        let mut assets = Assets::<&'static str>::default();
+        let first = assets.add("first");
+        let weak = first.clone_weak();
+        assets.remove(&first);
+
+        let second = assets.add("second");
+        assert_eq!(second.id().index(), weak.id().index());
+        assert_ne!(second.id(), weak.id());
+        assert_eq!(assets.get(&weak), None);
+        assert_eq!(assets.get(&second), Some(&"second"));
+    }
+
+    #[test]
+    fn unload_unreferenced() {
+        let mut world = World::default();
+        world.insert_resource(AssetServer::default());
+        world.insert_resource(Assets::<&'static str>::default());
+        world.register_event::<AssetLoaderEvent<&'static str>>();
+        let free = world.register_system(free_unused_assets::<&'static str>);
+
+        let handle = world.resource_mut::<Assets<&'static str>>().add("asset");
+        let weak = handle.clone_weak();
+        let cloned = handle.clone();
+
+        drop(handle);
+        world.run_system(free);
+        assert_eq!(
+            world.resource::<Assets<&'static str>>().get(&weak),
+            Some(&"asset")
+        );
+
+        drop(cloned);
+        world.run_system(free);
+        assert_eq!(world.resource::<Assets<&'static str>>().get(&weak), None);
+        let events = world.resource::<Events<AssetLoaderEvent<&'static str>>>();
+        assert!(matches!(
+            events.peak(),
+            Some(AssetLoaderEvent::Unloaded { handle }) if *handle == weak
+        ));
+    }
+}
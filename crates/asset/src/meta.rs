@@ -0,0 +1,80 @@
+use crate::toml::{from_toml_str, to_toml_string, TomlError};
+use cereal::{ToValue, Value, WinnyFromValue, WinnyToValue};
+use std::path::{Path, PathBuf};
+
+/// Contents of a `<file>.meta` file, stored next to the file it describes.
+///
+/// ```toml
+/// [loader]
+/// sampler = "linear"
+///
+/// [processor]
+/// sample_rate = 44100
+/// ```
+#[derive(WinnyToValue, WinnyFromValue, Debug, Default, Clone, PartialEq)]
+pub struct AssetMeta {
+    /// Settings for the [`crate::AssetLoader`] of the file.
+    pub loader: Option<Value>,
+    /// Settings for the [`crate::processor::AssetProcessor`] of the file.
+    pub processor: Option<Value>,
+    /// Written next to processed artifacts by the [`crate::processor::AssetProcessors`].
+    pub processed: Option<ProcessedInfo>,
+}
+
+/// Describes how a processed artifact was created.
+#[derive(WinnyToValue, WinnyFromValue, Debug, Default, Clone, PartialEq)]
+pub struct ProcessedInfo {
+    /// Type name of the [`crate::processor::AssetProcessor`].
+    pub processor: String,
+    /// Hash of the source file, processor settings and processor version.
+    pub hash: String,
+}
+
+impl AssetMeta {
+    /// Returns the path of the `.meta` file for `path`.
+    pub fn path<P: AsRef<Path>>(path: P) -> PathBuf {
+        let mut meta = path.as_ref().as_os_str().to_owned();
+        meta.push(".meta");
+
+        meta.into()
+    }
+
+    pub fn is_meta_path<P: AsRef<Path>>(path: P) -> bool {
+        path.as_ref().extension().is_some_and(|ext| ext == "meta")
+    }
+
+    pub fn from_toml(source: &str) -> Result<Self, TomlError> {
+        from_toml_str(source)
+    }
+
+    pub fn to_toml(&self) -> String {
+        to_toml_string(&self.to_value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let meta = AssetMeta::from_toml(
+            "[loader]\nsampler = \"linear\"\natlas_dimensions = { width = 4, height = 1 }\n",
+        )
+        .unwrap();
+        let loader = meta.loader.as_ref().unwrap();
+
+        assert_eq!(loader.get("sampler"), Some(&Value::String("linear".into())));
+        assert!(meta.processor.is_none());
+        assert_eq!(AssetMeta::from_toml(&meta.to_toml()).unwrap(), meta);
+    }
+
+    #[test]
+    fn meta_path() {
+        assert_eq!(
+            AssetMeta::path("res/player.png"),
+            PathBuf::from("res/player.png.meta")
+        );
+        assert!(AssetMeta::is_meta_path("res/player.png.meta"));
+    }
+}
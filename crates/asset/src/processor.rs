@@ -0,0 +1,422 @@
+use crate::{
+    meta::{AssetMeta, ProcessedInfo},
+    AssetMode, AssetServer,
+};
+use app::prelude::*;
+use cereal::{FromValue, ToValue, Value, ValueError};
+use ecs::{Res, WinnyResource};
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    path::{Path, PathBuf},
+};
+use util::tracing::{error, info, trace};
+
+/// Default directory of processed artifacts.
+pub const IMPORTED_ASSETS: &str = "imported_assets";
+
+/// Processes the source directory into [`IMPORTED_ASSETS`] on start up and loads [`Asset`]s from
+/// the processed artifacts.
+///
+/// Only files that changed since the last run, or whose settings changed, are processed again.
+/// Files without an [`AssetProcessor`] are copied. Hot reloaded files are processed before they
+/// are reloaded.
+///
+/// Must be added after the [`crate::AssetLoaderPlugin`].
+///
+/// [`Asset`]: crate::Asset
+#[derive(Debug)]
+pub struct AssetProcessorPlugin {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+}
+
+impl Default for AssetProcessorPlugin {
+    fn default() -> Self {
+        Self {
+            source: "res".into(),
+            destination: IMPORTED_ASSETS.into(),
+        }
+    }
+}
+
+impl Plugin for AssetProcessorPlugin {
+    fn build(&mut self, app: &mut App) {
+        app.world()
+            .resource::<AssetServer>()
+            .set_mode(AssetMode::Processed(self.destination.clone()));
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let source = self.source.clone();
+            let destination = self.destination.clone();
+            app.add_systems(
+                AppSchedule::PreStartUp,
+                move |processors: Res<AssetProcessors>| {
+                    processors.process_dir(&source, &destination);
+                },
+            );
+        }
+    }
+}
+
+/// Companion to an [`AssetLoader`]. Transforms a source file into an artifact that the
+/// [`AssetLoader`] registered for its extension reads at runtime.
+///
+/// Settings are read from the `[processor]` table of the source's [`AssetMeta`] file.
+///
+/// [`AssetLoader`]: crate::AssetLoader
+pub trait AssetProcessor: Send + Sync + 'static {
+    type Settings: Default + ToValue + FromValue + Send + Sync;
+
+    fn process(
+        &self,
+        bytes: Vec<u8>,
+        settings: &Self::Settings,
+        ext: &str,
+    ) -> Result<Vec<u8>, ProcessError>;
+    fn extensions(&self) -> &'static [&'static str];
+    /// Increment to invalidate the artifacts of previous versions.
+    fn version(&self) -> u32 {
+        0
+    }
+}
+
+trait ErasedAssetProcessor: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+    fn version(&self) -> u32;
+    fn settings(&self, settings: Option<&Value>) -> Result<Value, ValueError>;
+    fn process(&self, bytes: Vec<u8>, settings: &Value, ext: &str)
+        -> Result<Vec<u8>, ProcessError>;
+}
+
+impl<P: AssetProcessor> ErasedAssetProcessor for P {
+    fn name(&self) -> &'static str {
+        std::any::type_name::<P>()
+    }
+
+    fn version(&self) -> u32 {
+        AssetProcessor::version(self)
+    }
+
+    fn settings(&self, settings: Option<&Value>) -> Result<Value, ValueError> {
+        Ok(match settings {
+            Some(settings) => P::Settings::from_value(settings)?,
+            None => P::Settings::default(),
+        }
+        .to_value())
+    }
+
+    fn process(
+        &self,
+        bytes: Vec<u8>,
+        settings: &Value,
+        ext: &str,
+    ) -> Result<Vec<u8>, ProcessError> {
+        let settings = P::Settings::from_value(settings)?;
+        AssetProcessor::process(self, bytes, &settings, ext)
+    }
+}
+
+/// Result of [`AssetProcessors::process_file`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessResult {
+    Processed,
+    /// The artifact is up to date.
+    Unchanged,
+    /// No [`AssetProcessor`] is registered for the file extension.
+    Copied,
+}
+
+/// Collection of [`AssetProcessor`]s.
+///
+/// Created by the [`crate::AssetLoaderPlugin`]. Processors are added with
+/// [`crate::AssetApp::register_asset_processor`].
+#[derive(WinnyResource, Default)]
+pub struct AssetProcessors {
+    processors: Vec<Box<dyn ErasedAssetProcessor>>,
+    ext_to_processor: HashMap<&'static str, usize>,
+}
+
+impl Debug for AssetProcessors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AssetProcessors")
+            .field("ext_to_processor", &self.ext_to_processor)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AssetProcessors {
+    pub(crate) fn register_processor(&mut self, processor: impl AssetProcessor) {
+        for ext in processor.extensions().iter() {
+            self.ext_to_processor.insert(ext, self.processors.len());
+        }
+        self.processors.push(Box::new(processor));
+    }
+
+    /// Processes every file in `source`, recursively. Artifacts are written to `destination`,
+    /// keeping the full `source` path.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn process_dir<P: AsRef<Path>, D: AsRef<Path>>(&self, source: P, destination: D) {
+        let mut files = Vec::new();
+        if let Err(e) = collect_files(source.as_ref(), &mut files) {
+            error!(
+                "Failed to read asset directory {:?}: {}",
+                source.as_ref(),
+                e
+            );
+            return;
+        }
+
+        let mut processed = 0;
+        for file in files.iter() {
+            match self.process_file(file, destination.as_ref()) {
+                Ok(ProcessResult::Processed) => processed += 1,
+                Ok(ProcessResult::Unchanged | ProcessResult::Copied) => (),
+                Err(e) => error!("Failed to process asset {:?}: {}", file, e),
+            }
+        }
+
+        info!(
+            "Processed {} of {} assets in {:?}",
+            processed,
+            files.len(),
+            source.as_ref()
+        );
+    }
+
+    /// Processes `source` into `destination`, skipping the work if the artifact's hash matches.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn process_file<P: AsRef<Path>, D: AsRef<Path>>(
+        &self,
+        source: P,
+        destination: D,
+    ) -> Result<ProcessResult, ProcessError> {
+        let source = source.as_ref();
+        let artifact = destination.as_ref().join(
+            source
+                .components()
+                .filter(|c| matches!(c, std::path::Component::Normal(_)))
+                .collect::<PathBuf>(),
+        );
+        let bytes = std::fs::read(source)?;
+        let meta = match std::fs::read_to_string(AssetMeta::path(source)) {
+            Ok(meta) => {
+                AssetMeta::from_toml(&meta).map_err(|e| ProcessError::Meta(e.to_string()))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => AssetMeta::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        if let Some(parent) = artifact.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let ext = source
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default();
+        let Some(processor) = self.ext_to_processor.get(ext) else {
+            if meta != AssetMeta::default() {
+                let meta = meta.to_toml();
+                let meta_path = AssetMeta::path(&artifact);
+                // Unchanged files are not rewritten, so that file watchers are not triggered
+                if std::fs::read_to_string(&meta_path).ok().as_deref() != Some(meta.as_str()) {
+                    std::fs::write(meta_path, meta)?;
+                }
+            }
+            if std::fs::read(&artifact).is_ok_and(|artifact| artifact == bytes) {
+                return Ok(ProcessResult::Unchanged);
+            }
+            std::fs::write(&artifact, bytes)?;
+
+            return Ok(ProcessResult::Copied);
+        };
+        let processor = &self.processors[*processor];
+
+        let settings = processor.settings(meta.processor.as_ref())?;
+        let hash = content_hash(&bytes, &settings, processor.name(), processor.version());
+        let processed = std::fs::read_to_string(AssetMeta::path(&artifact))
+            .ok()
+            .and_then(|meta| AssetMeta::from_toml(&meta).ok())
+            .and_then(|meta| meta.processed);
+        if artifact.exists() && processed.is_some_and(|processed| processed.hash == hash) {
+            trace!("Asset {:?} is up to date", source);
+            return Ok(ProcessResult::Unchanged);
+        }
+
+        let processed = processor.process(bytes, &settings, ext)?;
+        std::fs::write(&artifact, processed)?;
+        let meta = AssetMeta {
+            loader: meta.loader,
+            processor: Some(settings),
+            processed: Some(ProcessedInfo {
+                processor: processor.name().to_owned(),
+                hash,
+            }),
+        };
+        std::fs::write(AssetMeta::path(&artifact), meta.to_toml())?;
+        trace!("Processed asset {:?} into {:?}", source, artifact);
+
+        Ok(ProcessResult::Processed)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if !AssetMeta::is_meta_path(&path) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Hex encoded hash of everything that affects a processed artifact.
+fn content_hash(bytes: &[u8], settings: &Value, processor: &str, version: u32) -> String {
+    let mut hashed = bytes.to_vec();
+    hashed.extend_from_slice(crate::toml::to_toml_string(settings).as_bytes());
+    hashed.extend_from_slice(processor.as_bytes());
+    hashed.extend_from_slice(&version.to_le_bytes());
+
+    format!("{:016x}", fxhash::hash64(&hashed))
+}
+
+#[derive(Debug)]
+pub enum ProcessError {
+    Io(std::io::Error),
+    /// The `.meta` file could not be read.
+    Meta(String),
+    Settings(ValueError),
+    /// The [`AssetProcessor`] rejected the source.
+    Failed(String),
+}
+
+impl From<std::io::Error> for ProcessError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<ValueError> for ProcessError {
+    fn from(value: ValueError) -> Self {
+        Self::Settings(value)
+    }
+}
+
+impl Display for ProcessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "IO error: {e}"),
+            Self::Meta(e) => write!(f, "Invalid meta file: {e}"),
+            Self::Settings(e) => write!(f, "Invalid processor settings: {e}"),
+            Self::Failed(e) => write!(f, "Processing failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cereal::{WinnyFromValue, WinnyToValue};
+
+    #[derive(WinnyToValue, WinnyFromValue, Debug, Default)]
+    struct RepeatSettings {
+        count: usize,
+    }
+
+    struct RepeatProcessor;
+
+    impl AssetProcessor for RepeatProcessor {
+        type Settings = RepeatSettings;
+
+        fn process(
+            &self,
+            bytes: Vec<u8>,
+            settings: &Self::Settings,
+            _ext: &str,
+        ) -> Result<Vec<u8>, ProcessError> {
+            Ok(bytes.repeat(settings.count))
+        }
+
+        fn extensions(&self) -> &'static [&'static str] {
+            &["txt"]
+        }
+    }
+
+    #[test]
+    fn incremental_processing() {
+        let root = std::env::temp_dir().join(format!("winny_processor_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let source = root.join("a.txt");
+        let destination = root.join("imported");
+        let artifact = destination.join(source.strip_prefix("/").unwrap());
+        std::fs::write(&source, "ab").unwrap();
+        std::fs::write(AssetMeta::path(&source), "[processor]\ncount = 2\n").unwrap();
+
+        let mut processors = AssetProcessors::default();
+        processors.register_processor(RepeatProcessor);
+
+        let process = || processors.process_file(&source, &destination).unwrap();
+        assert_eq!(process(), ProcessResult::Processed);
+        assert_eq!(std::fs::read_to_string(&artifact).unwrap(), "abab");
+        assert_eq!(process(), ProcessResult::Unchanged);
+
+        std::fs::write(AssetMeta::path(&source), "[processor]\ncount = 3\n").unwrap();
+        assert_eq!(process(), ProcessResult::Processed);
+        assert_eq!(std::fs::read_to_string(&artifact).unwrap(), "ababab");
+
+        let meta = std::fs::read_to_string(AssetMeta::path(&artifact)).unwrap();
+        let meta = AssetMeta::from_toml(&meta).unwrap();
+        assert_eq!(
+            meta.processor.unwrap().get("count"),
+            Some(&Value::Integer(3))
+        );
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn copy_keeps_unchanged_meta() {
+        let root = std::env::temp_dir().join(format!("winny_copy_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let source = root.join("a.bin");
+        let destination = root.join("imported");
+        let artifact = destination.join(source.strip_prefix("/").unwrap());
+        std::fs::write(&source, "ab").unwrap();
+        std::fs::write(AssetMeta::path(&source), "[loader]\nsize = 2\n").unwrap();
+
+        let processors = AssetProcessors::default();
+        let process = || processors.process_file(&source, &destination).unwrap();
+        assert_eq!(process(), ProcessResult::Copied);
+
+        let meta = std::fs::File::options()
+            .write(true)
+            .open(AssetMeta::path(&artifact))
+            .unwrap();
+        meta.set_modified(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap();
+        let modified = || {
+            std::fs::metadata(AssetMeta::path(&artifact))
+                .unwrap()
+                .modified()
+                .unwrap()
+        };
+        assert_eq!(process(), ProcessResult::Unchanged);
+        assert_eq!(modified(), std::time::SystemTime::UNIX_EPOCH);
+
+        std::fs::write(AssetMeta::path(&source), "[loader]\nsize = 3\n").unwrap();
+        assert_eq!(process(), ProcessResult::Unchanged);
+        assert_ne!(modified(), std::time::SystemTime::UNIX_EPOCH);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
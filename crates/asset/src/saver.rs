@@ -0,0 +1,148 @@
+use crate::{Asset, AssetServer};
+use cereal::{Serialize, Serializer};
+use std::{
+    fmt::{Debug, Display},
+    marker::PhantomData,
+    path::Path,
+};
+
+/// Writes an [`Asset`] to bytes. The counterpart of [`crate::AssetLoader`].
+///
+/// Registered with [`crate::AssetApp::register_asset_saver`] and used by [`AssetServer::save`].
+pub trait AssetSaver: Send + Sync + 'static {
+    type Asset: Asset;
+
+    fn save(&self, asset: &Self::Asset, ext: &str) -> Result<Vec<u8>, AssetSaverError>;
+    fn extensions(&self) -> &'static [&'static str];
+}
+
+/// Saves an [`Asset`] with its [`Serialize`] implementation, to be read back with
+/// [`cereal::Deserializer`].
+pub struct CerealAssetSaver<A> {
+    extensions: &'static [&'static str],
+    _phantom: PhantomData<fn() -> A>,
+}
+
+impl<A> Debug for CerealAssetSaver<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CerealAssetSaver")
+            .field("extensions", &self.extensions)
+            .finish()
+    }
+}
+
+impl<A> CerealAssetSaver<A> {
+    pub fn new(extensions: &'static [&'static str]) -> Self {
+        Self {
+            extensions,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<A: Asset + Serialize> AssetSaver for CerealAssetSaver<A> {
+    type Asset = A;
+
+    fn save(&self, asset: &Self::Asset, _ext: &str) -> Result<Vec<u8>, AssetSaverError> {
+        let mut bytes = Vec::new();
+        asset.serialize(&mut Serializer::new(&mut bytes));
+
+        Ok(bytes)
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        self.extensions
+    }
+}
+
+#[derive(Debug)]
+pub enum AssetSaverError {
+    UnsupportedFileExtension,
+    /// The [`Asset`] is not loaded, or was removed before it was saved.
+    AssetNotLoaded,
+    /// Files can only be written to the default [`crate::AssetSource`].
+    ReadOnlySource,
+    Io(std::io::Error),
+    Failed(String),
+}
+
+impl From<std::io::Error> for AssetSaverError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl Display for AssetSaverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedFileExtension => write!(f, "File extension is not supported"),
+            Self::AssetNotLoaded => write!(f, "Asset is not loaded"),
+            Self::ReadOnlySource => write!(f, "Asset source is read only"),
+            Self::Io(e) => write!(f, "IO error: {e}"),
+            Self::Failed(e) => write!(f, "Asset saver failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for AssetSaverError {}
+
+/// Writes `asset` to `path` with `saver`. The resulting file event will not reload the asset.
+pub(crate) fn save_asset<S: AssetSaver>(
+    saver: &S,
+    server: &AssetServer,
+    asset: Option<&S::Asset>,
+    path: &Path,
+) -> Result<(), AssetSaverError> {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| saver.extensions().contains(ext))
+        .ok_or(AssetSaverError::UnsupportedFileExtension)?;
+    let asset = asset.ok_or(AssetSaverError::AssetNotLoaded)?;
+    let write_path = server
+        .write_path(path)
+        .ok_or(AssetSaverError::ReadOnlySource)?;
+
+    let bytes = saver.save(asset, ext)?;
+    if let Some(parent) = write_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&write_path, &bytes)?;
+    server.saved(&write_path, &bytes);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Asset for u32 {}
+
+    #[test]
+    fn save_suppresses_reload() {
+        let dir = std::env::temp_dir().join("winny_saver");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("value.bin");
+
+        let server = AssetServer::default();
+        let saver = CerealAssetSaver::<u32>::new(&["bin"]);
+        save_asset(&saver, &server, Some(&7), &path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), 7u32.to_le_bytes());
+        assert!(server.is_unchanged_save(&path));
+
+        assert!(matches!(
+            save_asset(&saver, &server, Some(&7), &dir.join("value.txt")),
+            Err(AssetSaverError::UnsupportedFileExtension)
+        ));
+        assert!(matches!(
+            save_asset(&saver, &server, None, &path),
+            Err(AssetSaverError::AssetNotLoaded)
+        ));
+
+        std::fs::write(&path, [0; 4]).unwrap();
+        assert!(!server.is_unchanged_save(&path));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
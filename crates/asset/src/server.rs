@@ -1,18 +1,23 @@
 use crate::{
-    handle::{ErasedHandle, Handle},
-    Asset, AssetEvent, AssetId, AssetLoader, ErasedAssetLoader,
+    folder::LoadedFolder,
+    handle::{ErasedHandle, Handle, StrongHandle, UntypedHandle},
+    source::{
+        parse_asset_path, AssetFilePath, AssetReader, AssetSource, AssetSourceId, AssetSources,
+    },
+    task::{AssetTaskPools, LoadPriority, LoadToken, WorkerThreads},
+    Asset, AssetEvent, AssetId, ErasedAssetLoader, LoadContext, LoadRequest, SettingsOverride,
 };
 use crossbeam_channel::{Receiver, Sender};
 use ecs::WinnyResource;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::{
     any::TypeId,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Debug,
-    path::Path,
+    path::{Component, Path, PathBuf},
     sync::{
         atomic::{AtomicU32, Ordering},
-        Arc,
+        Arc, Weak,
     },
 };
 
@@ -27,27 +32,249 @@ pub struct AssetServer {
 impl AssetServer {
     pub(crate) fn register_loader<A: Asset>(
         &self,
-        loader: impl AssetLoader,
+        loader: impl ErasedAssetLoader,
+        extensions: &'static [&'static str],
         result: Sender<AssetEvent>,
         handler: Arc<AssetHandleCreator>,
     ) {
         self.loaders
             .write()
-            .register_loader::<A>(loader, result, handler);
+            .register_loader::<A>(loader, extensions, result, handler);
     }
 
     pub(crate) fn reload<P: AsRef<Path>>(&self, path: P) {
-        self.loaders.write().reload(path);
+        self.loaders.write().reload(path, self);
     }
 
+    /// Loads an [`Asset`] with the [`AssetLoader::Settings`] of its `<path>.meta` file, if it
+    /// exists.
+    ///
+    /// If the path was already requested, its [`Handle`] is returned, even if it failed to load.
+    /// See [`AssetServer::retry`].
     pub fn load<A: Asset, P: AsRef<Path>>(&self, path: P) -> Handle<A> {
-        self.loaders.write().load::<A, P>(path)
+        self.loaders.write().load::<A, P>(path, self, None)
+    }
+
+    /// Loads an [`Asset`], modifying the [`AssetLoader::Settings`] after they are read from the
+    /// `<path>.meta` file. The override is applied again when the [`Asset`] is reloaded.
+    ///
+    /// `S` must be the settings type of the [`AssetLoader`] for `A`. If the path is already
+    /// loaded, the existing [`Handle`] is returned and the settings are ignored.
+    pub fn load_with_settings<A: Asset, S: Send + Sync + 'static, P: AsRef<Path>>(
+        &self,
+        path: P,
+        settings: impl Fn(&mut S) + Send + Sync + 'static,
+    ) -> Handle<A> {
+        let settings: SettingsOverride =
+            Arc::new(
+                move |loader_settings| match loader_settings.downcast_mut::<S>() {
+                    Some(loader_settings) => settings(loader_settings),
+                    None => util::tracing::error!(
+                        "Settings type [{}] does not match the AssetLoader settings for [{}]",
+                        std::any::type_name::<S>(),
+                        std::any::type_name::<A>(),
+                    ),
+                },
+            );
+
+        self.loaders
+            .write()
+            .load::<A, P>(path, self, Some(settings))
+    }
+
+    /// Loads every file in the folder at `path`, and its subfolders, for which an
+    /// [`crate::AssetLoader`] is registered. Files with several loaders use the first
+    /// registered.
+    ///
+    /// The [`LoadedFolder`] is loaded with its dependencies once every file is loaded. Folders
+    /// can only be listed by [`AssetReader`]s which support it, which excludes HTTP on wasm.
+    pub fn load_folder<P: AsRef<Path>>(&self, path: P) -> Handle<LoadedFolder> {
+        self.load(path)
+    }
+
+    /// Loads every file listed in the manifest at `path` as a [`LoadedFolder`]. Manifests have
+    /// the [`crate::MANIFEST_EXTENSION`] and list one asset path per line, such as
+    /// `"sprites/player.png"` or `"embedded://shaders/sprite.wgsl"`. Blank lines and lines
+    /// starting with `#` are ignored.
+    ///
+    /// Files without a registered [`crate::AssetLoader`] are skipped, as with
+    /// [`AssetServer::load_folder`].
+    pub fn load_manifest<P: AsRef<Path>>(&self, path: P) -> Handle<LoadedFolder> {
+        self.load(path)
+    }
+
+    /// Loads a file with the first registered [`crate::AssetLoader`] for its extension. Returns
+    /// [`None`] if there is none.
+    pub fn load_untyped<P: AsRef<Path>>(&self, path: P) -> Option<UntypedHandle> {
+        self.loaders.write().load_untyped(path, self)
+    }
+
+    /// Sets the order in which queued loads start. Loads which already started are not affected.
+    pub fn set_priority<A: Asset>(&self, handle: &Handle<A>, priority: LoadPriority) {
+        if let Some(info) = self.loaders.read().infos.get(&handle.into()) {
+            info.token.set_priority(priority);
+        }
+    }
+
+    /// Sets the number of worker threads used to load [`Asset`]s. Must be set before the first
+    /// [`Asset`] is loaded.
+    pub fn set_worker_threads(&self, threads: WorkerThreads) {
+        let mut loaders = self.loaders.write();
+        if loaders.pools.is_some() {
+            util::tracing::warn!("Asset worker threads are already running");
+        }
+        loaders.worker_threads = threads;
+    }
+
+    /// Loads an [`Asset`] which failed to load again, keeping its [`Handle`]. Does nothing if
+    /// the [`Asset`] did not fail.
+    ///
+    /// The result is sent as an [`crate::AssetLoaderEvent`], as with [`AssetServer::load`].
+    pub fn retry<A: Asset>(&self, handle: &Handle<A>) {
+        self.loaders.write().retry(handle.into(), self);
+    }
+
+    /// Saves an [`Asset`] to `path` with the [`crate::AssetSaver`] registered for `A`, at the end
+    /// of the frame. Errors are logged.
+    ///
+    /// Only the default [`AssetSource`] is writable. The file event caused by the save does not
+    /// reload the [`Asset`].
+    pub fn save<A: Asset, P: AsRef<Path>>(&self, handle: &Handle<A>, path: P) {
+        self.loaders
+            .write()
+            .saves
+            .push((TypeId::of::<A>(), handle.id(), path.as_ref().to_owned()));
+    }
+
+    pub(crate) fn take_saves<A: Asset>(&self) -> Vec<(AssetId, PathBuf)> {
+        let mut loaders = self.loaders.write();
+        let (saves, rest) = std::mem::take(&mut loaders.saves)
+            .into_iter()
+            .partition(|(type_id, _, _)| *type_id == TypeId::of::<A>());
+        loaders.saves = rest;
+
+        saves.into_iter().map(|(_, id, path)| (id, path)).collect()
+    }
+
+    /// Returns the path of the file written for an asset path, or `None` if the [`AssetSource`]
+    /// is not writable.
+    pub(crate) fn write_path(&self, path: &Path) -> Option<PathBuf> {
+        let loaders = self.loaders.read();
+        let (source_id, path) = parse_asset_path(path.to_str()?);
+        if source_id != AssetSourceId::Default {
+            return None;
+        }
+
+        Some(if loaders.path_prefix.is_empty() {
+            path.into()
+        } else {
+            Path::new(&loaders.path_prefix).join(path)
+        })
+    }
+
+    /// Records the content hash of a file written by [`AssetServer::save`].
+    pub(crate) fn saved(&self, path: &Path, bytes: &[u8]) {
+        self.loaders
+            .write()
+            .saved
+            .insert(normalize_path(path), fxhash::hash64(bytes));
+    }
+
+    /// Returns true if the file has not changed since it was written by [`AssetServer::save`].
+    ///
+    /// Contents are compared, since modified times may be too coarse to tell writes apart.
+    pub(crate) fn is_unchanged_save(&self, path: &Path) -> bool {
+        let key = normalize_path(path);
+        let mut loaders = self.loaders.write();
+        let Some(saved) = loaders.saved.get(&key) else {
+            return false;
+        };
+
+        match std::fs::read(path) {
+            Ok(bytes) if fxhash::hash64(&bytes) == *saved => true,
+            _ => {
+                loaders.saved.remove(&key);
+                false
+            }
+        }
+    }
+
+    /// Returns the [`LoadState`] of a single [`Asset`], ignoring its dependencies.
+    pub fn load_state<A: Asset>(&self, handle: &Handle<A>) -> LoadState {
+        self.loaders.read().load_state(&handle.into())
+    }
+
+    /// Returns the combined [`LoadState`] of an [`Asset`] and all of its dependencies,
+    /// requested through a [`crate::LoadContext`].
+    ///
+    /// A failed dependency results in [`LoadState::Failed`].
+    pub fn recursive_dependency_load_state<A: Asset>(&self, handle: &Handle<A>) -> LoadState {
+        self.erased_recursive_dependency_load_state(&handle.into())
+    }
+
+    pub fn erased_recursive_dependency_load_state(&self, handle: &ErasedHandle) -> LoadState {
+        self.loaders
+            .read()
+            .recursive_dependency_load_state(handle, &mut HashSet::new())
+    }
+
+    pub(crate) fn loaded(&self, handle: ErasedHandle, dependencies: Vec<UntypedHandle>) {
+        if let Some(info) = self.loaders.write().infos.get_mut(&handle) {
+            info.state = LoadState::Loaded;
+            info.dependencies = dependencies;
+        }
+    }
+
+    /// Releases an [`Asset`] that is no longer referenced by any strong [`Handle`], clearing it
+    /// from the path cache.
+    ///
+    /// Returns false if the [`Asset`] was referenced again before being released.
+    pub(crate) fn release(&self, handle: ErasedHandle, handler: &AssetHandleCreator) -> bool {
+        let mut loaders = self.loaders.write();
+        if handler.is_referenced(handle.id()) {
+            return false;
+        }
+
+        handler.forget(handle.id());
+        loaders.loaded_assets.retain(|_, h| *h != handle);
+        if let Some(info) = loaders.infos.remove(&handle) {
+            info.token.cancel();
+        }
+
+        true
+    }
+
+    pub(crate) fn failed(&self, handle: ErasedHandle) {
+        if let Some(info) = self.loaders.write().infos.get_mut(&handle) {
+            info.state = LoadState::Failed;
+        }
     }
 
     pub fn set_prefix<P: AsRef<Path>>(&self, path: P) {
         self.loaders.write().set_prefix(path);
     }
 
+    /// Adds an [`AssetReader`] on top of an [`AssetSource`], creating the source if it does not
+    /// exist. Files are read from the last added reader that contains them.
+    ///
+    /// The default source reads from the working directory. Paths of the form `"name://path"`
+    /// are read from the source named `name`.
+    pub fn add_reader(&self, source: impl Into<AssetSourceId>, reader: impl AssetReader) {
+        self.loaders
+            .write()
+            .sources
+            .add_reader(source.into(), reader);
+    }
+
+    /// Sets where [`Asset`]s are loaded from. See [`AssetMode`].
+    pub fn set_mode(&self, mode: AssetMode) {
+        self.loaders.write().mode = mode;
+    }
+
+    pub fn mode(&self) -> AssetMode {
+        self.loaders.read().mode.clone()
+    }
+
     pub fn remove<A: Asset, P: AsRef<Path>>(&self, path: P) {
         self.loaders
             .write()
@@ -56,6 +283,47 @@ impl AssetServer {
     }
 }
 
+/// Source of the files read by the [`AssetServer`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum AssetMode {
+    /// Load source files directly.
+    #[default]
+    Unprocessed,
+    /// Load the artifacts written by the [`crate::processor::AssetProcessors`] into a directory,
+    /// such as [`crate::processor::IMPORTED_ASSETS`].
+    Processed(PathBuf),
+}
+
+/// Load progress of an [`Asset`] requested from the [`AssetServer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadState {
+    NotLoaded,
+    Loading,
+    Loaded,
+    Failed,
+}
+
+struct AssetInfo {
+    /// Path passed to [`AssetServer::load`].
+    path: String,
+    state: LoadState,
+    /// Strong, so that dependencies stay loaded while the [`Asset`] is.
+    dependencies: Vec<UntypedHandle>,
+    settings: Option<SettingsOverride>,
+    /// Of the latest load.
+    token: Arc<LoadToken>,
+}
+
+impl Debug for AssetInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AssetInfo")
+            .field("path", &self.path)
+            .field("state", &self.state)
+            .field("dependencies", &self.dependencies)
+            .finish_non_exhaustive()
+    }
+}
+
 /// Type-erased storage for an [`AssetLoader`].
 struct InternalAssetLoader {
     loader: Box<dyn ErasedAssetLoader>,
@@ -74,7 +342,7 @@ impl Debug for InternalAssetLoader {
 
 impl InternalAssetLoader {
     pub fn new(
-        loader: impl AssetLoader,
+        loader: impl ErasedAssetLoader,
         result: Sender<AssetEvent>,
         handler: Arc<AssetHandleCreator>,
     ) -> Self {
@@ -87,38 +355,86 @@ impl InternalAssetLoader {
 }
 
 /// Generates handles atomically for an [`Assets`] resource.
+///
+/// Reference counts strong [`Handle`]s. The ids of dropped strong [`Handle`]s are collected with
+/// [`AssetHandleCreator::dropped`].
 #[derive(Debug)]
 pub struct AssetHandleCreator {
     next_id: AtomicU32,
-    freed_rx: Receiver<u32>,
-    freed_tx: Sender<u32>,
+    freed_rx: Receiver<AssetId>,
+    freed_tx: Sender<AssetId>,
+    dropped_rx: Receiver<AssetId>,
+    dropped_tx: Sender<AssetId>,
+    strong_handles: Mutex<HashMap<AssetId, Weak<StrongHandle>>>,
+    type_id: TypeId,
 }
 
-impl Default for AssetHandleCreator {
-    fn default() -> Self {
+impl AssetHandleCreator {
+    pub fn new<A: Asset>() -> Self {
         let (freed_tx, freed_rx) = crossbeam_channel::unbounded();
+        let (dropped_tx, dropped_rx) = crossbeam_channel::unbounded();
 
         Self {
             next_id: AtomicU32::new(0),
             freed_rx,
             freed_tx,
+            dropped_rx,
+            dropped_tx,
+            strong_handles: Mutex::new(HashMap::new()),
+            type_id: TypeId::of::<A>(),
         }
     }
-}
 
-impl AssetHandleCreator {
+    /// Returns a strong [`Handle`], sharing the reference count of any living strong [`Handle`]
+    /// with the same id.
+    pub fn strong<A: Asset>(&self, handle: ErasedHandle) -> Handle<A> {
+        Handle::from_strong(self.strong_handle(handle.id()))
+    }
+
+    /// Returns a strong [`UntypedHandle`]. See [`AssetHandleCreator::strong`].
+    pub fn strong_untyped(&self, handle: ErasedHandle) -> UntypedHandle {
+        UntypedHandle::from_strong(self.type_id, self.strong_handle(handle.id()))
+    }
+
+    fn strong_handle(&self, id: AssetId) -> Arc<StrongHandle> {
+        let mut strong_handles = self.strong_handles.lock();
+        if let Some(strong) = strong_handles.get(&id).and_then(|strong| strong.upgrade()) {
+            return strong;
+        }
+
+        let strong = Arc::new(StrongHandle::new(id, self.dropped_tx.clone()));
+        strong_handles.insert(id, Arc::downgrade(&strong));
+
+        strong
+    }
+
+    pub fn is_referenced(&self, id: AssetId) -> bool {
+        self.strong_handles
+            .lock()
+            .get(&id)
+            .is_some_and(|strong| strong.strong_count() > 0)
+    }
+
+    /// Ids of assets whose last strong [`Handle`] was dropped since the last call.
+    pub fn dropped(&self) -> HashSet<AssetId> {
+        self.dropped_rx.try_iter().collect()
+    }
+
+    pub(crate) fn forget(&self, id: AssetId) {
+        self.strong_handles.lock().remove(&id);
+    }
+
     pub fn reserve(&self) -> ErasedHandle {
-        let index = if let Ok(index) = self.freed_rx.try_recv() {
-            index
-        } else {
-            self.next_id.fetch_add(1, Ordering::Relaxed)
+        let id = match self.freed_rx.try_recv() {
+            Ok(freed) => freed.next_generation(),
+            Err(_) => AssetId::new(self.next_id.fetch_add(1, Ordering::Relaxed)),
         };
 
-        ErasedHandle::new(AssetId(index))
+        ErasedHandle::new(id, self.type_id)
     }
 
     pub fn remove(&self, id: AssetId) {
-        if let Err(e) = self.freed_tx.send(id.0) {
+        if let Err(e) = self.freed_tx.send(id) {
             util::tracing::error!("Freed index tx error: {e}");
         }
     }
@@ -128,22 +444,39 @@ impl AssetHandleCreator {
 struct AssetLoaders {
     loaders: Vec<InternalAssetLoader>,
     type_to_loader: HashMap<TypeId, usize>,
+    /// First registered loader for each extension.
     ext_to_loader: HashMap<&'static str, usize>,
     loaded_assets: HashMap<String, ErasedHandle>,
+    infos: HashMap<ErasedHandle, AssetInfo>,
     path_prefix: String,
+    mode: AssetMode,
+    sources: AssetSources,
+    saves: Vec<(TypeId, AssetId, PathBuf)>,
+    /// Content hashes of files written by [`AssetServer::save`].
+    saved: HashMap<PathBuf, u64>,
+    worker_threads: WorkerThreads,
+    pools: Option<Arc<AssetTaskPools>>,
+}
+
+/// Removes `.` components, so that watcher and save paths compare equal.
+fn normalize_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
 }
 
 impl AssetLoaders {
     pub fn register_loader<A: Asset>(
         &mut self,
-        loader: impl AssetLoader,
+        loader: impl ErasedAssetLoader,
+        extensions: &'static [&'static str],
         result: Sender<AssetEvent>,
         handler: Arc<AssetHandleCreator>,
     ) {
         self.type_to_loader
             .insert(TypeId::of::<A>(), self.loaders.len());
-        for ext in loader.extensions().iter() {
-            self.ext_to_loader.insert(ext, self.loaders.len());
+        for ext in extensions.iter() {
+            self.ext_to_loader.entry(ext).or_insert(self.loaders.len());
         }
         self.loaders
             .push(InternalAssetLoader::new(loader, result, handler));
@@ -153,71 +486,238 @@ impl AssetLoaders {
         self.path_prefix = path.as_ref().to_str().unwrap().to_string();
     }
 
-    pub fn load<A: Asset, P: AsRef<Path>>(&mut self, path: P) -> Handle<A> {
-        if let Some(handle) = self.loaded_assets.get(path.as_ref().to_str().unwrap()) {
-            return (*handle).into();
+    /// Returns the [`AssetSource`] and the path within it for an asset path.
+    ///
+    /// The path prefix and [`AssetMode`] only apply to the default source.
+    fn read_path(&self, path: &str) -> Option<(AssetSource, String)> {
+        let (source_id, path) = parse_asset_path(path);
+        let source = self.sources.get(&source_id)?.clone();
+        if source_id != AssetSourceId::Default {
+            return Some((source, path.to_owned()));
         }
 
-        let file_ext = path
-            .as_ref()
-            .extension()
-            .expect("file extension")
-            .to_owned();
-        let mut relative_path = path.as_ref().to_str().unwrap().to_owned();
+        let mut path = path.to_owned();
         if !self.path_prefix.is_empty() {
-            relative_path = format!("{}/{}", self.path_prefix, relative_path);
+            path = format!("{}/{}", self.path_prefix, path);
         }
 
-        let asset_type_id = TypeId::of::<A>();
-        match self.type_to_loader.get(&asset_type_id) {
-            Some(loader) => {
-                let handle = self.loaders[*loader].loader.load(
-                    &self.loaders[*loader].handler,
-                    self.loaders[*loader].result.clone(),
-                    relative_path,
-                    file_ext.to_str().unwrap().to_owned(),
-                );
-                self.loaded_assets
-                    .insert(path.as_ref().to_str().unwrap().to_owned(), handle);
-
-                handle.into()
-            }
-            None => {
-                util::tracing::error!(
-                    "Could not find AssetLoader for file: {:?} of type: {:?}",
-                    relative_path,
-                    std::any::type_name::<A>()
-                );
-                Handle::dangling()
+        Some(match &self.mode {
+            AssetMode::Unprocessed => (source, path),
+            AssetMode::Processed(dir) => (source, format!("{}/{}", dir.to_str().unwrap(), path)),
+        })
+    }
+
+    pub fn load<A: Asset, P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        server: &AssetServer,
+        settings: Option<SettingsOverride>,
+    ) -> Handle<A> {
+        let path = path.as_ref().to_str().unwrap();
+        let Some(loader) = self.type_to_loader.get(&TypeId::of::<A>()).copied() else {
+            util::tracing::error!(
+                "Could not find AssetLoader for file: {:?} of type: {:?}",
+                path,
+                std::any::type_name::<A>()
+            );
+            return Handle::dangling();
+        };
+
+        match self.load_with_loader(loader, path, server, settings) {
+            Some(handle) => self.loaders[loader].handler.strong(handle),
+            None => Handle::dangling(),
+        }
+    }
+
+    pub fn load_untyped<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        server: &AssetServer,
+    ) -> Option<UntypedHandle> {
+        let path = path.as_ref().to_str().unwrap();
+        let ext = Path::new(path).extension().and_then(|ext| ext.to_str())?;
+        let loader = self.ext_to_loader.get(ext).copied()?;
+        let handle = self.load_with_loader(loader, path, server, None)?;
+
+        Some(self.loaders[loader].handler.strong_untyped(handle))
+    }
+
+    /// Returns the handle of the loaded or newly loading asset, or `None` if its
+    /// [`AssetSource`] does not exist.
+    fn load_with_loader(
+        &mut self,
+        loader: usize,
+        path: &str,
+        server: &AssetServer,
+        settings: Option<SettingsOverride>,
+    ) -> Option<ErasedHandle> {
+        let type_id = self.loaders[loader].handler.type_id;
+        if let Some(handle) = self.loaded_assets.get(path).copied() {
+            // A failed path stays failed until it is retried with `AssetServer::retry`
+            if handle.type_id() == type_id {
+                return Some(handle);
             }
         }
+
+        let handle = self.loaders[loader].handler.reserve();
+        let token = LoadToken::new(LoadPriority::default());
+        if !self.start_load(
+            loader,
+            handle,
+            path,
+            server,
+            settings.clone(),
+            token.clone(),
+        ) {
+            self.loaders[loader].handler.remove(handle.id());
+            return None;
+        }
+
+        self.loaded_assets.insert(path.to_owned(), handle);
+        self.infos.insert(
+            handle,
+            AssetInfo {
+                path: path.to_owned(),
+                state: LoadState::Loading,
+                dependencies: Vec::new(),
+                settings,
+                token,
+            },
+        );
+
+        Some(handle)
     }
 
-    pub fn reload<P: AsRef<Path>>(&mut self, path: P) {
-        let file_ext = path
-            .as_ref()
+    /// Queues a load of the file at `path` into `handle`. Returns false if the [`AssetSource`]
+    /// does not exist.
+    fn start_load(
+        &mut self,
+        loader: usize,
+        handle: ErasedHandle,
+        path: &str,
+        server: &AssetServer,
+        settings: Option<SettingsOverride>,
+        token: Arc<LoadToken>,
+    ) -> bool {
+        let Some((source, read_path)) = self.read_path(path) else {
+            util::tracing::error!("Could not find AssetSource for file: {:?}", path);
+            return false;
+        };
+        let ext = Path::new(path)
             .extension()
-            .expect("file extension")
-            .to_str()
-            .unwrap();
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_owned();
+
+        let worker_threads = self.worker_threads;
+        let pools = self
+            .pools
+            .get_or_insert_with(|| Arc::new(AssetTaskPools::new(worker_threads)))
+            .clone();
+        let loader = &self.loaders[loader];
+        loader.loader.load(
+            handle,
+            loader.result.clone(),
+            LoadContext::new(
+                server.clone(),
+                pools,
+                token,
+                path.to_owned(),
+                AssetFilePath::new(source.clone(), &read_path),
+            ),
+            LoadRequest {
+                source,
+                path: read_path,
+                ext,
+                settings,
+            },
+        );
+
+        true
+    }
+
+    /// Loads the file of a requested [`Asset`] again, into the same handle. Cancels the previous
+    /// load, if it has not finished.
+    fn restart(&mut self, handle: ErasedHandle, loader: usize, server: &AssetServer) {
+        let Some(info) = self.infos.get(&handle) else {
+            return;
+        };
+        info.token.cancel();
+        let token = LoadToken::new(info.token.priority());
+        let path = info.path.clone();
+        let settings = info.settings.clone();
+
+        let state = if self.start_load(loader, handle, &path, server, settings, token.clone()) {
+            LoadState::Loading
+        } else {
+            LoadState::Failed
+        };
+        if let Some(info) = self.infos.get_mut(&handle) {
+            info.state = state;
+            info.token = token;
+        }
+    }
+
+    pub fn retry(&mut self, handle: ErasedHandle, server: &AssetServer) {
+        if self.load_state(&handle) != LoadState::Failed {
+            return;
+        }
+
+        if let Some(loader) = self.type_to_loader.get(&handle.type_id()).copied() {
+            util::tracing::info!("{:?}: retrying", self.infos[&handle].path);
+            self.restart(handle, loader, server);
+        }
+    }
+
+    pub fn load_state(&self, handle: &ErasedHandle) -> LoadState {
+        self.infos
+            .get(handle)
+            .map(|info| info.state)
+            .unwrap_or(LoadState::NotLoaded)
+    }
+
+    pub fn recursive_dependency_load_state(
+        &self,
+        handle: &ErasedHandle,
+        visited: &mut HashSet<ErasedHandle>,
+    ) -> LoadState {
+        let Some(info) = self.infos.get(handle) else {
+            return LoadState::NotLoaded;
+        };
+        if info.state != LoadState::Loaded || !visited.insert(*handle) {
+            return info.state;
+        }
+
+        let mut state = LoadState::Loaded;
+        for dependency in info.dependencies.iter() {
+            match self.recursive_dependency_load_state(&dependency.erased(), visited) {
+                LoadState::Failed => return LoadState::Failed,
+                LoadState::Loaded => (),
+                LoadState::NotLoaded | LoadState::Loading => state = LoadState::Loading,
+            }
+        }
+
+        state
+    }
+
+    pub fn reload<P: AsRef<Path>>(&mut self, path: P, server: &AssetServer) {
+        // Watchers report every file in a directory, not only loaded assets
+        if path.as_ref().extension().is_none() {
+            return;
+        }
         let mut path = path.as_ref().to_str().unwrap().to_owned();
         if !self.path_prefix.is_empty() {
             path = format!("{}/{}", self.path_prefix, path);
         }
 
-        match self.ext_to_loader.get(file_ext) {
+        let Some(handle) = self.loaded_assets.get(&path).copied() else {
+            return;
+        };
+
+        match self.type_to_loader.get(&handle.type_id()).copied() {
             Some(loader) => {
-                let handle = self.loaded_assets.remove(&path).unwrap();
-                self.loaders[*loader].handler.remove(handle.id());
-
-                let new_handle = self.loaders[*loader].loader.load(
-                    &self.loaders[*loader].handler,
-                    self.loaders[*loader].result.clone(),
-                    path.clone(),
-                    file_ext.to_owned(),
-                );
-                assert_eq!(handle.id(), new_handle.id());
-                self.loaded_assets.insert(path, new_handle);
+                util::tracing::info!("{:?}: reloading", path);
+                self.restart(handle, loader, server);
             }
             None => {
                 util::tracing::warn!("Could not find AssetLoader for file reload: {:?}", path);
@@ -225,3 +725,167 @@ impl AssetLoaders {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{Text, TextLoader},
+        MemoryAssetReader,
+    };
+    use std::time::Duration;
+
+    fn text_server(
+        files: &[(&str, &str)],
+    ) -> (AssetServer, Receiver<AssetEvent>, Arc<AssetHandleCreator>) {
+        let mut reader = MemoryAssetReader::default();
+        for (path, contents) in files {
+            reader.insert(*path, contents.as_bytes());
+        }
+        let server = AssetServer::default();
+        server.add_reader("memory", reader);
+
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let handler = Arc::new(AssetHandleCreator::new::<Text>());
+        server.register_loader::<Text>(TextLoader, &["txt"], tx, handler.clone());
+
+        (server, rx, handler)
+    }
+
+    fn recv(rx: &Receiver<AssetEvent>) -> AssetEvent {
+        rx.recv_timeout(Duration::from_secs(5))
+            .expect("expected an asset event")
+    }
+
+    /// Applies a load result as the [`crate::Assets`] system does.
+    fn apply(server: &AssetServer, event: AssetEvent) -> ErasedHandle {
+        match event {
+            AssetEvent::Loaded {
+                handle,
+                dependencies,
+                ..
+            } => {
+                server.loaded(handle, dependencies);
+                handle
+            }
+            AssetEvent::Err { handle, .. } => {
+                server.failed(handle);
+                handle
+            }
+        }
+    }
+
+    fn event_handle(event: &AssetEvent) -> ErasedHandle {
+        match event {
+            AssetEvent::Loaded { handle, .. } | AssetEvent::Err { handle, .. } => *handle,
+        }
+    }
+
+    #[test]
+    fn load_state_transitions() {
+        let (server, rx, _) = text_server(&[("a.txt", "")]);
+        assert_eq!(
+            server.load_state(&Handle::<Text>::new(AssetId::new(0))),
+            LoadState::NotLoaded
+        );
+
+        let loaded = server.load::<Text, _>("memory://a.txt");
+        assert_eq!(server.load_state(&loaded), LoadState::Loading);
+        apply(&server, recv(&rx));
+        assert_eq!(server.load_state(&loaded), LoadState::Loaded);
+        assert_eq!(
+            server.recursive_dependency_load_state(&loaded),
+            LoadState::Loaded
+        );
+
+        let missing = server.load::<Text, _>("memory://missing.txt");
+        assert_eq!(server.load_state(&missing), LoadState::Loading);
+        assert!(matches!(recv(&rx), AssetEvent::Err { .. }));
+        server.failed((&missing).into());
+        assert_eq!(server.load_state(&missing), LoadState::Failed);
+        assert_eq!(
+            server.recursive_dependency_load_state(&missing),
+            LoadState::Failed
+        );
+    }
+
+    #[test]
+    fn retry_failed_load() {
+        let (server, rx, _) = text_server(&[]);
+        let missing = server.load::<Text, _>("memory://missing.txt");
+        apply(&server, recv(&rx));
+        assert_eq!(server.load_state(&missing), LoadState::Failed);
+
+        // Loading the path again returns the failed handle without loading it
+        assert_eq!(server.load::<Text, _>("memory://missing.txt"), missing);
+        assert_eq!(server.load_state(&missing), LoadState::Failed);
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+
+        server.retry(&missing);
+        assert_eq!(server.load_state(&missing), LoadState::Loading);
+        assert_eq!(event_handle(&recv(&rx)), (&missing).into());
+    }
+
+    #[test]
+    fn dependency_load_state() {
+        let (server, rx, _) = text_server(&[
+            ("parent.txt", "memory://child.txt"),
+            ("child.txt", ""),
+            ("broken.txt", "memory://missing.txt"),
+        ]);
+
+        // Apply the parent before its dependency
+        let parent = server.load::<Text, _>("memory://parent.txt");
+        let (mut parent_event, mut child_event) = (recv(&rx), recv(&rx));
+        if event_handle(&parent_event) != (&parent).into() {
+            std::mem::swap(&mut parent_event, &mut child_event);
+        }
+        apply(&server, parent_event);
+        assert_eq!(server.load_state(&parent), LoadState::Loaded);
+        assert_eq!(
+            server.recursive_dependency_load_state(&parent),
+            LoadState::Loading
+        );
+        apply(&server, child_event);
+        assert_eq!(
+            server.recursive_dependency_load_state(&parent),
+            LoadState::Loaded
+        );
+
+        let broken = server.load::<Text, _>("memory://broken.txt");
+        apply(&server, recv(&rx));
+        apply(&server, recv(&rx));
+        assert_eq!(server.load_state(&broken), LoadState::Loaded);
+        assert_eq!(
+            server.recursive_dependency_load_state(&broken),
+            LoadState::Failed
+        );
+    }
+
+    #[test]
+    fn dependencies_stay_loaded() {
+        let (server, rx, handler) =
+            text_server(&[("parent.txt", "memory://child.txt"), ("child.txt", "")]);
+
+        // The loader drops its handle to the child
+        let parent = server.load::<Text, _>("memory://parent.txt");
+        let child = [recv(&rx), recv(&rx)]
+            .map(|event| apply(&server, event))
+            .into_iter()
+            .find(|handle| *handle != (&parent).into())
+            .unwrap();
+        assert!(handler.is_referenced(child.id()));
+        assert!(!server.release(child, &handler));
+        assert_eq!(
+            server.recursive_dependency_load_state(&parent),
+            LoadState::Loaded
+        );
+
+        // Releasing the parent releases the child
+        let erased = (&parent).into();
+        drop(parent);
+        assert!(server.release(erased, &handler));
+        assert!(!handler.is_referenced(child.id()));
+        assert!(server.release(child, &handler));
+    }
+}
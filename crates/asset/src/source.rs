@@ -0,0 +1,436 @@
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    future::Future,
+    io::{Cursor, Read, Seek},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+};
+
+pub type BoxedFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// File opened with [`AssetReader::open`], read as it is used rather than whole.
+pub trait AssetFile: Read + Seek + Send + Sync {}
+
+impl<T: Read + Seek + Send + Sync> AssetFile for T {}
+
+/// Reads the bytes of asset files.
+///
+/// Added to an [`AssetSource`] with [`crate::AssetServer::add_reader`].
+pub trait AssetReader: Send + Sync + 'static {
+    fn read<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetReaderError>>;
+
+    /// Lists the files in the directory at `path` and its subdirectories, relative to `path`.
+    ///
+    /// Readers which cannot list directories return [`AssetReaderError::NotFound`].
+    fn read_directory<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Vec<PathBuf>, AssetReaderError>> {
+        Box::pin(async move { Err(AssetReaderError::NotFound(path.to_owned())) })
+    }
+
+    /// Opens the file at `path` to be read as it is used, such as by streamed audio.
+    ///
+    /// Readers which cannot seek into their files read them whole.
+    fn open<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Box<dyn AssetFile>, AssetReaderError>> {
+        Box::pin(async move {
+            let bytes = self.read(path).await?;
+            Ok(Box::new(Cursor::new(bytes)) as Box<dyn AssetFile>)
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum AssetReaderError {
+    NotFound(PathBuf),
+    Io(std::io::Error),
+}
+
+impl Display for AssetReaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound(path) => write!(f, "File could not be found: {:?}", path),
+            Self::Io(e) => write!(f, "IO error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AssetReaderError {}
+
+/// Reads from the filesystem on native and over HTTP on wasm, relative to `root`.
+#[derive(Debug, Default)]
+pub struct FileAssetReader {
+    root: PathBuf,
+}
+
+impl FileAssetReader {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_owned(),
+        }
+    }
+}
+
+impl AssetReader for FileAssetReader {
+    fn read<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetReaderError>> {
+        Box::pin(async move {
+            let path = self.root.join(path);
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                std::fs::read(&path).map_err(|e| match e.kind() {
+                    std::io::ErrorKind::NotFound => AssetReaderError::NotFound(path),
+                    _ => AssetReaderError::Io(e),
+                })
+            }
+            #[cfg(target_arch = "wasm32")]
+            {
+                crate::load_binary(path.to_str().unwrap())
+                    .await
+                    .map_err(|_| AssetReaderError::NotFound(path))
+            }
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Box<dyn AssetFile>, AssetReaderError>> {
+        Box::pin(async move {
+            let path = self.root.join(path);
+            let file = std::fs::File::open(&path).map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => AssetReaderError::NotFound(path),
+                _ => AssetReaderError::Io(e),
+            })?;
+
+            Ok(Box::new(file) as Box<dyn AssetFile>)
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_directory<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Vec<PathBuf>, AssetReaderError>> {
+        fn visit(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    visit(root, &path, files)?;
+                } else if let Ok(file) = path.strip_prefix(root) {
+                    files.push(file.to_owned());
+                }
+            }
+
+            Ok(())
+        }
+
+        Box::pin(async move {
+            let root = self.root.join(path);
+            let mut files = Vec::new();
+            visit(&root, &root, &mut files).map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => AssetReaderError::NotFound(root.clone()),
+                _ => AssetReaderError::Io(e),
+            })?;
+
+            Ok(files)
+        })
+    }
+}
+
+/// Serves files from memory, such as those embedded into the binary with [`embedded_asset`].
+#[derive(Debug, Default)]
+pub struct MemoryAssetReader {
+    files: HashMap<PathBuf, Arc<[u8]>>,
+}
+
+impl MemoryAssetReader {
+    pub fn insert<P: Into<PathBuf>, B: Into<Arc<[u8]>>>(&mut self, path: P, bytes: B) {
+        self.files.insert(path.into(), bytes.into());
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.files.contains_key(path.as_ref())
+    }
+}
+
+impl AssetReader for MemoryAssetReader {
+    fn read<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetReaderError>> {
+        Box::pin(async move {
+            self.files
+                .get(path)
+                .map(|bytes| bytes.to_vec())
+                .ok_or_else(|| AssetReaderError::NotFound(path.to_owned()))
+        })
+    }
+
+    fn open<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Box<dyn AssetFile>, AssetReaderError>> {
+        Box::pin(async move {
+            self.files
+                .get(path)
+                .map(|bytes| Box::new(Cursor::new(bytes.clone())) as Box<dyn AssetFile>)
+                .ok_or_else(|| AssetReaderError::NotFound(path.to_owned()))
+        })
+    }
+
+    fn read_directory<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Vec<PathBuf>, AssetReaderError>> {
+        Box::pin(async move {
+            let files = self
+                .files
+                .keys()
+                .filter_map(|file| file.strip_prefix(path).ok())
+                .filter(|file| !file.as_os_str().is_empty())
+                .map(|file| file.to_owned())
+                .collect::<Vec<_>>();
+
+            if files.is_empty() {
+                Err(AssetReaderError::NotFound(path.to_owned()))
+            } else {
+                Ok(files)
+            }
+        })
+    }
+}
+
+/// Embeds a file into the binary with [`include_bytes`] and inserts it into a
+/// [`MemoryAssetReader`] under the same path.
+///
+/// The path is relative to the file calling the macro, as with [`include_bytes`].
+///
+/// ```ignore
+/// let mut embedded = MemoryAssetReader::default();
+/// embedded_asset!(embedded, "shaders/sprite.wgsl");
+/// server.add_reader("embedded", embedded);
+/// let shader = server.load::<FragmentShaderSource, _>("embedded://shaders/sprite.wgsl");
+/// ```
+#[macro_export]
+macro_rules! embedded_asset {
+    ($reader:expr, $path:literal) => {
+        $reader.insert($path, include_bytes!($path).as_slice())
+    };
+}
+
+/// Names an [`AssetSource`]. Paths of the form `"name://path"` are read from the named source,
+/// all others from the default source.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub enum AssetSourceId {
+    #[default]
+    Default,
+    Name(String),
+}
+
+impl From<&str> for AssetSourceId {
+    fn from(value: &str) -> Self {
+        Self::Name(value.to_owned())
+    }
+}
+
+/// Overlay of [`AssetReader`]s. Readers added last are read first, falling through to earlier
+/// readers for files they do not contain.
+#[derive(Clone, Default)]
+pub struct AssetSource {
+    readers: Vec<Arc<dyn AssetReader>>,
+}
+
+impl Debug for AssetSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AssetSource")
+            .field("readers", &self.readers.len())
+            .finish()
+    }
+}
+
+impl AssetSource {
+    pub fn new(reader: impl AssetReader) -> Self {
+        Self {
+            readers: vec![Arc::new(reader)],
+        }
+    }
+
+    pub fn push(&mut self, reader: impl AssetReader) {
+        self.readers.push(Arc::new(reader));
+    }
+
+    pub async fn read(&self, path: &Path) -> Result<Vec<u8>, AssetReaderError> {
+        for reader in self.readers.iter().rev() {
+            match reader.read(path).await {
+                Err(AssetReaderError::NotFound(_)) => (),
+                result => return result,
+            }
+        }
+
+        Err(AssetReaderError::NotFound(path.to_owned()))
+    }
+
+    /// Opens the file from the last added reader that contains it. See [`AssetReader::open`].
+    pub async fn open(&self, path: &Path) -> Result<Box<dyn AssetFile>, AssetReaderError> {
+        for reader in self.readers.iter().rev() {
+            match reader.open(path).await {
+                Err(AssetReaderError::NotFound(_)) => (),
+                result => return result,
+            }
+        }
+
+        Err(AssetReaderError::NotFound(path.to_owned()))
+    }
+
+    /// Lists the files of every reader, sorted and without duplicates.
+    pub async fn read_directory(&self, path: &Path) -> Result<Vec<PathBuf>, AssetReaderError> {
+        let mut files = Vec::new();
+        let mut found = false;
+        for reader in self.readers.iter() {
+            match reader.read_directory(path).await {
+                Ok(listed) => {
+                    files.extend(listed);
+                    found = true;
+                }
+                Err(AssetReaderError::NotFound(_)) => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        if !found {
+            return Err(AssetReaderError::NotFound(path.to_owned()));
+        }
+        files.sort();
+        files.dedup();
+
+        Ok(files)
+    }
+}
+
+/// File of an [`crate::Asset`] within its [`AssetSource`], for assets which read their file as
+/// they are used rather than when they are loaded. See [`crate::AssetLoader::streamed`].
+#[derive(Debug, Clone)]
+pub struct AssetFilePath {
+    source: AssetSource,
+    path: PathBuf,
+}
+
+impl AssetFilePath {
+    pub fn new(source: AssetSource, path: impl Into<PathBuf>) -> Self {
+        Self {
+            source,
+            path: path.into(),
+        }
+    }
+
+    /// Path within the [`AssetSource`], including the path prefix.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Opens the file. Each call returns a new reader.
+    pub async fn open(&self) -> Result<Box<dyn AssetFile>, AssetReaderError> {
+        self.source.open(&self.path).await
+    }
+}
+
+/// Collection of [`AssetSource`]s. The default source reads from the working directory.
+#[derive(Debug)]
+pub(crate) struct AssetSources {
+    sources: HashMap<AssetSourceId, AssetSource>,
+}
+
+impl Default for AssetSources {
+    fn default() -> Self {
+        let mut sources = HashMap::new();
+        sources.insert(
+            AssetSourceId::Default,
+            AssetSource::new(FileAssetReader::default()),
+        );
+
+        Self { sources }
+    }
+}
+
+impl AssetSources {
+    pub fn add_reader(&mut self, source: AssetSourceId, reader: impl AssetReader) {
+        self.sources.entry(source).or_default().push(reader);
+    }
+
+    pub fn get(&self, source: &AssetSourceId) -> Option<&AssetSource> {
+        self.sources.get(source)
+    }
+}
+
+/// Splits `"name://path"` into its [`AssetSourceId`] and path.
+pub fn parse_asset_path(path: &str) -> (AssetSourceId, &str) {
+    match path.split_once("://") {
+        Some((source, path)) => (AssetSourceId::Name(source.to_owned()), path),
+        None => (AssetSourceId::Default, path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlay() {
+        let mut base = MemoryAssetReader::default();
+        base.insert("a.txt", b"base a".as_slice());
+        base.insert("b.txt", b"base b".as_slice());
+        let mut overlay = MemoryAssetReader::default();
+        overlay.insert("a.txt", b"mod a".as_slice());
+
+        let mut source = AssetSource::new(base);
+        source.push(overlay);
+
+        let read = |path: &str| pollster::block_on(source.read(Path::new(path)));
+        assert_eq!(read("a.txt").unwrap(), b"mod a");
+        assert_eq!(read("b.txt").unwrap(), b"base b");
+        assert!(matches!(read("c.txt"), Err(AssetReaderError::NotFound(_))));
+    }
+
+    #[test]
+    fn read_directory() {
+        let mut base = MemoryAssetReader::default();
+        base.insert("level/a.png", b"".as_slice());
+        base.insert("level/sounds/b.wav", b"".as_slice());
+        base.insert("other/c.png", b"".as_slice());
+        let mut overlay = MemoryAssetReader::default();
+        overlay.insert("level/a.png", b"".as_slice());
+        overlay.insert("level/d.png", b"".as_slice());
+
+        let mut source = AssetSource::new(base);
+        source.push(overlay);
+
+        let files = pollster::block_on(source.read_directory(Path::new("level"))).unwrap();
+        assert_eq!(
+            files,
+            [
+                PathBuf::from("a.png"),
+                PathBuf::from("d.png"),
+                PathBuf::from("sounds/b.wav")
+            ]
+        );
+        assert!(matches!(
+            pollster::block_on(source.read_directory(Path::new("missing"))),
+            Err(AssetReaderError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn source_paths() {
+        assert_eq!(
+            parse_asset_path("mod://textures/x.png"),
+            (AssetSourceId::Name("mod".into()), "textures/x.png")
+        );
+        assert_eq!(
+            parse_asset_path("res/x.png"),
+            (AssetSourceId::Default, "res/x.png")
+        );
+    }
+}
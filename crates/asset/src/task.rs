@@ -0,0 +1,539 @@
+#[cfg(not(target_arch = "wasm32"))]
+use parking_lot::{Condvar, Mutex};
+use std::{
+    fmt::Debug,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicU8, Ordering},
+        Arc,
+    },
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    pin::Pin,
+    task::{Context, Poll, Wake, Waker},
+};
+
+/// Order in which queued loads start. Set with [`crate::AssetServer::set_priority`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LoadPriority {
+    Low = 0,
+    #[default]
+    Normal = 1,
+    /// For assets that are currently visible.
+    High = 2,
+}
+
+impl LoadPriority {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Low,
+            1 => Self::Normal,
+            _ => Self::High,
+        }
+    }
+}
+
+/// Shared between a load and the server, which may change its priority or cancel it.
+#[derive(Debug, Default)]
+pub(crate) struct LoadToken {
+    priority: AtomicU8,
+    cancelled: AtomicBool,
+}
+
+impl LoadToken {
+    pub fn new(priority: LoadPriority) -> Arc<Self> {
+        Arc::new(Self {
+            priority: AtomicU8::new(priority as u8),
+            cancelled: AtomicBool::new(false),
+        })
+    }
+
+    pub fn priority(&self) -> LoadPriority {
+        LoadPriority::from_u8(self.priority.load(Ordering::Relaxed))
+    }
+
+    pub fn set_priority(&self, priority: LoadPriority) {
+        self.priority.store(priority as u8, Ordering::Relaxed);
+    }
+
+    /// Queued tasks of a cancelled load are dropped without running. Their
+    /// [`crate::LoadContext::compute`] results resolve with [`crate::AssetLoaderError::Cancelled`].
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type LocalFuture = Pin<Box<dyn Future<Output = ()>>>;
+
+#[cfg(not(target_arch = "wasm32"))]
+struct Task {
+    token: Arc<LoadToken>,
+    /// Creates the future on the worker which runs it.
+    run: Box<dyn FnOnce() -> LocalFuture + Send>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+enum Next {
+    Task(Task),
+    /// A suspended task of the worker was woken.
+    Woken,
+    Shutdown,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct Queue {
+    tasks: Mutex<Vec<Task>>,
+    available: Condvar,
+    shutdown: AtomicBool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Queue {
+    /// Blocks until a task is available or `woken` returns true. Returns the oldest task with the
+    /// highest priority, after the woken tasks of the worker.
+    fn pop(&self, woken: impl Fn() -> bool) -> Next {
+        let mut tasks = self.tasks.lock();
+        loop {
+            if self.shutdown.load(Ordering::Relaxed) {
+                return Next::Shutdown;
+            }
+            if woken() {
+                return Next::Woken;
+            }
+
+            tasks.retain(|task| !task.token.is_cancelled());
+            let next = tasks
+                .iter()
+                .enumerate()
+                .rev()
+                .max_by_key(|(_, task)| task.token.priority())
+                .map(|(i, _)| i);
+            match next {
+                Some(i) => return Next::Task(tasks.remove(i)),
+                None => self.available.wait(&mut tasks),
+            }
+        }
+    }
+}
+
+/// Wakes the worker running a suspended task.
+#[cfg(not(target_arch = "wasm32"))]
+struct TaskWaker {
+    woken: AtomicBool,
+    queue: Arc<Queue>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        // Locked so that a worker cannot check for woken tasks and then miss the notification
+        let _tasks = self.queue.tasks.lock();
+        self.queue.available.notify_all();
+    }
+}
+
+/// Task which returned [`Poll::Pending`], such as a load waiting on
+/// [`crate::LoadContext::compute`]. Kept by its worker, since the future need not be `Send`.
+#[cfg(not(target_arch = "wasm32"))]
+struct Suspended {
+    future: LocalFuture,
+    waker: Arc<TaskWaker>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Suspended {
+    /// Returns the task if it is still pending.
+    fn poll(mut self) -> Option<Self> {
+        self.waker.woken.store(false, Ordering::Release);
+        let waker = Waker::from(self.waker.clone());
+        match self.future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(()) => None,
+            Poll::Pending => Some(self),
+        }
+    }
+
+    fn is_woken(&self) -> bool {
+        self.waker.woken.load(Ordering::Acquire)
+    }
+}
+
+/// Runs tasks until the pool is dropped. Suspended tasks yield the worker to other tasks.
+#[cfg(not(target_arch = "wasm32"))]
+fn run_worker(queue: Arc<Queue>) {
+    let mut suspended: Vec<Suspended> = Vec::new();
+    loop {
+        match queue.pop(|| suspended.iter().any(Suspended::is_woken)) {
+            Next::Task(task) => {
+                let task = Suspended {
+                    future: (task.run)(),
+                    waker: Arc::new(TaskWaker {
+                        woken: AtomicBool::new(false),
+                        queue: queue.clone(),
+                    }),
+                };
+                suspended.extend(task.poll());
+            }
+            Next::Woken => {
+                let (woken, waiting) = std::mem::take(&mut suspended)
+                    .into_iter()
+                    .partition::<Vec<_>, _>(Suspended::is_woken);
+                suspended = waiting;
+                suspended.extend(woken.into_iter().filter_map(Suspended::poll));
+            }
+            Next::Shutdown => return,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct PoolInner {
+    queue: Arc<Queue>,
+    threads: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for PoolInner {
+    fn drop(&mut self) {
+        self.queue.shutdown.store(true, Ordering::Relaxed);
+        self.queue.available.notify_all();
+    }
+}
+
+/// Fixed number of worker threads which run tasks by [`LoadPriority`].
+///
+/// The workers exit once the last clone of the pool is dropped.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone)]
+pub(crate) struct TaskPool {
+    inner: Arc<PoolInner>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Debug for TaskPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TaskPool")
+            .field("threads", &self.inner.threads)
+            .field("queued", &self.inner.queue.tasks.lock().len())
+            .finish()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TaskPool {
+    pub fn new(name: &str, threads: usize) -> Self {
+        let threads = threads.max(1);
+        let queue = Arc::new(Queue::default());
+        for i in 0..threads {
+            let queue = queue.clone();
+            std::thread::Builder::new()
+                .name(format!("{name} {i}"))
+                .spawn(move || run_worker(queue))
+                .expect("spawn task pool thread");
+        }
+
+        Self {
+            inner: Arc::new(PoolInner { queue, threads }),
+        }
+    }
+
+    pub fn spawn(&self, token: Arc<LoadToken>, run: impl FnOnce() + Send + 'static) {
+        self.spawn_future(token, move || async move { run() });
+    }
+
+    /// Runs the future created by `create` on a worker. While the future waits, the worker runs
+    /// other tasks.
+    pub fn spawn_future<F: Future<Output = ()> + 'static>(
+        &self,
+        token: Arc<LoadToken>,
+        create: impl FnOnce() -> F + Send + 'static,
+    ) {
+        self.inner.queue.tasks.lock().push(Task {
+            token,
+            run: Box::new(move || Box::pin(create()) as LocalFuture),
+        });
+        // Any worker may take the task, but workers waiting on a suspended task are also
+        // notified by its waker
+        self.inner.queue.available.notify_all();
+    }
+
+    /// Runs `f` on the pool, resolving once it returns, or with [`None`] if the task is
+    /// cancelled before it runs.
+    pub fn run<T: Send + 'static>(
+        &self,
+        token: Arc<LoadToken>,
+        f: impl FnOnce() -> T + Send + 'static,
+    ) -> TaskResult<T> {
+        let result = TaskResult::default();
+        let sender = TaskSender {
+            state: result.state.clone(),
+        };
+        self.spawn(token, move || sender.send(f()));
+
+        result
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct TaskState<T> {
+    value: Option<T>,
+    finished: bool,
+    waker: Option<Waker>,
+}
+
+/// Resolves a [`TaskResult`]. Dropping the sender without sending, as when a cancelled task is
+/// removed from the queue, resolves it with [`None`].
+#[cfg(not(target_arch = "wasm32"))]
+struct TaskSender<T> {
+    state: Arc<Mutex<TaskState<T>>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T> TaskSender<T> {
+    fn send(self, value: T) {
+        self.state.lock().value = Some(value);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T> Drop for TaskSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.state.lock();
+        state.finished = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Output of [`TaskPool::run`].
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct TaskResult<T> {
+    state: Arc<Mutex<TaskState<T>>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T> Default for TaskResult<T> {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(TaskState {
+                value: None,
+                finished: false,
+                waker: None,
+            })),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T> Future for TaskResult<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock();
+        if state.finished {
+            Poll::Ready(state.value.take())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Worker threads for loading assets. Created on the first load.
+///
+/// On wasm, loads run on the browser's event loop and priorities are ignored.
+#[derive(Debug)]
+pub(crate) struct AssetTaskPools {
+    /// Reads files and runs [`crate::AssetLoader::load`].
+    #[cfg(not(target_arch = "wasm32"))]
+    io: TaskPool,
+    /// Runs [`crate::LoadContext::compute`].
+    #[cfg(not(target_arch = "wasm32"))]
+    compute: TaskPool,
+}
+
+impl AssetTaskPools {
+    pub fn new(threads: WorkerThreads) -> Self {
+        #[cfg(target_arch = "wasm32")]
+        let _ = threads;
+
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            io: TaskPool::new("asset io", threads.io),
+            #[cfg(not(target_arch = "wasm32"))]
+            compute: TaskPool::new("asset compute", threads.compute),
+        }
+    }
+
+    /// Runs a load on the IO pool. The future is created on the worker, so it need not be `Send`.
+    /// Loads waiting on [`Self::compute`] leave the worker free to run other loads.
+    pub fn spawn_load<F: Future<Output = ()> + 'static>(
+        &self,
+        token: Arc<LoadToken>,
+        load: impl FnOnce() -> F + Send + 'static,
+    ) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.io.spawn_future(token, load);
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = token;
+            wasm_bindgen_futures::spawn_local(load());
+        }
+    }
+
+    /// Returns [`None`] if the load is cancelled before `f` runs.
+    pub async fn compute<T: Send + 'static>(
+        &self,
+        token: Arc<LoadToken>,
+        f: impl FnOnce() -> T + Send + 'static,
+    ) -> Option<T> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.compute.run(token, f).await
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = token;
+            Some(f())
+        }
+    }
+}
+
+/// Number of worker threads used to load assets. Set with
+/// [`crate::AssetServer::set_worker_threads`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkerThreads {
+    pub io: usize,
+    pub compute: usize,
+}
+
+impl Default for WorkerThreads {
+    fn default() -> Self {
+        let parallelism = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4);
+
+        Self {
+            io: 4,
+            compute: parallelism.saturating_sub(1).max(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, time::Duration};
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn priority_and_cancellation() {
+        let pool = TaskPool::new("test", 1);
+        let (tx, rx) = mpsc::channel();
+
+        // Occupies the only worker while the other tasks are queued
+        let (start_tx, start_rx) = mpsc::channel::<()>();
+        pool.spawn(LoadToken::new(LoadPriority::Normal), move || {
+            start_rx.recv().unwrap();
+        });
+
+        let mut cancelled = None;
+        for (name, priority) in [
+            ("low", LoadPriority::Low),
+            ("normal", LoadPriority::Normal),
+            ("cancelled", LoadPriority::High),
+            ("high", LoadPriority::High),
+        ] {
+            let token = LoadToken::new(priority);
+            if name == "cancelled" {
+                cancelled = Some(token.clone());
+            }
+            let tx = tx.clone();
+            pool.spawn(token, move || tx.send(name).unwrap());
+        }
+        cancelled.unwrap().cancel();
+        start_tx.send(()).unwrap();
+
+        let order = (0..3).map(|_| rx.recv().unwrap()).collect::<Vec<_>>();
+        assert_eq!(order, ["high", "normal", "low"]);
+
+        let result = pool.run(LoadToken::new(LoadPriority::Normal), || 2 + 2);
+        assert_eq!(pollster::block_on(result), Some(4));
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn cancel_load_waiting_on_compute() {
+        let pools = Arc::new(AssetTaskPools::new(WorkerThreads { io: 1, compute: 1 }));
+        let (tx, rx) = mpsc::channel();
+        let load = |token: Arc<LoadToken>, value: u32| {
+            let pools_clone = pools.clone();
+            let tx = tx.clone();
+            pools.spawn_load(token.clone(), move || async move {
+                tx.send(None).unwrap();
+                let result = pools_clone.compute(token, move || value).await;
+                tx.send(Some(result)).unwrap();
+            });
+        };
+
+        // Occupies the only compute worker, so the load waits with its task queued
+        let (start_tx, start_rx) = mpsc::channel::<()>();
+        pools
+            .compute
+            .spawn(LoadToken::new(LoadPriority::Normal), move || {
+                start_rx.recv().unwrap();
+            });
+
+        let cancelled = LoadToken::new(LoadPriority::Normal);
+        load(cancelled.clone(), 1);
+        let recv = || rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(recv(), None);
+        cancelled.cancel();
+        start_tx.send(()).unwrap();
+        assert_eq!(recv(), Some(None));
+
+        // The IO worker is free to run the next load
+        load(LoadToken::new(LoadPriority::Normal), 2);
+        assert_eq!(recv(), None);
+        assert_eq!(recv(), Some(Some(2)));
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_waiting_on_compute_yields_io_worker() {
+        let pools = Arc::new(AssetTaskPools::new(WorkerThreads { io: 1, compute: 1 }));
+        let (tx, rx) = mpsc::channel();
+        let recv = || rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        // The first load waits on compute until the second load has run on the only IO worker
+        let (start_tx, start_rx) = mpsc::channel::<()>();
+        let pools_clone = pools.clone();
+        let tx_clone = tx.clone();
+        pools.spawn_load(LoadToken::new(LoadPriority::High), move || async move {
+            let token = LoadToken::new(LoadPriority::Normal);
+            pools_clone
+                .compute(token, move || start_rx.recv().unwrap())
+                .await;
+            tx_clone.send("first").unwrap();
+        });
+        pools.spawn_load(LoadToken::new(LoadPriority::Normal), move || async move {
+            tx.send("second").unwrap();
+        });
+
+        assert_eq!(recv(), "second");
+        start_tx.send(()).unwrap();
+        assert_eq!(recv(), "first");
+    }
+}
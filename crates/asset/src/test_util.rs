@@ -0,0 +1,38 @@
+//! [`Asset`] and [`AssetLoader`] shared by the tests of this crate.
+
+use crate::{reader::ByteReader, Asset, AssetLoader, AssetLoaderError, LoadContext};
+use std::io::Cursor;
+
+#[derive(Debug)]
+pub struct Text;
+
+impl Asset for Text {}
+
+/// Loads the path in the file, if any, as a dependency.
+pub struct TextLoader;
+
+impl AssetLoader for TextLoader {
+    type Asset = Text;
+    type Settings = ();
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["txt"]
+    }
+
+    async fn load(
+        mut reader: ByteReader<Cursor<Vec<u8>>>,
+        _settings: Self::Settings,
+        _path: String,
+        _ext: &str,
+        context: &mut LoadContext,
+    ) -> Result<Self::Asset, AssetLoaderError> {
+        let dependency = reader
+            .read_all_to_string()
+            .map_err(|_| AssetLoaderError::FailedToParse)?;
+        if !dependency.is_empty() {
+            context.load::<Text, _>(dependency);
+        }
+
+        Ok(Text)
+    }
+}
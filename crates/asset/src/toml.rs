@@ -1,7 +1,8 @@
 use crate::{reader::ByteReader, Asset, AssetApp, AssetLoader, AssetLoaderError};
 use app::prelude::*;
-use std::{io::Cursor, sync::Arc};
-use taplo::dom;
+use cereal::{FromValue, Value, ValueError, ValuePath};
+use std::{fmt::Display, io::Cursor, marker::PhantomData, ops::Deref, sync::Arc};
+use taplo::dom::{self, node::IntegerValue};
 
 #[derive(Debug)]
 pub struct TomlPlugin;
@@ -28,6 +29,7 @@ impl AssetLoader for TomlAssetLoader {
         _settings: Self::Settings,
         _path: String,
         ext: &str,
+        _context: &mut crate::LoadContext,
     ) -> Result<Self::Asset, crate::AssetLoaderError> {
         if ext != "toml" {
             return Err(crate::AssetLoaderError::UnsupportedFileExtension);
@@ -68,3 +70,372 @@ impl Toml {
         &self.head
     }
 }
+
+/// TOML file converted into `T` with [`FromValue`]. Registered with
+/// [`AssetApp::register_toml_asset`].
+///
+/// ```ignore
+/// #[derive(WinnyFromValue, Default)]
+/// struct Enemy {
+///     health: u32,
+///     speed: f32,
+/// }
+///
+/// app.register_toml_asset::<Enemy>();
+///
+/// let handle: Handle<TomlAsset<Enemy>> = server.load("enemies/slime.toml");
+/// ```
+#[derive(Debug)]
+pub struct TomlAsset<T> {
+    value: T,
+}
+
+impl<T: Send + Sync + 'static> Asset for TomlAsset<T> {}
+
+impl<T> TomlAsset<T> {
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for TomlAsset<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+pub(crate) struct TypedTomlAssetLoader<T> {
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for TypedTomlAssetLoader<T> {
+    fn default() -> Self {
+        Self {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: FromValue + Send + Sync + 'static> AssetLoader for TypedTomlAssetLoader<T> {
+    type Asset = TomlAsset<T>;
+    type Settings = ();
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["toml"]
+    }
+
+    async fn load(
+        mut reader: ByteReader<Cursor<Vec<u8>>>,
+        _settings: Self::Settings,
+        _path: String,
+        ext: &str,
+        _context: &mut crate::LoadContext,
+    ) -> Result<Self::Asset, AssetLoaderError> {
+        if ext != "toml" {
+            return Err(AssetLoaderError::UnsupportedFileExtension);
+        }
+
+        let value = from_toml_str(&reader.read_all_to_string()?)
+            .map_err(|e| AssetLoaderError::Failed(e.to_string()))?;
+
+        Ok(TomlAsset { value })
+    }
+}
+
+/// Failed to read TOML with [`from_toml_str`].
+#[derive(Debug)]
+pub enum TomlError {
+    /// Invalid TOML. `line` and `column` start at 1.
+    Parse {
+        message: String,
+        line: usize,
+        column: usize,
+    },
+    /// The TOML does not match the type. `line` and `column` point to the offending value, or
+    /// to its closest parent which exists.
+    Value {
+        error: ValueError,
+        line: usize,
+        column: usize,
+    },
+}
+
+impl Display for TomlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse {
+                message,
+                line,
+                column,
+            } => write!(f, "{message} at line {line}, column {column}"),
+            Self::Value {
+                error,
+                line,
+                column,
+            } => write!(f, "{error} at line {line}, column {column}"),
+        }
+    }
+}
+
+impl std::error::Error for TomlError {}
+
+/// Returns the 1-based line and column of a byte offset into `source`.
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+
+    (line, column)
+}
+
+/// Parses TOML into a [`Value::Table`].
+pub fn parse_toml(source: &str) -> Result<Value, TomlError> {
+    Ok(node_to_value(&parse_dom(source)?))
+}
+
+fn parse_dom(source: &str) -> Result<dom::Node, TomlError> {
+    let parsed = taplo::parser::parse(source);
+    if let Some(error) = parsed.errors.first() {
+        let (line, column) = line_column(source, u32::from(error.range.start()) as usize);
+        return Err(TomlError::Parse {
+            message: error.message.clone(),
+            line,
+            column,
+        });
+    }
+
+    let dom = parsed.into_dom();
+    if let Err(mut errors) = dom.validate() {
+        if let Some(error) = errors.next() {
+            let offset = match &error {
+                dom::Error::ConflictingKeys { key, .. } => key.text_ranges().next(),
+                _ => None,
+            }
+            .map(|range| u32::from(range.start()) as usize)
+            .unwrap_or_default();
+            let (line, column) = line_column(source, offset);
+
+            return Err(TomlError::Parse {
+                message: error.to_string(),
+                line,
+                column,
+            });
+        }
+    }
+
+    Ok(dom)
+}
+
+/// Parses TOML and converts it with [`FromValue`].
+pub fn from_toml_str<T: FromValue>(source: &str) -> Result<T, TomlError> {
+    let dom = parse_dom(source)?;
+    T::from_value(&node_to_value(&dom)).map_err(|error| {
+        let (line, column) = line_column(source, value_offset(&dom, &error.path));
+        TomlError::Value {
+            error,
+            line,
+            column,
+        }
+    })
+}
+
+/// Returns the byte offset of the node at `path`, or of its closest parent which exists.
+fn value_offset(dom: &dom::Node, path: &[ValuePath]) -> usize {
+    let mut node = dom.clone();
+    for segment in path.iter() {
+        let next = match segment {
+            ValuePath::Key(key) => node.try_get(key),
+            ValuePath::Index(index) => node.try_get(*index),
+        };
+        match next {
+            Ok(next) => node = next,
+            Err(_) => break,
+        }
+    }
+
+    node.text_ranges()
+        .next()
+        .map(|range| u32::from(range.start()) as usize)
+        .unwrap_or_default()
+}
+
+/// Converts a [`dom::Node`] into a [`Value`]. Dates are converted to strings.
+pub fn node_to_value(node: &dom::Node) -> Value {
+    match node {
+        dom::Node::Table(table) => Value::Table(
+            table
+                .entries()
+                .read()
+                .iter()
+                .map(|(key, node)| (key.value().to_owned(), node_to_value(node)))
+                .collect(),
+        ),
+        dom::Node::Array(array) => {
+            Value::Array(array.items().read().iter().map(node_to_value).collect())
+        }
+        dom::Node::Bool(b) => Value::Bool(b.value()),
+        dom::Node::Str(s) => Value::String(s.value().to_owned()),
+        dom::Node::Integer(i) => match i.value() {
+            IntegerValue::Negative(i) => Value::Integer(i),
+            IntegerValue::Positive(i) => Value::Integer(i as i64),
+        },
+        dom::Node::Float(f) => Value::Float(f.value()),
+        dom::Node::Date(d) => Value::String(d.value().to_string()),
+        dom::Node::Invalid(_) => Value::Table(Vec::new()),
+    }
+}
+
+/// Writes a [`Value::Table`] as TOML. Nested tables are written as `[headers]`.
+pub fn to_toml_string(value: &Value) -> String {
+    let mut toml = String::new();
+    if let Value::Table(entries) = value {
+        write_table(&mut toml, &mut Vec::new(), entries);
+    }
+
+    toml
+}
+
+fn is_table_array(value: &Value) -> bool {
+    matches!(value, Value::Array(values)
+        if !values.is_empty() && values.iter().all(|v| matches!(v, Value::Table(_))))
+}
+
+fn write_table<'a>(toml: &mut String, path: &mut Vec<&'a str>, entries: &'a [(String, Value)]) {
+    for (key, value) in entries.iter() {
+        if !matches!(value, Value::Table(_)) && !is_table_array(value) {
+            toml.push_str(&format!("{} = ", toml_key(key)));
+            write_inline(toml, value);
+            toml.push('\n');
+        }
+    }
+
+    for (key, value) in entries.iter() {
+        match value {
+            Value::Table(entries) => {
+                path.push(key);
+                toml.push_str(&format!("\n[{}]\n", toml_path(path)));
+                write_table(toml, path, entries);
+                path.pop();
+            }
+            Value::Array(values) if is_table_array(value) => {
+                path.push(key);
+                for value in values.iter() {
+                    toml.push_str(&format!("\n[[{}]]\n", toml_path(path)));
+                    if let Value::Table(entries) = value {
+                        write_table(toml, path, entries);
+                    }
+                }
+                path.pop();
+            }
+            _ => (),
+        }
+    }
+}
+
+fn write_inline(toml: &mut String, value: &Value) {
+    match value {
+        Value::Bool(b) => toml.push_str(&b.to_string()),
+        Value::Integer(i) => toml.push_str(&i.to_string()),
+        Value::Float(f) if f.is_nan() => toml.push_str("nan"),
+        Value::Float(f) if f.is_infinite() => toml.push_str(if *f > 0.0 { "inf" } else { "-inf" }),
+        Value::Float(f) => toml.push_str(&format!("{f:?}")),
+        Value::String(s) => toml.push_str(&toml_string(s)),
+        Value::Array(values) => {
+            toml.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i != 0 {
+                    toml.push_str(", ");
+                }
+                write_inline(toml, value);
+            }
+            toml.push(']');
+        }
+        Value::Table(entries) => {
+            toml.push('{');
+            for (i, (key, value)) in entries.iter().enumerate() {
+                toml.push_str(if i == 0 { " " } else { ", " });
+                toml.push_str(&format!("{} = ", toml_key(key)));
+                write_inline(toml, value);
+            }
+            toml.push_str(if entries.is_empty() { "}" } else { " }" });
+        }
+    }
+}
+
+fn toml_path(path: &[&str]) -> String {
+    path.iter()
+        .map(|key| toml_key(key))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn toml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_owned()
+    } else {
+        toml_string(key)
+    }
+}
+
+fn toml_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04X}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cereal::{ValueErrorKind, WinnyFromValue};
+
+    #[derive(WinnyFromValue, Debug, Default, PartialEq)]
+    struct Enemy {
+        name: String,
+        health: u32,
+        drops: Vec<String>,
+    }
+
+    #[test]
+    fn value_error_location() {
+        let enemy = from_toml_str::<Enemy>("name = \"slime\"\nhealth = 10\n").unwrap();
+        assert_eq!(enemy.health, 10);
+        assert!(enemy.drops.is_empty());
+
+        let source = "name = \"slime\"\ndrops = [\"gel\",\n  4]\n";
+        let Err(TomlError::Value {
+            error,
+            line,
+            column,
+        }) = from_toml_str::<Enemy>(source)
+        else {
+            panic!("expected value error");
+        };
+        assert!(matches!(error.kind, ValueErrorKind::InvalidType { .. }));
+        assert_eq!(error.path_string(), "drops[1]");
+        assert_eq!((line, column), (3, 3));
+
+        assert!(matches!(
+            from_toml_str::<Enemy>("name = \"slime\"\nhealth = \n"),
+            Err(TomlError::Parse { line: 2, .. })
+        ));
+    }
+}
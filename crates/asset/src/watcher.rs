@@ -4,11 +4,11 @@ use ecs::egui_widget::Widget;
 use ecs::{WinnyBundle, *};
 use ecs::{WinnyComponent, WinnyEvent};
 use std::{
-    fmt::Display,
+    collections::HashMap,
+    fmt::{Debug, Display},
     path::{Path, PathBuf},
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
-use util::info;
 
 use crate::ReloadAsset;
 
@@ -44,24 +44,26 @@ fn emit_watcher_events(
     mut dirs: Query<(Mut<DirWatcher>, Option<WatchForAsset>)>,
     mut files: Query<(Mut<FileWatcher>, Option<WatchForAsset>)>,
 ) {
+    let now = Instant::now();
+
     for (dir, asset) in dirs.iter_mut() {
-        for file in dir.watchers() {
-            for change in file.changes() {
-                if asset.is_some() {
-                    info!("{:?}: reloading", change);
-                    asset_writer.send(ReloadAsset(file.path().into()));
+        for change in dir.watcher.changes(now) {
+            if asset.is_some() {
+                if let Some(path) = change.reload_path() {
+                    asset_writer.send(ReloadAsset(path.into()));
                 }
-
-                file_writer.send(change);
             }
+
+            file_writer.send(change);
         }
     }
 
     for (file, asset) in files.iter_mut() {
-        for change in file.changes() {
+        for change in file.watcher.changes(now) {
             if asset.is_some() {
-                info!("{:?}: reloading", change);
-                asset_writer.send(ReloadAsset(file.path().into()));
+                if let Some(path) = change.reload_path() {
+                    asset_writer.send(ReloadAsset(path.into()));
+                }
             }
 
             file_writer.send(change);
@@ -69,109 +71,524 @@ fn emit_watcher_events(
     }
 }
 
-/// Event from a [`FileState`] emitted during the [`AppSchedule::Platform`] schedule.
-#[derive(WinnyEvent, Debug)]
+/// Event from a [`FileWatcher`] or [`DirWatcher`] emitted during the [`AppSchedule::Platform`]
+/// schedule.
+#[derive(WinnyEvent, Debug, Clone, PartialEq, Eq)]
 pub enum FileEvent {
+    Created(PathBuf),
     Modified(PathBuf),
+    Removed(PathBuf),
+    /// Only reported by backends which can pair both sides of a rename. Otherwise reported as
+    /// [`FileEvent::Removed`] followed by [`FileEvent::Created`].
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
 }
 
-/// Tracks state of a file and notifies [`FileWatcher`] of any events.
+impl FileEvent {
+    /// Path of the file after the event.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Created(path) | Self::Modified(path) | Self::Removed(path) => path,
+            Self::Renamed { to, .. } => to,
+        }
+    }
+
+    /// Path whose contents may have changed.
+    fn reload_path(&self) -> Option<&Path> {
+        match self {
+            Self::Removed(_) => None,
+            event => Some(event.path()),
+        }
+    }
+}
+
+/// Source of [`FileEvent`]s for a [`FileWatcher`] or [`DirWatcher`].
+///
+/// [`InotifyWatcher`] is used on Linux, falling back to [`PollWatcher`] on other platforms or
+/// if inotify is unavailable.
+pub trait WatcherBackend: Send + Sync + 'static {
+    /// Appends all events since the last call. Must not block.
+    fn poll(&mut self, events: &mut Vec<FileEvent>);
+}
+
+/// Compares the modified time of every file on each poll.
+///
+/// Cost scales with the number of watched files. Renames are reported as a removal and
+/// a creation.
 #[derive(Debug)]
-struct FileState {
-    last_modified: SystemTime,
+pub struct PollWatcher {
     path: PathBuf,
+    files: HashMap<PathBuf, SystemTime>,
 }
 
-#[cfg(feature = "widgets")]
-impl Widget for FileState {
-    fn display(&mut self, ui: &mut egui::Ui) {
-        ui.label(format!("{:?}", self).as_str());
+impl PollWatcher {
+    /// Watches a single file, or every file in a directory recursively.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, WatcherError> {
+        let path = path.as_ref().to_owned();
+        let mut files = HashMap::new();
+        scan(&path, &mut files)?;
+
+        Ok(Self { path, files })
     }
 }
 
-impl FileState {
+fn scan(path: &Path, files: &mut HashMap<PathBuf, SystemTime>) -> Result<(), WatcherError> {
+    let metadata = std::fs::metadata(path).map_err(|_| WatcherError::FileNotFound(path.into()))?;
+    if metadata.is_file() {
+        files.insert(
+            path.to_owned(),
+            metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        );
+        return Ok(());
+    }
+
+    let dir = std::fs::read_dir(path).map_err(|_| WatcherError::ReadDirectory)?;
+    for entry in dir {
+        let entry = entry.map_err(|_| WatcherError::Entry)?;
+        // Files may be removed while scanning
+        let _ = scan(&entry.path(), files);
+    }
+
+    Ok(())
+}
+
+impl WatcherBackend for PollWatcher {
+    fn poll(&mut self, events: &mut Vec<FileEvent>) {
+        let mut files = HashMap::with_capacity(self.files.len());
+        if scan(&self.path, &mut files).is_err() {
+            files.clear();
+        }
+
+        for (path, modified) in files.iter() {
+            match self.files.get(path) {
+                None => events.push(FileEvent::Created(path.clone())),
+                Some(last_modified) if last_modified != modified => {
+                    events.push(FileEvent::Modified(path.clone()))
+                }
+                _ => (),
+            }
+        }
+
+        for path in self.files.keys() {
+            if !files.contains_key(path) {
+                events.push(FileEvent::Removed(path.clone()));
+            }
+        }
+
+        self.files = files;
+    }
+}
+
+/// Receives events from the kernel with inotify.
+///
+/// Directories are watched recursively, including those created after the watcher. A single
+/// file is watched through its parent directory, so that editors which save by replacing the
+/// file are still seen.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct InotifyWatcher {
+    inotify: inotify::Inotify,
+    dirs: HashMap<inotify::WatchDescriptor, PathBuf>,
+    /// Set when watching a single file.
+    file: Option<PathBuf>,
+    buffer: Vec<u8>,
+}
+
+#[cfg(target_os = "linux")]
+impl InotifyWatcher {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, WatcherError> {
-        let metadata = match std::fs::metadata(path.as_ref()) {
-            Ok(m) => m,
-            Err(_) => return Err(WatcherError::FileNotFound(path.as_ref().into())),
+        let path = path.as_ref();
+        let metadata =
+            std::fs::metadata(path).map_err(|_| WatcherError::FileNotFound(path.into()))?;
+
+        let mut watcher = Self {
+            inotify: inotify::Inotify::init().map_err(|_| WatcherError::Io)?,
+            dirs: HashMap::new(),
+            file: None,
+            buffer: vec![0; 4096],
         };
-        if !metadata.is_file() {
-            return Err(WatcherError::FileStateIsNotFile);
+
+        if metadata.is_file() {
+            let parent = path.parent().unwrap_or(Path::new(""));
+            watcher.watch_dir(parent)?;
+            watcher.file = Some(parent.join(path.file_name().unwrap()));
+        } else {
+            watcher.watch_dir_recursive(path, &mut Vec::new())?;
         }
-        let path = path.as_ref().into();
 
-        let last_modified = if let Ok(t) = metadata.modified() {
-            t
+        Ok(watcher)
+    }
+
+    fn watch_dir(&mut self, path: &Path) -> Result<(), WatcherError> {
+        use inotify::WatchMask;
+
+        // Paths are reported relative to `path`, which is empty for the working directory
+        let dir = if path.as_os_str().is_empty() {
+            Path::new(".")
         } else {
-            SystemTime::now()
+            path
         };
+        let wd = self
+            .inotify
+            .watches()
+            .add(
+                dir,
+                WatchMask::CREATE
+                    | WatchMask::MODIFY
+                    | WatchMask::CLOSE_WRITE
+                    | WatchMask::DELETE
+                    | WatchMask::MOVE
+                    | WatchMask::ONLYDIR,
+            )
+            .map_err(|_| WatcherError::Io)?;
+        self.dirs.insert(wd, path.to_owned());
+
+        Ok(())
+    }
 
-        Ok(Self {
-            path,
-            last_modified,
-        })
+    /// Watches `path` and its subdirectories. Collects the files found, which may have been
+    /// created before the watch was added.
+    fn watch_dir_recursive(
+        &mut self,
+        path: &Path,
+        files: &mut Vec<PathBuf>,
+    ) -> Result<(), WatcherError> {
+        self.watch_dir(path)?;
+
+        let dir = std::fs::read_dir(path).map_err(|_| WatcherError::ReadDirectory)?;
+        for entry in dir {
+            let path = entry.map_err(|_| WatcherError::Entry)?.path();
+            if path.is_dir() {
+                self.watch_dir_recursive(&path, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+
+        Ok(())
     }
 
-    pub fn events(&mut self) -> Vec<FileEvent> {
-        let mut events = Vec::new();
-        let metadata = match std::fs::metadata(&self.path) {
-            Ok(m) => m,
-            Err(_) => {
-                return vec![];
+    fn created_dir(&mut self, path: &Path, events: &mut Vec<FileEvent>) {
+        let mut files = Vec::new();
+        if let Err(e) = self.watch_dir_recursive(path, &mut files) {
+            util::tracing::warn!("Failed to watch directory {:?}: {}", path, e);
+        }
+        events.extend(files.into_iter().map(FileEvent::Created));
+    }
+
+    fn renamed_dir(&mut self, from: &Path, to: &Path, events: &mut Vec<FileEvent>) {
+        for dir in self.dirs.values_mut() {
+            if let Ok(relative) = dir.strip_prefix(from) {
+                *dir = to.join(relative);
+            }
+        }
+
+        let mut files = Vec::new();
+        collect_files(to, &mut files);
+        events.extend(files.into_iter().map(|path| FileEvent::Renamed {
+            from: from.join(path.strip_prefix(to).unwrap()),
+            to: path,
+        }));
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(dir) = std::fs::read_dir(path) else {
+        return;
+    };
+
+    for entry in dir.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl WatcherBackend for InotifyWatcher {
+    fn poll(&mut self, events: &mut Vec<FileEvent>) {
+        use inotify::EventMask;
+
+        let start = events.len();
+        // Moves out of a watched directory have no matching `MOVED_TO`
+        let mut moved_from: Vec<(u32, PathBuf, bool)> = Vec::new();
+
+        loop {
+            let mut buffer = std::mem::take(&mut self.buffer);
+            let raw = match self.inotify.read_events(&mut buffer) {
+                Ok(raw) => raw
+                    .map(|e| (e.wd, e.mask, e.cookie, e.name.map(|n| n.to_owned())))
+                    .collect::<Vec<_>>(),
+                Err(e) => {
+                    if e.kind() != std::io::ErrorKind::WouldBlock {
+                        util::tracing::error!("Failed to read inotify events: {}", e);
+                    }
+                    self.buffer = buffer;
+                    break;
+                }
+            };
+            self.buffer = buffer;
+
+            for (wd, mask, cookie, name) in raw.into_iter() {
+                if mask.contains(EventMask::Q_OVERFLOW) {
+                    util::tracing::warn!("inotify queue overflowed, events were lost");
+                    continue;
+                }
+                if mask.contains(EventMask::IGNORED) {
+                    self.dirs.remove(&wd);
+                    continue;
+                }
+
+                let (Some(dir), Some(name)) = (self.dirs.get(&wd), name) else {
+                    continue;
+                };
+                let path = dir.join(name);
+                let is_dir = mask.contains(EventMask::ISDIR);
+
+                if mask.contains(EventMask::MOVED_FROM) {
+                    moved_from.push((cookie, path, is_dir));
+                } else if mask.contains(EventMask::MOVED_TO) {
+                    match moved_from.iter().position(|(c, _, _)| *c == cookie) {
+                        Some(i) => {
+                            let (_, from, _) = moved_from.swap_remove(i);
+                            if is_dir {
+                                self.renamed_dir(&from, &path, events);
+                            } else {
+                                events.push(FileEvent::Renamed { from, to: path });
+                            }
+                        }
+                        None if is_dir => self.created_dir(&path, events),
+                        None => events.push(FileEvent::Created(path)),
+                    }
+                } else if mask.contains(EventMask::CREATE) {
+                    if is_dir {
+                        self.created_dir(&path, events);
+                    } else {
+                        events.push(FileEvent::Created(path));
+                    }
+                } else if mask.contains(EventMask::DELETE) {
+                    if !is_dir {
+                        events.push(FileEvent::Removed(path));
+                    }
+                } else if mask.intersects(EventMask::MODIFY | EventMask::CLOSE_WRITE) {
+                    events.push(FileEvent::Modified(path));
+                }
+            }
+        }
+
+        for (_, path, is_dir) in moved_from.into_iter() {
+            if !is_dir {
+                events.push(FileEvent::Removed(path));
             }
+        }
+
+        if let Some(file) = &self.file {
+            let filtered = events
+                .split_off(start)
+                .into_iter()
+                .filter_map(|event| match event {
+                    FileEvent::Renamed { to, .. } if &to == file => Some(FileEvent::Created(to)),
+                    FileEvent::Renamed { from, .. } if &from == file => {
+                        Some(FileEvent::Removed(from))
+                    }
+                    event if event.path() == file => Some(event),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            events.extend(filtered);
+        }
+    }
+}
+
+/// Delay after the last event for a file before it is reported.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Coalesces bursts of events for the same file, such as an editor writing a file in several
+/// steps, into a single event.
+#[derive(Debug)]
+struct Debouncer {
+    delay: Duration,
+    pending: Vec<(FileEvent, Instant)>,
+}
+
+impl Debouncer {
+    fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            pending: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, event: FileEvent, now: Instant) {
+        let previous = self
+            .pending
+            .iter()
+            .position(|(pending, _)| pending.path() == event.path())
+            .map(|i| self.pending.remove(i).0);
+
+        let event = match (previous, event) {
+            (None, event) => Some(event),
+            (Some(FileEvent::Created(path)), FileEvent::Modified(_)) => {
+                Some(FileEvent::Created(path))
+            }
+            (Some(FileEvent::Created(_)), FileEvent::Removed(_)) => None,
+            (Some(FileEvent::Removed(path)), FileEvent::Created(_)) => {
+                Some(FileEvent::Modified(path))
+            }
+            (Some(FileEvent::Renamed { from, to }), FileEvent::Modified(_)) => {
+                Some(FileEvent::Renamed { from, to })
+            }
+            (Some(FileEvent::Renamed { from, .. }), FileEvent::Removed(_)) => {
+                Some(FileEvent::Removed(from))
+            }
+            (Some(_), event) => Some(event),
         };
 
-        match metadata.modified() {
-            Ok(t) => {
-                if t.duration_since(self.last_modified).unwrap_or_default()
-                    > Duration::from_millis(10)
+        // A file written under a temporary name, then renamed over the target
+        let event = match event {
+            Some(FileEvent::Renamed { from, to }) => {
+                match self
+                    .pending
+                    .iter()
+                    .position(|(pending, _)| pending.path() == from)
                 {
-                    events.push(FileEvent::Modified(self.path.clone()));
-                    self.last_modified = t;
+                    Some(i) => match self.pending.remove(i).0 {
+                        FileEvent::Created(_) => Some(FileEvent::Created(to)),
+                        _ => Some(FileEvent::Renamed { from, to }),
+                    },
+                    None => Some(FileEvent::Renamed { from, to }),
                 }
             }
-            Err(_) => (),
+            event => event,
+        };
+
+        if let Some(event) = event {
+            self.pending.push((event, now));
+        }
+    }
+
+    /// Returns the events which have not changed for the debounce delay.
+    fn drain(&mut self, now: Instant) -> Vec<FileEvent> {
+        let mut ready = Vec::new();
+        let mut i = 0;
+        while i < self.pending.len() {
+            if now.duration_since(self.pending[i].1) >= self.delay {
+                ready.push(self.pending.remove(i).0);
+            } else {
+                i += 1;
+            }
         }
 
-        events
+        ready
+    }
+}
+
+struct Watcher {
+    path: PathBuf,
+    backend: Box<dyn WatcherBackend>,
+    debouncer: Debouncer,
+    events: Vec<FileEvent>,
+}
+
+impl Debug for Watcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watcher")
+            .field("path", &self.path)
+            .field("debouncer", &self.debouncer)
+            .finish()
+    }
+}
+
+impl Watcher {
+    fn new(path: &Path) -> Result<Self, WatcherError> {
+        #[cfg(target_os = "linux")]
+        match InotifyWatcher::new(path) {
+            Ok(backend) => return Ok(Self::with_backend(path, backend)),
+            Err(e) => util::tracing::warn!(
+                "Failed to watch {:?} with inotify, falling back to polling: {}",
+                path,
+                e
+            ),
+        }
+
+        Ok(Self::with_backend(path, PollWatcher::new(path)?))
+    }
+
+    fn with_backend(path: &Path, backend: impl WatcherBackend) -> Self {
+        Self {
+            path: path.to_owned(),
+            backend: Box::new(backend),
+            debouncer: Debouncer::new(DEFAULT_DEBOUNCE),
+            events: Vec::new(),
+        }
+    }
+
+    fn changes(&mut self, now: Instant) -> Vec<FileEvent> {
+        self.backend.poll(&mut self.events);
+        for event in self.events.drain(..) {
+            self.debouncer.push(event, now);
+        }
+
+        self.debouncer.drain(now)
     }
 }
 
-/// Emits [`FileEvent`].
+/// Emits [`FileEvent`] for a single file.
 #[derive(WinnyComponent, Debug)]
 pub struct FileWatcher {
-    state: FileState,
+    watcher: Watcher,
 }
 
 #[cfg(feature = "widgets")]
 impl Widget for FileWatcher {
     fn display(&mut self, ui: &mut ecs::egui::Ui) {
-        self.state.display(ui);
+        ui.label(format!("{:?}", self.path()).as_str());
     }
 }
 
 impl FileWatcher {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, WatcherError> {
+        if !path.as_ref().is_file() {
+            return Err(WatcherError::FileStateIsNotFile);
+        }
+
         Ok(Self {
-            state: FileState::new(path)?,
+            watcher: Watcher::new(path.as_ref())?,
         })
     }
 
+    pub fn with_backend<P: AsRef<Path>>(path: P, backend: impl WatcherBackend) -> Self {
+        Self {
+            watcher: Watcher::with_backend(path.as_ref(), backend),
+        }
+    }
+
+    /// Sets the delay after the last event for the file before it is reported. Defaults to
+    /// [`DEFAULT_DEBOUNCE`].
+    pub fn with_debounce(mut self, delay: Duration) -> Self {
+        self.watcher.debouncer.delay = delay;
+        self
+    }
+
     pub fn changes(&mut self) -> Vec<FileEvent> {
-        self.state.events()
+        self.watcher.changes(Instant::now())
     }
 
     pub fn path(&self) -> &Path {
-        &self.state.path
+        &self.watcher.path
     }
 }
 
-/// TODO: watch for added and removed files.
-/// Collection of [`FileWatcher`]s.
+/// Emits [`FileEvent`] for every file in a directory, recursively.
 #[derive(WinnyComponent, Debug)]
 pub struct DirWatcher {
-    path: PathBuf,
-    file_watchers: Vec<FileWatcher>,
+    watcher: Watcher,
 }
 
 impl DirWatcher {
@@ -180,45 +597,30 @@ impl DirWatcher {
             return Err(WatcherError::DirIsNotDir);
         }
 
-        fn read_dir(path: &Path, file_watchers: &mut Vec<FileWatcher>) -> Result<(), WatcherError> {
-            let dir = match std::fs::read_dir(path) {
-                Ok(dir) => dir,
-                Err(_) => return Err(WatcherError::ReadDirectory),
-            };
-
-            for entry in dir {
-                match entry {
-                    Ok(entry) => {
-                        let path = entry.path();
-                        if path.is_dir() {
-                            read_dir(&path, file_watchers)?;
-                        } else {
-                            file_watchers.push(FileWatcher::new(path)?);
-                        }
-                    }
-                    Err(_) => return Err(WatcherError::Entry),
-                }
-            }
+        Ok(Self {
+            watcher: Watcher::new(path.as_ref())?,
+        })
+    }
 
-            Ok(())
+    pub fn with_backend<P: AsRef<Path>>(path: P, backend: impl WatcherBackend) -> Self {
+        Self {
+            watcher: Watcher::with_backend(path.as_ref(), backend),
         }
+    }
 
-        let mut file_watchers = Vec::new();
-        read_dir(path.as_ref(), &mut file_watchers)?;
-        let path = path.as_ref().into();
-
-        Ok(Self {
-            path,
-            file_watchers,
-        })
+    /// Sets the delay after the last event for a file before it is reported. Defaults to
+    /// [`DEFAULT_DEBOUNCE`].
+    pub fn with_debounce(mut self, delay: Duration) -> Self {
+        self.watcher.debouncer.delay = delay;
+        self
     }
 
-    pub fn path(&self) -> &Path {
-        &self.path
+    pub fn changes(&mut self) -> Vec<FileEvent> {
+        self.watcher.changes(Instant::now())
     }
 
-    pub fn watchers(&mut self) -> &mut [FileWatcher] {
-        &mut self.file_watchers
+    pub fn path(&self) -> &Path {
+        &self.watcher.path
     }
 }
 
@@ -239,3 +641,87 @@ impl Display for WatcherError {
 }
 
 impl std::error::Error for WatcherError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debounce() {
+        let start = Instant::now();
+        let later = start + DEFAULT_DEBOUNCE;
+        let mut debouncer = Debouncer::new(DEFAULT_DEBOUNCE);
+
+        debouncer.push(FileEvent::Modified("a.txt".into()), start);
+        debouncer.push(FileEvent::Modified("a.txt".into()), start);
+        debouncer.push(FileEvent::Created("b.txt".into()), start);
+        debouncer.push(FileEvent::Removed("b.txt".into()), start);
+        assert!(debouncer.drain(start).is_empty());
+        assert_eq!(
+            debouncer.drain(later),
+            vec![FileEvent::Modified("a.txt".into())]
+        );
+
+        debouncer.push(FileEvent::Created("a.txt~".into()), start);
+        debouncer.push(FileEvent::Modified("a.txt~".into()), start);
+        debouncer.push(
+            FileEvent::Renamed {
+                from: "a.txt~".into(),
+                to: "a.txt".into(),
+            },
+            start,
+        );
+        assert_eq!(
+            debouncer.drain(later),
+            vec![FileEvent::Created("a.txt".into())]
+        );
+    }
+
+    fn backend_events(backend: &mut dyn WatcherBackend) -> Vec<FileEvent> {
+        let mut events = Vec::new();
+        backend.poll(&mut events);
+        events.sort_by(|a, b| a.path().cmp(b.path()));
+        events
+    }
+
+    fn check_backend<B: WatcherBackend>(name: &str, new: impl Fn(&Path) -> B) {
+        let dir = std::env::temp_dir().join(format!("winny_watcher_{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub/a.txt"), "a").unwrap();
+
+        let mut backend = new(&dir);
+        assert!(backend_events(&mut backend).is_empty());
+
+        std::fs::create_dir(dir.join("new")).unwrap();
+        std::fs::write(dir.join("new/b.txt"), "b").unwrap();
+        std::fs::remove_file(dir.join("sub/a.txt")).unwrap();
+        let events = backend_events(&mut backend);
+        assert!(events.contains(&FileEvent::Created(dir.join("new/b.txt"))));
+        assert!(events.contains(&FileEvent::Removed(dir.join("sub/a.txt"))));
+
+        // Watches directories created after the watcher
+        std::fs::rename(dir.join("new/b.txt"), dir.join("new/c.txt")).unwrap();
+        let events = backend_events(&mut backend);
+        assert!(
+            events.contains(&FileEvent::Created(dir.join("new/c.txt")))
+                || events.contains(&FileEvent::Renamed {
+                    from: dir.join("new/b.txt"),
+                    to: dir.join("new/c.txt"),
+                })
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn poll_backend() {
+        check_backend("poll", |dir| PollWatcher::new(dir).unwrap());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn inotify_backend() {
+        check_backend("inotify", |dir| InotifyWatcher::new(dir).unwrap());
+    }
+}
@@ -0,0 +1,286 @@
+//! Backends render the [`Mixer`] to an output. [`CpalBackend`] plays through the default output
+//! device. [`NullBackend`] and [`WavBackend`] render a frame's worth of audio every frame, so audio
+//! systems run on machines without an output device.
+//!
+//! ```ignore
+//! // After adding the default plugins
+//! app.insert_resource(AudioOutput::with_backend(MixerConfig::default(), NullBackend::default()));
+//! ```
+
+use crate::{
+    mixer::{Mixer, MixerConfig},
+    Error,
+};
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    Device, SampleFormat, StreamConfig, SupportedStreamConfigRange,
+};
+use hound::{WavSpec, WavWriter};
+use std::{fs::File, io::BufWriter, path::PathBuf, time::Duration};
+use util::tracing::error;
+
+pub trait AudioBackend: Send + Sync + 'static {
+    /// Prepares the output for the requested `config`. Returns the config the output runs at,
+    /// which the [`Mixer`] is built with. The [`crate::AudioOutput`] falls back to a
+    /// [`NullBackend`] if this fails.
+    fn open(&mut self, config: &MixerConfig) -> Result<MixerConfig, Error>;
+
+    /// Starts rendering `mixer` after the backend is opened.
+    fn start(&mut self, mixer: Mixer) -> Result<(), Error>;
+
+    /// Called every frame with the time since the last frame. Backends without their own audio
+    /// thread render here.
+    fn update(&mut self, _delta: Duration) {}
+}
+
+macro_rules! map_stream_err {
+    ($err:expr, $f:expr) => {
+        $f.map_err(|err| {
+            error!("{:?}", err);
+            $err
+        })
+    };
+}
+
+/// Kept alive so the stream keeps playing.
+struct OutputStream(#[allow(dead_code)] cpal::Stream);
+
+unsafe impl Sync for OutputStream {}
+unsafe impl Send for OutputStream {}
+
+/// Plays through the default output device of the default host, at the device's default sample
+/// rate and channel count rather than those of the requested [`MixerConfig`].
+#[derive(Default)]
+pub struct CpalBackend {
+    device: Option<(Device, StreamConfig)>,
+    stream: Option<OutputStream>,
+}
+
+impl AudioBackend for CpalBackend {
+    fn open(&mut self, config: &MixerConfig) -> Result<MixerConfig, Error> {
+        let host = cpal::default_host();
+        let device = map_stream_err!(Error::HostNA, host.default_output_device().ok_or(()))?;
+        let default = map_stream_err!(
+            Error::SupportedOutputConfigNA,
+            device.default_output_config()
+        )?;
+        let supported = if default.sample_format() == SampleFormat::F32 {
+            default
+        } else {
+            // The f32 config closest to the device's default
+            let rate = default.sample_rate().0;
+            let closest_rate = |supported: &SupportedStreamConfigRange| {
+                rate.clamp(supported.min_sample_rate().0, supported.max_sample_rate().0)
+            };
+            map_stream_err!(
+                Error::SupportedOutputConfigNA,
+                device.supported_output_configs()
+            )?
+            .filter(|supported| supported.sample_format() == SampleFormat::F32)
+            .min_by_key(|supported| {
+                (
+                    supported.channels().abs_diff(default.channels()),
+                    closest_rate(supported).abs_diff(rate),
+                )
+            })
+            .map(|supported| {
+                let rate = closest_rate(&supported);
+                supported.with_sample_rate(cpal::SampleRate(rate))
+            })
+            .ok_or_else(|| {
+                error!("No f32 output config");
+                Error::OutputConfigNotSupported
+            })?
+        };
+        let stream_config: StreamConfig = supported.into();
+        let config = MixerConfig {
+            sample_rate: stream_config.sample_rate.0,
+            channels: stream_config.channels,
+            ..*config
+        };
+        self.device = Some((device, stream_config));
+
+        Ok(config)
+    }
+
+    fn start(&mut self, mut mixer: Mixer) -> Result<(), Error> {
+        let (device, config) = self.device.as_ref().ok_or(Error::HostNA)?;
+        let stream = map_stream_err!(
+            Error::BuildStream,
+            device.build_output_stream(
+                config,
+                move |output: &mut [f32], _: &cpal::OutputCallbackInfo| mixer.render(output),
+                move |err| error!("Error in audio stream: {}", err),
+                None,
+            )
+        )?;
+        map_stream_err!(Error::PlayStream, stream.play())?;
+        self.stream = Some(OutputStream(stream));
+
+        Ok(())
+    }
+}
+
+/// Renders the frames played since the last update.
+struct FrameRenderer {
+    mixer: Mixer,
+    buffer: Vec<f32>,
+    /// Fraction of a frame carried to the next update.
+    remainder: f64,
+}
+
+impl FrameRenderer {
+    fn new(mut mixer: Mixer) -> Self {
+        mixer.set_offline(true);
+        Self {
+            mixer,
+            buffer: Vec::new(),
+            remainder: 0.0,
+        }
+    }
+
+    fn render(&mut self, delta: Duration) -> &[f32] {
+        let config = self.mixer.config();
+        let frames = delta.as_secs_f64() * config.sample_rate as f64 + self.remainder;
+        self.remainder = frames.fract();
+
+        self.buffer.clear();
+        self.buffer
+            .resize(frames as usize * config.channels as usize, 0.0);
+        self.mixer.render(&mut self.buffer);
+
+        &self.buffer
+    }
+}
+
+/// Renders the mixer and discards the output, for tests and dedicated servers.
+#[derive(Default)]
+pub struct NullBackend {
+    renderer: Option<FrameRenderer>,
+}
+
+impl AudioBackend for NullBackend {
+    fn open(&mut self, config: &MixerConfig) -> Result<MixerConfig, Error> {
+        Ok(*config)
+    }
+
+    fn start(&mut self, mixer: Mixer) -> Result<(), Error> {
+        self.renderer = Some(FrameRenderer::new(mixer));
+        Ok(())
+    }
+
+    fn update(&mut self, delta: Duration) {
+        if let Some(renderer) = &mut self.renderer {
+            renderer.render(delta);
+        }
+    }
+}
+
+/// Records the output to a 32 bit float WAV file. The file is valid after every update.
+pub struct WavBackend {
+    path: PathBuf,
+    writer: Option<WavWriter<BufWriter<File>>>,
+    renderer: Option<FrameRenderer>,
+}
+
+impl WavBackend {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            writer: None,
+            renderer: None,
+        }
+    }
+}
+
+impl AudioBackend for WavBackend {
+    fn open(&mut self, config: &MixerConfig) -> Result<MixerConfig, Error> {
+        let spec = WavSpec {
+            channels: config.channels,
+            sample_rate: config.sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        self.writer = Some(map_stream_err!(
+            Error::WavWriter,
+            WavWriter::create(&self.path, spec)
+        )?);
+
+        Ok(*config)
+    }
+
+    fn start(&mut self, mixer: Mixer) -> Result<(), Error> {
+        self.renderer = Some(FrameRenderer::new(mixer));
+        Ok(())
+    }
+
+    fn update(&mut self, delta: Duration) {
+        let (Some(writer), Some(renderer)) = (&mut self.writer, &mut self.renderer) else {
+            return;
+        };
+
+        let result = renderer
+            .render(delta)
+            .iter()
+            .try_for_each(|sample| writer.write_sample(*sample))
+            .and_then(|_| writer.flush());
+        if let Err(e) = result {
+            error!("Could not write audio to {:?}: {e}", self.path);
+            self.writer = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mixer::MixerEvent, AudioBus, AudioOutput, AudioSource, PlaybackSettings};
+
+    #[test]
+    fn render_to_wav() {
+        let config = MixerConfig {
+            sample_rate: 1000,
+            channels: 2,
+            max_voices: 4,
+        };
+        let path = std::env::temp_dir().join(format!("winny-audio-{}.wav", std::process::id()));
+        let mut output = AudioOutput::with_backend(config, WavBackend::new(&path));
+        output.start().unwrap();
+
+        let source = AudioSource {
+            samples: vec![0.5; 150].into(),
+            channels: 1,
+            sample_rate: 1000,
+        };
+        let voice = output
+            .mixer()
+            .play(&source, PlaybackSettings::default(), AudioBus::Sfx);
+        for _ in 0..2 {
+            output.backend.update(Duration::from_millis(100));
+        }
+        assert_eq!(
+            output.mixer().events().collect::<Vec<_>>(),
+            [MixerEvent::Finished(voice)]
+        );
+
+        let samples = hound::WavReader::open(&path)
+            .unwrap()
+            .into_samples::<f32>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(samples.len(), 400);
+        assert!(samples[..300].iter().all(|s| *s == 0.5));
+        assert!(samples[300..].iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn fall_back_to_null() {
+        let mut output = AudioOutput::with_backend(
+            MixerConfig::default(),
+            WavBackend::new("/nonexistent/output.wav"),
+        );
+        assert!(output.start().is_ok());
+        output.backend.update(Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,213 @@
+//! Buses group voices so they share a volume. Every bus is routed into another bus, ending at
+//! [`AudioBus::Master`].
+
+use crate::AudioOutput;
+use ecs::{egui, egui_widget::Widget, Local, Res, WinnyAsEgui, WinnyComponent, WinnyResource};
+
+/// The bus a sound plays on. Added to an entity with [`crate::AudioBundle`].
+#[derive(WinnyComponent, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudioBus {
+    Master,
+    Music,
+    #[default]
+    Sfx,
+    Voice,
+    Ui,
+}
+
+impl AudioBus {
+    pub const COUNT: usize = 5;
+    pub const ALL: [AudioBus; Self::COUNT] = [
+        AudioBus::Master,
+        AudioBus::Music,
+        AudioBus::Sfx,
+        AudioBus::Voice,
+        AudioBus::Ui,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Master => "Master",
+            Self::Music => "Music",
+            Self::Sfx => "Sfx",
+            Self::Voice => "Voice",
+            Self::Ui => "Ui",
+        }
+    }
+}
+
+impl Widget for AudioBus {
+    fn display(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("output")
+            .selected_text(self.name())
+            .show_ui(ui, |ui| {
+                for bus in AudioBus::ALL {
+                    ui.selectable_value(self, bus, bus.name());
+                }
+            });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BusSettings {
+    pub volume: f32,
+    pub muted: bool,
+    /// While any bus is soloed, only soloed buses and the buses routed into them are heard.
+    pub solo: bool,
+    /// Set with [`AudioBuses::route`]. Ignored for [`AudioBus::Master`].
+    output: AudioBus,
+}
+
+impl Default for BusSettings {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            muted: false,
+            solo: false,
+            output: AudioBus::Master,
+        }
+    }
+}
+
+impl BusSettings {
+    pub fn output(&self) -> AudioBus {
+        self.output
+    }
+}
+
+impl Widget for BusSettings {
+    fn display(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.volume, 0.0..=1.0).text("volume"));
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.muted, "muted");
+            ui.checkbox(&mut self.solo, "solo");
+        });
+        ui.label(format!("output: {}", self.output.name()));
+    }
+}
+
+/// Volume, mute, solo and routing of every [`AudioBus`].
+///
+/// ```ignore
+/// fn music_slider(mut buses: ResMut<AudioBuses>) {
+///     buses.music.volume = 0.5;
+/// }
+/// ```
+#[derive(WinnyResource, WinnyAsEgui, Debug, Default, Clone, Copy, PartialEq)]
+pub struct AudioBuses {
+    pub master: BusSettings,
+    pub music: BusSettings,
+    pub sfx: BusSettings,
+    pub voice: BusSettings,
+    pub ui: BusSettings,
+}
+
+impl AudioBuses {
+    pub fn get(&self, bus: AudioBus) -> &BusSettings {
+        match bus {
+            AudioBus::Master => &self.master,
+            AudioBus::Music => &self.music,
+            AudioBus::Sfx => &self.sfx,
+            AudioBus::Voice => &self.voice,
+            AudioBus::Ui => &self.ui,
+        }
+    }
+
+    pub fn get_mut(&mut self, bus: AudioBus) -> &mut BusSettings {
+        match bus {
+            AudioBus::Master => &mut self.master,
+            AudioBus::Music => &mut self.music,
+            AudioBus::Sfx => &mut self.sfx,
+            AudioBus::Voice => &mut self.voice,
+            AudioBus::Ui => &mut self.ui,
+        }
+    }
+
+    /// Routes `bus` into `output`. Returns false, leaving the routing unchanged, if `bus` is
+    /// [`AudioBus::Master`] or the route would form a cycle.
+    pub fn route(&mut self, bus: AudioBus, output: AudioBus) -> bool {
+        if bus == AudioBus::Master || self.path(output).any(|next| next == bus) {
+            return false;
+        }
+
+        self.get_mut(bus).output = output;
+        true
+    }
+
+    /// Gain of a voice played on `bus`, after every bus on its route to [`AudioBus::Master`].
+    pub fn gain(&self, bus: AudioBus) -> f32 {
+        let any_solo = AudioBus::ALL.iter().any(|bus| self.get(*bus).solo);
+        if any_solo && !self.path(bus).any(|bus| self.get(bus).solo) {
+            return 0.0;
+        }
+
+        self.path(bus)
+            .map(|bus| self.get(bus))
+            .map(|settings| match settings.muted {
+                true => 0.0,
+                false => settings.volume,
+            })
+            .product()
+    }
+
+    /// `bus` and every bus it is routed through, ending at [`AudioBus::Master`].
+    fn path(&self, bus: AudioBus) -> impl Iterator<Item = AudioBus> + '_ {
+        // Routes are acyclic, so every path reaches master within COUNT steps
+        std::iter::successors(Some(bus), |bus| match bus {
+            AudioBus::Master => None,
+            bus => Some(self.get(*bus).output),
+        })
+        .take(AudioBus::COUNT)
+    }
+}
+
+pub(crate) fn update_bus_gains(
+    output: Res<AudioOutput>,
+    buses: Res<AudioBuses>,
+    mut last: Local<Option<([f32; AudioBus::COUNT], [AudioBus; AudioBus::COUNT])>>,
+) {
+    let gains = AudioBus::ALL.map(|bus| buses.gain(bus));
+    let outputs = AudioBus::ALL.map(|bus| buses.get(bus).output);
+    for bus in AudioBus::ALL {
+        let i = bus.index();
+        if last.map(|(gains, _)| gains[i]) != Some(gains[i]) {
+            output.mixer().set_bus_gain(bus, gains[i]);
+        }
+        if last.map(|(_, outputs)| outputs[i]) != Some(outputs[i]) {
+            output.mixer().set_bus_output(bus, outputs[i]);
+        }
+    }
+    *last = Some((gains, outputs));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routing_mute_and_solo() {
+        let mut buses = AudioBuses::default();
+        buses.master.volume = 0.5;
+        buses.music.volume = 0.5;
+        assert_eq!(buses.gain(AudioBus::Music), 0.25);
+
+        // Voice lines are ducked with the music
+        assert!(buses.route(AudioBus::Voice, AudioBus::Music));
+        assert!(!buses.route(AudioBus::Music, AudioBus::Voice));
+        assert!(!buses.route(AudioBus::Master, AudioBus::Sfx));
+        assert_eq!(buses.gain(AudioBus::Voice), 0.25);
+
+        buses.music.muted = true;
+        assert_eq!(buses.gain(AudioBus::Voice), 0.0);
+        assert_eq!(buses.gain(AudioBus::Sfx), 0.5);
+
+        buses.music.muted = false;
+        buses.music.solo = true;
+        assert_eq!(buses.gain(AudioBus::Voice), 0.25);
+        assert_eq!(buses.gain(AudioBus::Sfx), 0.0);
+    }
+}
@@ -0,0 +1,117 @@
+//! Controls [`AudioPlayback`]s with events, so gameplay systems do not need to query them.
+//!
+//! ```ignore
+//! fn duck_engine(player: Query<Entity, With<Engine>>, mut audio: EventWriter<AudioCommand>) {
+//!     for entity in player.iter() {
+//!         audio.send(AudioCommand::SetVolume {
+//!             entity,
+//!             volume: 0.2,
+//!             ramp: Duration::from_millis(250),
+//!         });
+//!     }
+//! }
+//! ```
+
+use crate::{AudioPlayback, PlaybackSettings};
+use ecs::{Entity, EventReader, Local, Mut, Query, WinnyEvent, With, Without};
+use std::time::Duration;
+
+/// Controls the [`AudioPlayback`] of an entity. Commands sent while its source is loading are
+/// applied once it starts playing.
+#[derive(WinnyEvent, Debug, Clone, Copy, PartialEq)]
+pub enum AudioCommand {
+    Play(Entity),
+    Pause(Entity),
+    /// The entity is despawned, and [`AudioFinished`] is sent.
+    Stop(Entity),
+    /// Ramps the volume linearly over `ramp`, or sets it immediately if `ramp` is zero.
+    SetVolume {
+        entity: Entity,
+        volume: f32,
+        ramp: Duration,
+    },
+    /// Ramps the speed linearly over `ramp`, or sets it immediately if `ramp` is zero.
+    SetSpeed {
+        entity: Entity,
+        speed: f32,
+        ramp: Duration,
+    },
+    Seek(Entity, Duration),
+}
+
+impl AudioCommand {
+    pub fn entity(&self) -> Entity {
+        match self {
+            Self::Play(entity)
+            | Self::Pause(entity)
+            | Self::Stop(entity)
+            | Self::SetVolume { entity, .. }
+            | Self::SetSpeed { entity, .. }
+            | Self::Seek(entity, _) => *entity,
+        }
+    }
+}
+
+/// Sent when the sound of an entity played to the end, was stopped or was stolen by a newer
+/// sound. The entity is despawned.
+#[derive(WinnyEvent, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioFinished(pub Entity);
+
+impl AudioPlayback {
+    /// Ramps the volume linearly over `ramp`, or sets it immediately if `ramp` is zero.
+    pub fn set_volume(&self, volume: f32, ramp: Duration) {
+        match ramp.is_zero() {
+            true => self.mixer.set_volume(self.voice, volume),
+            false => self.mixer.fade(self.voice, volume, ramp),
+        }
+    }
+
+    /// Ramps the speed linearly over `ramp`, or sets it immediately if `ramp` is zero.
+    pub fn set_speed(&self, speed: f32, ramp: Duration) {
+        match ramp.is_zero() {
+            true => self.mixer.set_speed(self.voice, speed),
+            false => self.mixer.ramp_speed(self.voice, speed, ramp),
+        }
+    }
+
+    /// Continues playing from `position` in the source.
+    pub fn seek(&self, position: Duration) {
+        self.mixer.seek(self.voice, position);
+    }
+}
+
+pub(crate) fn apply_audio_commands(
+    reader: EventReader<AudioCommand>,
+    playbacks: Query<(AudioPlayback, Mut<PlaybackSettings>)>,
+    loading: Query<Entity, (With<PlaybackSettings>, Without<AudioPlayback>)>,
+    mut pending: Local<Vec<AudioCommand>>,
+) {
+    let commands = std::mem::take(&mut *pending)
+        .into_iter()
+        .chain(reader.peak_read().copied())
+        .collect::<Vec<_>>();
+    for command in commands {
+        let Some((playback, settings)) = playbacks.get_mut(command.entity()) else {
+            if loading.get(command.entity()).is_some() {
+                pending.push(command);
+            }
+            continue;
+        };
+
+        // The settings follow the target, so the inspector shows the current volume and speed
+        match command {
+            AudioCommand::Play(_) => playback.play(),
+            AudioCommand::Pause(_) => playback.pause(),
+            AudioCommand::Stop(_) => playback.stop(),
+            AudioCommand::SetVolume { volume, ramp, .. } => {
+                playback.set_volume(volume, ramp);
+                settings.volume = volume;
+            }
+            AudioCommand::SetSpeed { speed, ramp, .. } => {
+                playback.set_speed(speed, ramp);
+                settings.speed = speed;
+            }
+            AudioCommand::Seek(_, position) => playback.seek(position),
+        }
+    }
+}
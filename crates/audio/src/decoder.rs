@@ -0,0 +1,657 @@
+//! Decodes WAV, Ogg Vorbis, FLAC and MP3 into interleaved `f32` samples.
+
+use asset::AssetFile;
+#[cfg(not(target_arch = "wasm32"))]
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use std::{
+    fmt::Display,
+    io::{Cursor, Read, Seek, SeekFrom},
+    sync::Arc,
+};
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL},
+    errors::Error as SymphoniaError,
+    formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
+    io::{MediaSource, MediaSourceStream},
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+/// Extensions of the formats which can be decoded.
+pub const AUDIO_EXTENSIONS: &[&str] = &["wav", "ogg", "oga", "flac", "mp3"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    UnsupportedFormat,
+    /// The file contains no audio track.
+    NoTrack,
+    /// The file could not be opened.
+    Open(String),
+    Decode(String),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedFormat => write!(f, "unsupported audio format"),
+            Self::NoTrack => write!(f, "no audio track"),
+            Self::Open(e) => write!(f, "failed to open audio file: {e}"),
+            Self::Decode(e) => write!(f, "failed to decode audio: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<SymphoniaError> for DecodeError {
+    fn from(value: SymphoniaError) -> Self {
+        match value {
+            SymphoniaError::Unsupported(_) => Self::UnsupportedFormat,
+            e => Self::Decode(e.to_string()),
+        }
+    }
+}
+
+/// [`AssetFile`] read by symphonia.
+struct FileSource {
+    file: Box<dyn AssetFile>,
+    len: u64,
+}
+
+impl FileSource {
+    fn new(mut file: Box<dyn AssetFile>) -> std::io::Result<Self> {
+        let len = file.seek(SeekFrom::End(0))?;
+        file.seek(SeekFrom::Start(0))?;
+
+        Ok(Self { file, len })
+    }
+}
+
+impl Read for FileSource {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Seek for FileSource {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl MediaSource for FileSource {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        Some(self.len)
+    }
+}
+
+/// Decodes an encoded file a block at a time, so long tracks are never held decoded in memory.
+pub struct AudioDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track: u32,
+    channels: u16,
+    sample_rate: u32,
+    frames: Option<u64>,
+    buffer: Option<SampleBuffer<f32>>,
+    /// Interleaved samples of the last decoded block.
+    block: Vec<f32>,
+    /// Decoded in [`AudioDecoder::new`] to find the channels and sample rate.
+    primed: bool,
+    /// Frames decoded before the target of the last seek.
+    skip: usize,
+    /// Frame after the last decoded block.
+    position: u64,
+}
+
+impl std::fmt::Debug for AudioDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioDecoder")
+            .field("channels", &self.channels)
+            .field("sample_rate", &self.sample_rate)
+            .field("frames", &self.frames)
+            .finish()
+    }
+}
+
+impl AudioDecoder {
+    /// Probes the container format, using `ext` as a hint.
+    pub fn new(bytes: Arc<[u8]>, ext: &str) -> Result<Self, DecodeError> {
+        Self::from_source(Box::new(Cursor::new(bytes)), ext)
+    }
+
+    /// Decodes `file` as it is read, rather than reading it whole.
+    pub fn from_file(file: Box<dyn AssetFile>, ext: &str) -> Result<Self, DecodeError> {
+        let file = FileSource::new(file).map_err(|e| DecodeError::Open(e.to_string()))?;
+        Self::from_source(Box::new(file), ext)
+    }
+
+    fn from_source(source: Box<dyn MediaSource>, ext: &str) -> Result<Self, DecodeError> {
+        let stream = MediaSourceStream::new(source, Default::default());
+        let mut hint = Hint::new();
+        hint.with_extension(ext);
+        let probed = symphonia::default::get_probe().format(
+            &hint,
+            stream,
+            &FormatOptions {
+                enable_gapless: true,
+                ..Default::default()
+            },
+            &MetadataOptions::default(),
+        )?;
+
+        let format = probed.format;
+        let track = format
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or(DecodeError::NoTrack)?;
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())?;
+
+        let mut decoder = Self {
+            track: track.id,
+            frames: track.codec_params.n_frames,
+            channels: track
+                .codec_params
+                .channels
+                .map(|channels| channels.count() as u16)
+                .unwrap_or_default(),
+            sample_rate: track.codec_params.sample_rate.unwrap_or_default(),
+            format,
+            decoder,
+            buffer: None,
+            block: Vec::new(),
+            primed: false,
+            skip: 0,
+            position: 0,
+        };
+        decoder.decode_block()?;
+        decoder.primed = !decoder.block.is_empty();
+
+        Ok(decoder)
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Length of the track, if the container stores it.
+    pub fn frames(&self) -> Option<u64> {
+        self.frames
+    }
+
+    /// Decodes the next block of interleaved samples. Returns `None` at the end of the track.
+    pub fn next_block(&mut self) -> Result<Option<&[f32]>, DecodeError> {
+        if !std::mem::take(&mut self.primed) && !self.decode_block()? {
+            return Ok(None);
+        }
+
+        Ok(Some(&self.block))
+    }
+
+    /// Continues decoding from `frame`.
+    pub fn seek(&mut self, frame: u64) -> Result<(), DecodeError> {
+        let seeked = self.format.seek(
+            SeekMode::Accurate,
+            SeekTo::TimeStamp {
+                ts: frame,
+                track_id: self.track,
+            },
+        )?;
+        self.decoder.reset();
+        self.primed = false;
+        self.skip = seeked.required_ts.saturating_sub(seeked.actual_ts) as usize;
+        self.position = seeked.required_ts;
+
+        Ok(())
+    }
+
+    /// Decodes into the buffer, returning false at the end of the track.
+    fn decode_block(&mut self) -> Result<bool, DecodeError> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(false)
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if packet.track_id() != self.track {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // Corrupt packets are skipped
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let spec = *decoded.spec();
+            self.channels = spec.channels.count() as u16;
+            self.sample_rate = spec.rate;
+
+            let frames = decoded.frames();
+            if self.skip >= frames {
+                self.skip -= frames;
+                continue;
+            }
+
+            let buffer = match &mut self.buffer {
+                Some(buffer) if buffer.capacity() >= decoded.capacity() * spec.channels.count() => {
+                    buffer
+                }
+                buffer => buffer.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+            };
+            buffer.copy_interleaved_ref(decoded);
+            let skipped = std::mem::take(&mut self.skip) * spec.channels.count();
+            self.block.clear();
+            self.block.extend_from_slice(&buffer.samples()[skipped..]);
+
+            // Some encoders pad the last block past the length of the track
+            let channels = spec.channels.count();
+            let mut frames = (self.block.len() / channels) as u64;
+            if let Some(remaining) = self.frames.map(|len| len.saturating_sub(self.position)) {
+                frames = frames.min(remaining);
+                self.block.truncate(frames as usize * channels);
+            }
+            self.position += frames;
+
+            return Ok(true);
+        }
+    }
+}
+
+/// Frames decoded by [`StreamDecoder::new`], before the stream is played. Kept to start and loop
+/// the stream without waiting on the decoder.
+const HEAD_FRAMES: usize = 8192;
+/// Blocks decoded ahead of the [`crate::mixer::Mixer`].
+#[cfg(not(target_arch = "wasm32"))]
+const QUEUED_BLOCKS: usize = 16;
+
+/// Result of [`StreamDecoder::next`].
+pub(crate) enum StreamBlock {
+    Samples(Vec<f32>),
+    End,
+    /// The decoder has not caught up with playback.
+    Pending,
+}
+
+/// Decodes a stream for the [`crate::mixer::Mixer`].
+///
+/// On native, blocks are decoded ahead on a worker thread and queued, so the audio thread only
+/// copies decoded samples. On wasm, blocks are decoded when they are requested.
+pub(crate) struct StreamDecoder {
+    channels: u16,
+    sample_rate: u32,
+    /// Interleaved samples from the start of the stream.
+    head: Vec<f32>,
+    /// The whole stream fits in `head`.
+    head_ended: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    worker: Option<DecodeWorker>,
+    #[cfg(target_arch = "wasm32")]
+    decoder: AudioDecoder,
+}
+
+impl StreamDecoder {
+    /// Decodes the head of the stream on the calling thread, then continues on a worker thread.
+    pub fn new(mut decoder: AudioDecoder) -> Self {
+        let mut head = Vec::new();
+        let mut head_ended = false;
+        while head.len() < HEAD_FRAMES * decoder.channels().max(1) as usize {
+            match decoder.next_block() {
+                Ok(Some(block)) => head.extend_from_slice(block),
+                Ok(None) => head_ended = true,
+                Err(e) => {
+                    util::tracing::error!("Error decoding audio stream: {e}");
+                    head_ended = true;
+                }
+            }
+            if head_ended {
+                break;
+            }
+        }
+
+        Self {
+            channels: decoder.channels(),
+            sample_rate: decoder.sample_rate(),
+            head,
+            head_ended,
+            #[cfg(not(target_arch = "wasm32"))]
+            worker: (!head_ended)
+                .then(|| DecodeWorker::spawn(decoder))
+                .flatten(),
+            #[cfg(target_arch = "wasm32")]
+            decoder,
+        }
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn head(&self) -> &[f32] {
+        &self.head
+    }
+
+    pub fn head_ended(&self) -> bool {
+        self.head_ended
+    }
+
+    /// Returns the next block after the head, or after the last seek. Blocks until the block is
+    /// decoded if `wait` is set, instead of returning [`StreamBlock::Pending`].
+    pub fn next(&mut self, wait: bool) -> StreamBlock {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            match &mut self.worker {
+                Some(worker) => worker.next(wait),
+                None => StreamBlock::End,
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = wait;
+            match self.decoder.next_block() {
+                Ok(Some(block)) => StreamBlock::Samples(block.to_vec()),
+                Ok(None) => StreamBlock::End,
+                Err(e) => {
+                    util::tracing::error!("Error decoding audio stream: {e}");
+                    StreamBlock::End
+                }
+            }
+        }
+    }
+
+    /// Returns a block from [`StreamDecoder::next`] once it is copied, so it is freed off the
+    /// audio thread.
+    pub fn recycle(&mut self, block: Vec<f32>) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(worker) = &self.worker {
+            let _ = worker.spent.try_send(block);
+        }
+        #[cfg(target_arch = "wasm32")]
+        drop(block);
+    }
+
+    /// Continues decoding from `frame`. Blocks decoded before the seek are discarded.
+    pub fn seek(&mut self, frame: u64) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(worker) = &mut self.worker {
+            worker.epoch += 1;
+            if worker.seeks.try_send((worker.epoch, frame)).is_err() {
+                util::tracing::error!("Audio stream seek queue is full");
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        if let Err(e) = self.decoder.seek(frame) {
+            util::tracing::error!("Could not seek audio stream: {e}");
+        }
+    }
+}
+
+/// Blocks sent by the worker, tagged with the seek they follow. `None` marks the end of the
+/// stream.
+#[cfg(not(target_arch = "wasm32"))]
+type EpochBlock = (u64, Option<Vec<f32>>);
+
+/// Decodes blocks ahead of playback. The thread exits once the [`StreamDecoder`] is dropped.
+#[cfg(not(target_arch = "wasm32"))]
+struct DecodeWorker {
+    blocks: Receiver<EpochBlock>,
+    seeks: Sender<(u64, u64)>,
+    spent: Sender<Vec<f32>>,
+    /// Of the last seek.
+    epoch: u64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DecodeWorker {
+    fn spawn(decoder: AudioDecoder) -> Option<Self> {
+        let (blocks_tx, blocks) = crossbeam_channel::bounded(QUEUED_BLOCKS);
+        let (seeks, seeks_rx) = crossbeam_channel::bounded(QUEUED_BLOCKS);
+        let (spent, spent_rx) = crossbeam_channel::bounded(QUEUED_BLOCKS);
+        let spawned = std::thread::Builder::new()
+            .name("audio stream".into())
+            .spawn(move || decode_ahead(decoder, blocks_tx, seeks_rx, spent_rx));
+        if let Err(e) = spawned {
+            util::tracing::error!("Could not spawn audio stream thread: {e}");
+            return None;
+        }
+
+        Some(Self {
+            blocks,
+            seeks,
+            spent,
+            epoch: 0,
+        })
+    }
+
+    fn next(&mut self, wait: bool) -> StreamBlock {
+        loop {
+            let received = if wait {
+                self.blocks.recv().map_err(|_| TryRecvError::Disconnected)
+            } else {
+                self.blocks.try_recv()
+            };
+
+            return match received {
+                Ok((epoch, block)) if epoch != self.epoch => {
+                    if let Some(block) = block {
+                        let _ = self.spent.try_send(block);
+                    }
+                    continue;
+                }
+                Ok((_, Some(block))) => StreamBlock::Samples(block),
+                Ok((_, None)) | Err(TryRecvError::Disconnected) => StreamBlock::End,
+                Err(TryRecvError::Empty) => StreamBlock::Pending,
+            };
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn decode_ahead(
+    mut decoder: AudioDecoder,
+    blocks: Sender<EpochBlock>,
+    seeks: Receiver<(u64, u64)>,
+    spent: Receiver<Vec<f32>>,
+) {
+    let mut epoch = 0;
+    let mut ended = false;
+    loop {
+        spent.try_iter().for_each(drop);
+
+        let seek = if ended {
+            seeks.recv()
+        } else {
+            let block = match decoder.next_block() {
+                Ok(block) => block.map(|block| block.to_vec()),
+                Err(e) => {
+                    util::tracing::error!("Error decoding audio stream: {e}");
+                    None
+                }
+            };
+            ended = block.is_none();
+
+            // A seek discards the block
+            crossbeam_channel::select! {
+                send(blocks, (epoch, block)) -> sent => match sent {
+                    Ok(()) => continue,
+                    Err(_) => return,
+                },
+                recv(seeks) -> seek => seek,
+            }
+        };
+
+        let Ok((seek_epoch, frame)) = seek else {
+            return;
+        };
+        epoch = seek_epoch;
+        ended = false;
+        if let Err(e) = decoder.seek(frame) {
+            util::tracing::error!("Could not seek audio stream: {e}");
+        }
+    }
+}
+
+/// Decodes the whole file.
+pub(crate) fn decode(bytes: Arc<[u8]>, ext: &str) -> Result<(Vec<f32>, u16, u32), DecodeError> {
+    let mut decoder = AudioDecoder::new(bytes, ext)?;
+    let mut samples = Vec::with_capacity(
+        decoder.frames().unwrap_or_default() as usize * decoder.channels() as usize,
+    );
+    while let Some(block) = decoder.next_block()? {
+        samples.extend_from_slice(block);
+    }
+
+    Ok((samples, decoder.channels(), decoder.sample_rate()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AudioStream;
+    use asset::{
+        ArchiveAssetReader, ArchiveCompression, ArchiveWriter, AssetFilePath, AssetSource,
+        FileAssetReader,
+    };
+
+    const FRAMES: usize = 8820;
+
+    fn wav() -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = Vec::new();
+        let mut writer = hound::WavWriter::new(Cursor::new(&mut wav), spec).unwrap();
+        for i in 0..FRAMES {
+            let s = (i as f32 * 440.0 * std::f32::consts::TAU / 44100.0).sin();
+            writer.write_sample((s * 0.5 * 32767.0) as i16).unwrap();
+            writer.write_sample((s * 0.25 * 32767.0) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        wav
+    }
+
+    fn peak(samples: &[f32], channel: usize) -> f32 {
+        samples
+            .iter()
+            .skip(channel)
+            .step_by(2)
+            .fold(0.0, |peak, s| f32::max(peak, s.abs()))
+    }
+
+    #[test]
+    fn decode_fixtures() {
+        // 0.2s of a 440hz sine, at 0.5 on the left and 0.25 on the right
+        for (ext, bytes) in [
+            ("wav", wav()),
+            ("ogg", include_bytes!("../fixtures/sine.ogg").to_vec()),
+            ("flac", include_bytes!("../fixtures/sine.flac").to_vec()),
+            ("mp3", include_bytes!("../fixtures/sine.mp3").to_vec()),
+        ] {
+            let (samples, channels, sample_rate) = decode(bytes.into(), ext).unwrap();
+            assert_eq!((channels, sample_rate), (2, 44100), "{ext}");
+            let frames = samples.len() / 2;
+            assert!(frames.abs_diff(FRAMES) < 1152, "{ext}: {frames} frames");
+            assert!((peak(&samples, 0) - 0.5).abs() < 0.05, "{ext}");
+            assert!((peak(&samples, 1) - 0.25).abs() < 0.05, "{ext}");
+        }
+
+        assert_eq!(
+            decode(Arc::from(b"not audio".as_slice()), "ogg").unwrap_err(),
+            DecodeError::UnsupportedFormat
+        );
+    }
+
+    #[test]
+    fn stream_and_seek() {
+        let bytes: Arc<[u8]> = include_bytes!("../fixtures/sine.flac").as_slice().into();
+        let (samples, ..) = decode(bytes.clone(), "flac").unwrap();
+
+        let mut decoder = AudioDecoder::new(bytes, "flac").unwrap();
+        assert_eq!(decoder.frames(), Some(FRAMES as u64));
+        let mut streamed = Vec::new();
+        while let Some(block) = decoder.next_block().unwrap() {
+            assert!(block.len() < samples.len());
+            streamed.extend_from_slice(block);
+        }
+        assert_eq!(streamed, samples);
+
+        decoder.seek(5000).unwrap();
+        let block = decoder.next_block().unwrap().unwrap();
+        assert_eq!(block[..8], samples[5000 * 2..5004 * 2]);
+    }
+
+    #[test]
+    fn decode_stream_ahead() {
+        let bytes: Arc<[u8]> = include_bytes!("../fixtures/sine.flac").as_slice().into();
+        let (samples, ..) = decode(bytes.clone(), "flac").unwrap();
+
+        let mut stream = StreamDecoder::new(AudioDecoder::new(bytes, "flac").unwrap());
+        assert!(!stream.head_ended());
+        assert_eq!(stream.head(), &samples[..stream.head().len()]);
+        let mut streamed = stream.head().to_vec();
+        while let StreamBlock::Samples(block) = stream.next(true) {
+            streamed.extend_from_slice(&block);
+            stream.recycle(block);
+        }
+        assert_eq!(streamed, samples);
+
+        // Blocks queued before the seek are discarded
+        stream.seek(5000);
+        let StreamBlock::Samples(block) = stream.next(true) else {
+            panic!("no block after seeking");
+        };
+        assert_eq!(block[..8], samples[5000 * 2..5004 * 2]);
+    }
+
+    #[test]
+    fn stream_from_file() {
+        let bytes: Arc<[u8]> = include_bytes!("../fixtures/sine.flac").as_slice().into();
+        let (samples, ..) = decode(bytes.clone(), "flac").unwrap();
+
+        let archive = std::env::temp_dir().join(format!("winny-audio-{}.pak", std::process::id()));
+        let mut writer = ArchiveWriter::new(ArchiveCompression::None);
+        writer.add("sine.flac", bytes.to_vec());
+        writer
+            .write(std::fs::File::create(&archive).unwrap())
+            .unwrap();
+
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+        for source in [
+            AssetSource::new(FileAssetReader::new(fixtures)),
+            AssetSource::new(ArchiveAssetReader::open(&archive).unwrap()),
+        ] {
+            let file = AssetFilePath::new(source, "sine.flac");
+            let stream = pollster::block_on(AudioStream::from_file(file, "flac")).unwrap();
+            let mut decoder = stream.decoder().unwrap();
+            let mut streamed = Vec::new();
+            while let Some(block) = decoder.next_block().unwrap() {
+                streamed.extend_from_slice(block);
+            }
+            assert_eq!(streamed, samples);
+        }
+        std::fs::remove_file(archive).unwrap();
+    }
+}
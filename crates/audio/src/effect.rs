@@ -0,0 +1,417 @@
+//! DSP effects, run by the [`crate::mixer::Mixer`] on a single voice or on everything mixed into a
+//! bus.
+//!
+//! ```ignore
+//! // Muffle the game while the player is underwater
+//! output.mixer().set_bus_effects(AudioBus::Sfx, vec![Box::new(Biquad::low_pass(500.0, 0.7))]);
+//! ```
+
+use std::{f32::consts::TAU, time::Duration};
+
+/// Processes interleaved blocks of samples in place. Runs on the audio thread, so it should not
+/// block.
+pub trait AudioEffect: Send + Sync + 'static {
+    fn process(&mut self, samples: &mut [f32], channels: usize, sample_rate: u32);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BiquadKind {
+    LowPass,
+    HighPass,
+}
+
+/// Second order low-pass or high-pass filter.
+#[derive(Debug, Clone)]
+pub struct Biquad {
+    pub kind: BiquadKind,
+    /// In hertz.
+    pub cutoff: f32,
+    /// Resonance at the cutoff. 0.707 has no peak.
+    pub q: f32,
+    /// `b0, b1, b2, a1, a2`, normalized by `a0`.
+    coefficients: [f32; 5],
+    /// Sample rate the coefficients were computed for.
+    sample_rate: u32,
+    /// `x1, x2, y1, y2` of each channel.
+    state: Vec<[f32; 4]>,
+}
+
+impl Biquad {
+    pub fn new(kind: BiquadKind, cutoff: f32, q: f32) -> Self {
+        Self {
+            kind,
+            cutoff,
+            q,
+            coefficients: [0.0; 5],
+            sample_rate: 0,
+            state: Vec::new(),
+        }
+    }
+
+    pub fn low_pass(cutoff: f32, q: f32) -> Self {
+        Self::new(BiquadKind::LowPass, cutoff, q)
+    }
+
+    pub fn high_pass(cutoff: f32, q: f32) -> Self {
+        Self::new(BiquadKind::HighPass, cutoff, q)
+    }
+
+    fn update_coefficients(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        let cutoff = self.cutoff.clamp(1.0, sample_rate as f32 * 0.49);
+        let w0 = TAU * cutoff / sample_rate as f32;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * self.q.max(0.01));
+
+        let (b0, b1, b2) = match self.kind {
+            BiquadKind::LowPass => ((1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0),
+            BiquadKind::HighPass => ((1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0),
+        };
+        let a0 = 1.0 + alpha;
+        self.coefficients = [
+            b0 / a0,
+            b1 / a0,
+            b2 / a0,
+            -2.0 * cos / a0,
+            (1.0 - alpha) / a0,
+        ];
+    }
+}
+
+impl AudioEffect for Biquad {
+    fn process(&mut self, samples: &mut [f32], channels: usize, sample_rate: u32) {
+        if self.sample_rate != sample_rate {
+            self.update_coefficients(sample_rate);
+        }
+        self.state.resize(channels, [0.0; 4]);
+
+        let [b0, b1, b2, a1, a2] = self.coefficients;
+        for frame in samples.chunks_exact_mut(channels) {
+            for (sample, [x1, x2, y1, y2]) in frame.iter_mut().zip(self.state.iter_mut()) {
+                let x = *sample;
+                let y = b0 * x + b1 * *x1 + b2 * *x2 - a1 * *y1 - a2 * *y2;
+                (*x2, *x1, *y2, *y1) = (*x1, x, *y1, y);
+                *sample = y;
+            }
+        }
+    }
+}
+
+/// Echo with feedback.
+#[derive(Debug, Clone)]
+pub struct Delay {
+    pub time: Duration,
+    /// Gain of each repeat, below 1.
+    pub feedback: f32,
+    /// Gain of the echo added to the input.
+    pub mix: f32,
+    /// Interleaved ring buffer of `time` frames.
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Delay {
+    pub fn new(time: Duration, feedback: f32, mix: f32) -> Self {
+        Self {
+            time,
+            feedback,
+            mix,
+            buffer: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl AudioEffect for Delay {
+    fn process(&mut self, samples: &mut [f32], channels: usize, sample_rate: u32) {
+        let frames = ((self.time.as_secs_f64() * sample_rate as f64) as usize).max(1);
+        if self.buffer.len() != frames * channels {
+            self.buffer = vec![0.0; frames * channels];
+            self.position = 0;
+        }
+
+        for frame in samples.chunks_exact_mut(channels) {
+            let delayed = &mut self.buffer[self.position * channels..][..channels];
+            for (sample, delayed) in frame.iter_mut().zip(delayed.iter_mut()) {
+                let input = *sample;
+                *sample = input + *delayed * self.mix;
+                *delayed = input + *delayed * self.feedback;
+            }
+            self.position = (self.position + 1) % frames;
+        }
+    }
+}
+
+/// Freeverb tunings at 44.1 kHz.
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+/// Offset of the tunings between channels, which widens the stereo image.
+const STEREO_SPREAD: usize = 23;
+
+#[derive(Debug, Clone)]
+struct Comb {
+    buffer: Vec<f32>,
+    position: usize,
+    filter: f32,
+}
+
+impl Comb {
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.position];
+        self.filter = output * (1.0 - damping) + self.filter * damping;
+        self.buffer[self.position] = input + self.filter * feedback;
+        self.position = (self.position + 1) % self.buffer.len();
+
+        output
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Allpass {
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Allpass {
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.position];
+        self.buffer[self.position] = input + delayed * 0.5;
+        self.position = (self.position + 1) % self.buffer.len();
+
+        delayed - input
+    }
+}
+
+/// Schroeder reverb with the Freeverb tunings: parallel comb filters into series allpass
+/// filters, for each channel.
+#[derive(Debug, Clone)]
+pub struct Reverb {
+    /// From 0 to 1. Larger rooms ring for longer.
+    pub room_size: f32,
+    /// From 0 to 1. Damped rooms lose high frequencies faster.
+    pub damping: f32,
+    /// Gain of the reverb added to the input.
+    pub mix: f32,
+    channels: Vec<(Vec<Comb>, Vec<Allpass>)>,
+    sample_rate: u32,
+}
+
+impl Default for Reverb {
+    fn default() -> Self {
+        Self::new(0.5, 0.5, 0.3)
+    }
+}
+
+impl Reverb {
+    pub fn new(room_size: f32, damping: f32, mix: f32) -> Self {
+        Self {
+            room_size,
+            damping,
+            mix,
+            channels: Vec::new(),
+            sample_rate: 0,
+        }
+    }
+
+    fn allocate(&mut self, channels: usize, sample_rate: u32) {
+        let scale = |tuning: usize, channel: usize| {
+            ((tuning + channel * STEREO_SPREAD) as f64 * sample_rate as f64 / 44100.0) as usize
+        };
+
+        self.sample_rate = sample_rate;
+        self.channels = (0..channels)
+            .map(|channel| {
+                let combs = COMB_TUNING
+                    .iter()
+                    .map(|tuning| Comb {
+                        buffer: vec![0.0; scale(*tuning, channel).max(1)],
+                        position: 0,
+                        filter: 0.0,
+                    })
+                    .collect();
+                let allpasses = ALLPASS_TUNING
+                    .iter()
+                    .map(|tuning| Allpass {
+                        buffer: vec![0.0; scale(*tuning, channel).max(1)],
+                        position: 0,
+                    })
+                    .collect();
+
+                (combs, allpasses)
+            })
+            .collect();
+    }
+}
+
+impl AudioEffect for Reverb {
+    fn process(&mut self, samples: &mut [f32], channels: usize, sample_rate: u32) {
+        if self.channels.len() != channels || self.sample_rate != sample_rate {
+            self.allocate(channels, sample_rate);
+        }
+
+        let feedback = self.room_size.clamp(0.0, 1.0) * 0.28 + 0.7;
+        let damping = self.damping.clamp(0.0, 1.0) * 0.4;
+        for frame in samples.chunks_exact_mut(channels) {
+            // Every channel reverberates the mono sum, with its own tunings
+            let input = frame.iter().sum::<f32>() * 0.015;
+            for (sample, (combs, allpasses)) in frame.iter_mut().zip(self.channels.iter_mut()) {
+                let mut wet = combs
+                    .iter_mut()
+                    .map(|comb| comb.process(input, feedback, damping))
+                    .sum::<f32>();
+                for allpass in allpasses.iter_mut() {
+                    wet = allpass.process(wet);
+                }
+                *sample += wet * self.mix;
+            }
+        }
+    }
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-6).log10()
+}
+
+/// Reduces the gain of the signal above the threshold by the ratio.
+#[derive(Debug, Clone)]
+pub struct Compressor {
+    /// In decibels relative to full scale.
+    pub threshold: f32,
+    /// 4 reduces 4 dB over the threshold to 1 dB. Infinite for a limiter.
+    pub ratio: f32,
+    pub attack: Duration,
+    pub release: Duration,
+    /// Gain in decibels applied after compressing.
+    pub makeup: f32,
+    /// Peak level followed with the attack and release.
+    envelope: f32,
+}
+
+impl Compressor {
+    pub fn new(threshold: f32, ratio: f32) -> Self {
+        Self {
+            threshold,
+            ratio,
+            attack: Duration::from_millis(10),
+            release: Duration::from_millis(100),
+            makeup: 0.0,
+            envelope: 0.0,
+        }
+    }
+
+    /// Keeps the signal below `threshold`, to avoid clipping when many voices play at once.
+    pub fn limiter(threshold: f32) -> Self {
+        Self {
+            attack: Duration::from_millis(1),
+            release: Duration::from_millis(50),
+            ..Self::new(threshold, f32::INFINITY)
+        }
+    }
+}
+
+impl AudioEffect for Compressor {
+    fn process(&mut self, samples: &mut [f32], channels: usize, sample_rate: u32) {
+        let coefficient =
+            |time: Duration| (-1.0 / (time.as_secs_f32() * sample_rate as f32).max(1.0)).exp();
+        let attack = coefficient(self.attack);
+        let release = coefficient(self.release);
+        let slope = 1.0 - 1.0 / self.ratio.max(1.0);
+        let makeup = db_to_gain(self.makeup);
+
+        for frame in samples.chunks_exact_mut(channels) {
+            let peak = frame.iter().fold(0.0, |peak, s| f32::max(peak, s.abs()));
+            let coefficient = match peak > self.envelope {
+                true => attack,
+                false => release,
+            };
+            self.envelope = peak + (self.envelope - peak) * coefficient;
+
+            let over = gain_to_db(self.envelope) - self.threshold;
+            let gain = match over > 0.0 {
+                true => db_to_gain(-over * slope),
+                false => 1.0,
+            };
+            for sample in frame.iter_mut() {
+                *sample *= gain * makeup;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    /// One second of a mono sine.
+    fn sine(frequency: f32, amplitude: f32) -> Vec<f32> {
+        (0..SAMPLE_RATE)
+            .map(|i| amplitude * (TAU * frequency * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    /// Peak of the second half, after the effect has settled.
+    fn peak(samples: &[f32]) -> f32 {
+        samples[samples.len() / 2..]
+            .iter()
+            .fold(0.0, |peak, s| f32::max(peak, s.abs()))
+    }
+
+    fn process(effect: &mut impl AudioEffect, mut samples: Vec<f32>) -> Vec<f32> {
+        // In blocks, like the mixer
+        for block in samples.chunks_mut(512) {
+            effect.process(block, 1, SAMPLE_RATE);
+        }
+
+        samples
+    }
+
+    #[test]
+    fn filters() {
+        let low_pass = || Biquad::low_pass(500.0, 0.707);
+        assert!(peak(&process(&mut low_pass(), sine(100.0, 1.0))) > 0.95);
+        assert!(peak(&process(&mut low_pass(), sine(8000.0, 1.0))) < 0.01);
+
+        let high_pass = || Biquad::high_pass(2000.0, 0.707);
+        assert!(peak(&process(&mut high_pass(), sine(100.0, 1.0))) < 0.01);
+        assert!(peak(&process(&mut high_pass(), sine(8000.0, 1.0))) > 0.95);
+    }
+
+    #[test]
+    fn delay_and_reverb() {
+        let mut impulse = vec![0.0; SAMPLE_RATE as usize];
+        impulse[0] = 1.0;
+
+        let echoes = process(
+            &mut Delay::new(Duration::from_millis(100), 0.5, 1.0),
+            impulse.clone(),
+        );
+        assert_eq!(echoes[0], 1.0);
+        assert_eq!(echoes[4410], 1.0);
+        assert_eq!(echoes[8820], 0.5);
+        assert_eq!(echoes.iter().filter(|s| **s != 0.0).count(), 10);
+
+        let tail = process(&mut Reverb::default(), impulse);
+        assert!(tail[1..].iter().all(|s| s.is_finite() && s.abs() < 1.0));
+        assert!(tail[4410..8820].iter().any(|s| s.abs() > 1e-4));
+    }
+
+    #[test]
+    fn compressor() {
+        // -6 dB over a -12 dB threshold is reduced to 1.5 dB over, less the smoothing of the
+        // envelope
+        let mut compressor = Compressor::new(-12.0, 4.0);
+        let compressed = peak(&process(&mut compressor, sine(440.0, 0.5)));
+        assert!((-11.0..-9.0).contains(&gain_to_db(compressed)));
+        let quiet = peak(&process(&mut compressor, sine(440.0, 0.1)));
+        assert!((quiet - 0.1).abs() < 0.01);
+
+        let limited = peak(&process(&mut Compressor::limiter(-6.0), sine(440.0, 1.0)));
+        assert!(gain_to_db(limited) < -5.5);
+    }
+}
@@ -3,30 +3,36 @@ use app::input::mouse_and_key::KeyInput;
 use app::prelude::*;
 use asset::*;
 use asset::{AssetApp, AssetLoader};
-use cpal::{
-    traits::{DeviceTrait, HostTrait, StreamTrait},
-    Device, SampleFormat, StreamConfig,
-};
-use ecs::egui_widget::Widget;
-#[cfg(target_arch = "wasm32")]
-use ecs::EventReader;
+use cereal::{WinnyFromValue, WinnyToValue};
 use ecs::{
-    Commands, Entity, EventWriter, Query, Res, ResMut, WinnyAsEgui, WinnyBundle, WinnyComponent,
-    WinnyEvent, WinnyResource, Without,
+    Commands, Entity, EventReader, EventWriter, Query, Res, ResMut, WinnyAsEgui, WinnyBundle,
+    WinnyComponent, WinnyEvent, WinnyResource, Without,
 };
+use gfx::transform::Transform;
 use hound::{WavReader, WavSpec};
+use mixer::{Mixer, MixerConfig, MixerEvent, MixerHandle, VoiceId};
 use rand::Rng;
-use std::{
-    fmt::Debug,
-    io::Cursor,
-    ops::Range,
-    sync::{
-        mpsc::{channel, Receiver, Sender},
-        Arc,
-    },
-};
-// use util::info;
-use util::tracing::error;
+use std::{fmt::Debug, io::Cursor, ops::Range, sync::Arc, time::Duration};
+use util::tracing::{error, warn};
+
+pub mod backend;
+pub mod bus;
+pub mod command;
+pub mod decoder;
+pub mod effect;
+pub mod mixer;
+pub mod music;
+pub mod spatial;
+pub mod synth;
+
+pub use backend::*;
+pub use bus::*;
+pub use command::*;
+pub use decoder::*;
+pub use effect::*;
+pub use music::*;
+pub use spatial::*;
+pub use synth::*;
 
 pub extern crate hound;
 
@@ -35,286 +41,232 @@ pub struct AudioPlugin;
 
 impl Plugin for AudioPlugin {
     fn build(&mut self, app: &mut App) {
-        let loader = AudioAssetLoader {};
         #[cfg(not(target_arch = "wasm32"))]
         app.egui_component::<AudioPlayback>()
             .egui_component::<PlaybackSettings>()
+            .egui_resource::<AudioBuses>()
+            .egui_component::<AudioListener>()
+            .egui_component::<SpatialAudio>()
             .register_event::<ExitingStream>()
+            .register_event::<MixerEvent>()
+            .register_event::<AudioCommand>()
+            .register_event::<AudioFinished>()
             .register_asset::<AudioSource>()
-            .register_asset_loader::<AudioSource>(loader)
+            .register_asset_loader::<AudioSource>(AudioAssetLoader)
+            .register_asset::<AudioStream>()
+            .register_asset_loader::<AudioStream>(AudioStreamLoader)
+            .register_asset_processor(AudioProcessor)
             .add_systems(
                 Schedule::PreUpdate,
-                (init_audio_bundle_streams, flush_finished_streams),
+                (
+                    start_output_stream,
+                    update_audio_backend,
+                    forward_mixer_events,
+                    bus::update_bus_gains,
+                    init_audio_bundle_streams,
+                    flush_finished_streams,
+                    music::update_music_player,
+                    music::update_music_volume,
+                ),
             )
-            .insert_resource(GlobalAudio::new());
+            .add_systems(
+                Schedule::PostUpdate,
+                (command::apply_audio_commands, spatial::update_spatial_audio),
+            )
+            .insert_resource(GlobalAudio::new())
+            .insert_resource(AudioBuses::default())
+            .insert_resource(MusicPlayer::default())
+            .insert_resource(AudioOutput::new(MixerConfig::default()));
         #[cfg(target_arch = "wasm32")]
         app.register_asset::<AudioSource>()
-            .register_asset_loader::<AudioSource>(loader)
+            .register_asset_loader::<AudioSource>(AudioAssetLoader)
+            .register_asset::<AudioStream>()
+            .register_asset_loader::<AudioStream>(AudioStreamLoader)
+            .register_asset_processor(AudioProcessor)
             .register_event::<ExitingStream>()
+            .register_event::<MixerEvent>()
+            .register_event::<AudioCommand>()
+            .register_event::<AudioFinished>()
             .add_systems(
                 Schedule::PreUpdate,
                 (
                     init_wasm_audio,
+                    start_output_stream,
+                    update_audio_backend,
+                    forward_mixer_events,
+                    bus::update_bus_gains,
                     init_audio_bundle_streams,
                     flush_finished_streams,
+                    music::update_music_player,
+                    music::update_music_volume,
                 ),
             )
-            .insert_resource(GlobalAudio::new());
+            .add_systems(
+                Schedule::PostUpdate,
+                (command::apply_audio_commands, spatial::update_spatial_audio),
+            )
+            .insert_resource(GlobalAudio::new())
+            .insert_resource(AudioBuses::default())
+            .insert_resource(MusicPlayer::default())
+            .insert_resource(AudioOutput::new(MixerConfig::default()));
     }
 }
 
-#[derive(WinnyResource)]
+/// The master volume is [`AudioBuses::master`].
+#[derive(WinnyResource, Debug, Default)]
 pub struct GlobalAudio {
-    pub volume: f32,
     #[cfg(target_arch = "wasm32")]
     pub wasm_initialized: bool,
 }
 
-impl Debug for GlobalAudio {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("GlobalAudio")
-            .field("audio", &self.volume)
-            .finish()
-    }
-}
-
 impl GlobalAudio {
     pub fn new() -> Self {
-        Self {
-            volume: 1.0,
-            #[cfg(target_arch = "wasm32")]
-            wasm_initialized: false,
-        }
+        Self::default()
     }
 }
 
-macro_rules! map_stream_err {
-    ($err:expr, $f:expr) => {
-        $f.map_err(|err| {
-            error!("{:?}", err);
-            $err
-        })
-    };
-}
-
-fn device() -> Result<Device, Error> {
-    let host = cpal::default_host();
-    let device = map_stream_err!(Error::HostNA, host.default_output_device().ok_or(()))?;
-    // info!("audio device: {}", device.name().unwrap_or("NA".into()));
-    Ok(device)
-}
-
-fn config(device: &Device) -> Result<StreamConfig, Error> {
-    let supported_output_configs = map_stream_err!(
-        Error::SupportedOutputConfigNA,
-        device.supported_output_configs()
-    )?;
-    let config = map_stream_err!(
-        Error::SupportedOutputConfigNA,
-        supported_output_configs
-            .into_iter()
-            .find(|config| {
-                // info!("{:?}", config);
-                config.sample_format() == SampleFormat::F32 && config.channels() == 2
-            })
-            .ok_or(())
-    )?;
-    let config = config.with_sample_rate(cpal::SampleRate(44100)).into();
-    // info!("Retrieving audio context: {:?}", config);
-    Ok(config)
-}
-
+/// Decoded audio. Samples are interleaved and normalized to `-1.0..=1.0`.
+///
+/// Cloning is cheap, the samples are shared.
+#[derive(Clone)]
 pub struct AudioSource {
-    pub bytes: Arc<[u8]>,
-    pub spec: WavSpec,
+    pub samples: Arc<[f32]>,
+    pub channels: u16,
+    pub sample_rate: u32,
 }
 
 impl Asset for AudioSource {}
 
 impl AudioSource {
-    pub fn new(
-        mut reader: asset::reader::ByteReader<std::io::Cursor<Vec<u8>>>,
-    ) -> Result<Self, AssetLoaderError> {
-        let bytes = reader
-            .read_all()
-            .map_err(|_| AssetLoaderError::FailedToParse)?;
-        let reader = hound::WavReader::new(Cursor::new(&bytes))
-            .map_err(|_| AssetLoaderError::FailedToBuild)?;
-        let spec = reader.spec();
-        let bytes = bytes.into();
+    /// Decodes a whole file in one of the [`AUDIO_EXTENSIONS`] formats.
+    pub fn decode(bytes: impl Into<Arc<[u8]>>, ext: &str) -> Result<Self, DecodeError> {
+        let (samples, channels, sample_rate) = decoder::decode(bytes.into(), ext)?;
 
-        // let (bytes, format) = wav::load_from_bytes(reader).map_err(|e| {
-        //     error!("{:?}", e);
-        //     AssetLoaderError::from(e)
-        // })?;
+        Ok(Self {
+            samples: samples.into(),
+            channels,
+            sample_rate,
+        })
+    }
 
-        Ok(Self { bytes, spec })
+    /// Number of frames, each holding one sample per channel.
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
     }
 
-    pub fn stream(
-        &self,
-        device: Device,
-        config: StreamConfig,
-        global_audio: &GlobalAudio,
-        playback_settings: PlaybackSettings,
-    ) -> Result<StreamHandle, Error> {
-        let volume = global_audio.volume * playback_settings.volume;
-        // info!("volume for stream: {volume}");
-        let bytes = self.bytes.clone();
-        let mut reader = WavReader::new(Cursor::new(bytes)).map_err(|_| Error::WavReader)?;
-        let spec = self.spec.clone();
-
-        let resample_ratio = spec.sample_rate as f32 / config.sample_rate.0 as f32;
-        // info!("resampling stream: {}", resample_ratio);
-        let (eos_tx, eos_rx) = channel();
-        // let mut sample_cursor = 0.0;
-        // let mut stream_offset = 0;
-
-        let stream = map_stream_err!(
-            Error::BuildStream,
-            device.build_output_stream(
-                &config,
-                move |output: &mut [f32], _: &cpal::OutputCallbackInfo| resampling_stream_f32(
-                    output,
-                    &mut reader,
-                    // resample_ratio,
-                    // &mut stream_offset,
-                    volume,
-                    // &mut sample_cursor,
-                    // &spec,
-                    &eos_tx,
-                    &playback_settings,
-                ),
-                move |err| error!("Error in audio stream: {}", err),
-                None,
-            )
-        )
-        .unwrap();
-        stream.play().map_err(|_| Error::PlayStream).unwrap();
-        Ok(StreamHandle(stream, eos_rx))
-    }
-}
-
-// #[allow(dead_code)]
-// fn resampling_stream_i16(
-//     output: &mut [i16],
-//     data: &[u8],
-//     resample_ratio: f32,
-//     stream_offset: &mut usize,
-//     volume: f32,
-//     sample_cursor: &mut f32,
-//     format: &,
-//     eos_tx: &Sender<()>,
-//     playback_settings: &PlaybackSettings,
-// ) {
-//     let bytes_per_sample = format.bits_per_sample as usize / 8;
-//     let mut byte_offset = 0;
-//     let mut end_of_stream = false;
-//     let mut samples_read = 0;
-//
-//     for frame in output.chunks_mut(format.channels as usize) {
-//         let stream_index = byte_offset + *stream_offset;
-//         for sample in frame.iter_mut() {
-//             if stream_index >= data.len() {
-//                 *sample = 0;
-//                 end_of_stream = true;
-//             } else {
-//                 let s1 = i16::from_le_bytes([data[stream_index], data[stream_index + 1]]);
-//                 let s2 = i16::from_le_bytes([data[stream_index + 2], data[stream_index + 3]]);
-//
-//                 let mut s = lerp(s1 as f32, s2 as f32, 1.0 - *sample_cursor) as i16;
-//                 s = (volume * s as f32) as i16;
-//                 *sample = s;
-//             }
-//
-//             *sample_cursor += resample_ratio as f32;
-//             if *sample_cursor >= 1.0 {
-//                 byte_offset += bytes_per_sample;
-//                 *sample_cursor = 0.0;
-//                 samples_read += 1;
-//             }
-//         }
-//     }
-//
-//     *stream_offset += samples_read * bytes_per_sample;
-//
-//     if end_of_stream {
-//         if playback_settings.loop_track {
-//             *stream_offset = 0;
-//         } else {
-//             if eos_tx.send(()).is_err() {
-//                 error!("audio stream reciever closed");
-//             }
-//         }
-//     }
-// }
+    pub fn duration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(self.frames() as f64 / self.sample_rate as f64)
+    }
+}
 
-#[allow(dead_code)]
-fn resampling_stream_f32(
-    output: &mut [f32],
-    reader: &mut WavReader<Cursor<Arc<[u8]>>>,
-    // resample_ratio: f32,
-    // stream_offset: &mut usize,
-    volume: f32,
-    // sample_cursor: &mut f32,
-    // spec: &WavSpec,
-    eos_tx: &Sender<()>,
-    playback_settings: &PlaybackSettings,
-) {
-    // let bytes_per_sample = spec.bits_per_sample as usize / 8;
-    // let mut byte_offset = 0;
-    // let mut end_of_stream = false;
-    // let mut samples_read = 0;
+impl Debug for AudioSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioSource")
+            .field("samples", &self.samples.len())
+            .field("channels", &self.channels)
+            .field("sample_rate", &self.sample_rate)
+            .finish()
+    }
+}
+
+/// Encoded audio which is decoded while it plays, for long tracks such as music.
+///
+/// Loaded from the same files as an [`AudioSource`] with `server.load::<AudioStream>(path)`.
+///
+/// On native, the encoded file is read from its [`AssetSource`] while it plays, and each
+/// playback opens the file again. Readers which cannot seek into their files, such as for
+/// compressed archive entries, read them whole. On wasm, the file is read whole when it is
+/// loaded.
+#[derive(Clone)]
+pub struct AudioStream {
+    source: StreamSource,
+    ext: String,
+    channels: u16,
+    sample_rate: u32,
+    frames: Option<u64>,
+}
 
-    for (buf, sample) in output.iter_mut().zip(reader.samples::<i32>()) {
-        let sample = sample.unwrap_or_default() as f32 / i32::MAX as f32;
-        *buf = sample * playback_settings.volume * volume;
+#[derive(Clone)]
+enum StreamSource {
+    Bytes(Arc<[u8]>),
+    File(AssetFilePath),
+}
+
+impl Asset for AudioStream {}
+
+impl AudioStream {
+    /// Checks that `bytes` can be decoded.
+    pub fn new(bytes: impl Into<Arc<[u8]>>, ext: &str) -> Result<Self, DecodeError> {
+        let bytes = bytes.into();
+        let decoder = AudioDecoder::new(bytes.clone(), ext)?;
+
+        Ok(Self::with_decoder(
+            StreamSource::Bytes(bytes),
+            ext,
+            &decoder,
+        ))
     }
 
-    let remaining_samples = reader.samples::<f32>().len();
+    /// Checks that `file` can be decoded, without reading it whole.
+    pub async fn from_file(file: AssetFilePath, ext: &str) -> Result<Self, DecodeError> {
+        let opened = file
+            .open()
+            .await
+            .map_err(|e| DecodeError::Open(e.to_string()))?;
+        let decoder = AudioDecoder::from_file(opened, ext)?;
 
-    // for frame in output.chunks_mut(format.channels as usize) {
-    //
-    //     // let stream_index = byte_offset + *stream_offset;
-    //     // for sample in frame.iter_mut() {
-    //     //     if stream_index >= data.len() {
-    //     //         *sample = 0.0;
-    //     //         end_of_stream = true;
-    //     //     } else {
-    //     //         let s1 = i16::from_le_bytes([data[stream_index], data[stream_index + 1]]);
-    //     //         let s2 = i16::from_le_bytes([data[stream_index + 2], data[stream_index + 3]]);
-    //     //         let s = lerp(s1 as f32, s2 as f32, 1.0 - *sample_cursor);
-    //     //         *sample = s / i16::MAX as f32 * volume;
-    //     //     }
-    //     //
-    //     //     *sample_cursor += resample_ratio as f32;
-    //     //     if *sample_cursor >= 1.0 {
-    //     //         byte_offset += bytes_per_sample;
-    //     //         *sample_cursor = 0.0;
-    //     //         samples_read += 1;
-    //     //     }
-    //     // }
-    // }
+        Ok(Self::with_decoder(StreamSource::File(file), ext, &decoder))
+    }
 
-    // *stream_offset += samples_read * bytes_per_sample;
+    fn with_decoder(source: StreamSource, ext: &str, decoder: &AudioDecoder) -> Self {
+        Self {
+            source,
+            ext: ext.to_owned(),
+            channels: decoder.channels(),
+            sample_rate: decoder.sample_rate(),
+            frames: decoder.frames(),
+        }
+    }
 
-    if remaining_samples == 0 {
-        if eos_tx.send(()).is_err() {
-            error!("audio stream reciever closed");
+    pub fn decoder(&self) -> Result<AudioDecoder, DecodeError> {
+        match &self.source {
+            StreamSource::Bytes(bytes) => AudioDecoder::new(bytes.clone(), &self.ext),
+            // Readers which seek into their files open them without waiting
+            StreamSource::File(file) => {
+                let opened = pollster::block_on(file.open())
+                    .map_err(|e| DecodeError::Open(e.to_string()))?;
+                AudioDecoder::from_file(opened, &self.ext)
+            }
         }
-        // }
     }
-}
 
-// fn lerp(v1: f32, v2: f32, l: f32) -> f32 {
-//     v1 * (1.0 - l) + v2 * l
-// }
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
 
-impl Debug for AudioSource {
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Length of the track, if the container stores it.
+    pub fn duration(&self) -> Option<std::time::Duration> {
+        self.frames.map(|frames| {
+            std::time::Duration::from_secs_f64(frames as f64 / self.sample_rate as f64)
+        })
+    }
+}
+
+impl Debug for AudioStream {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("AudioSource")
-            .field("bytes: Vec<u8>", &self.bytes.len())
-            .field("format", &self.spec)
+        let mut debug = f.debug_struct("AudioStream");
+        match &self.source {
+            StreamSource::Bytes(bytes) => debug.field("bytes", &bytes.len()),
+            StreamSource::File(file) => debug.field("file", &file.path()),
+        };
+        debug
+            .field("ext", &self.ext)
+            .field("channels", &self.channels)
+            .field("sample_rate", &self.sample_rate)
             .finish()
     }
 }
@@ -325,6 +277,15 @@ pub struct PlaybackSettings {
     pub speed: f32,
     pub loop_track: bool,
     pub play_on_creation: bool,
+    /// Frame of the [`MixerHandle::clock`] the sound starts at. Sounds scheduled in the past, or
+    /// whose source loads too late, start immediately.
+    pub start_at: Option<u64>,
+    /// Gain of a [`SpatialAudio`] sound until the first spatial update. Set from the
+    /// [`AudioListener`] when the sound is spawned, so it does not start centered at full volume.
+    pub spatial_gain: f32,
+    /// Pan of a [`SpatialAudio`] sound until the first spatial update, from -1 (left) to 1
+    /// (right).
+    pub spatial_pan: f32,
 }
 
 impl Default for PlaybackSettings {
@@ -334,6 +295,9 @@ impl Default for PlaybackSettings {
             speed: 1.0,
             loop_track: false,
             play_on_creation: true,
+            start_at: None,
+            spatial_gain: 1.0,
+            spatial_pan: 0.0,
         }
     }
 }
@@ -363,99 +327,231 @@ impl PlaybackSettings {
         self.play_on_creation = play;
         self
     }
+
+    pub fn start_at(mut self, frame: u64) -> Self {
+        self.start_at = Some(frame);
+        self
+    }
+
+    pub fn with_spatial(mut self, gain: f32, pan: f32) -> Self {
+        self.spatial_gain = gain;
+        self.spatial_pan = pan;
+        self
+    }
 }
 
-pub struct StreamHandle(cpal::Stream, Receiver<()>);
+/// The single output stream, which renders the [`Mixer`] with an [`AudioBackend`].
+///
+/// The stream is started on the first frame, or after the first user gesture on wasm.
+#[derive(WinnyResource)]
+pub struct AudioOutput {
+    mixer: MixerHandle,
+    /// Moved into the backend once it starts.
+    pending: Option<Mixer>,
+    backend: Box<dyn AudioBackend>,
+}
 
-impl Widget for StreamHandle {
-    fn display(&mut self, ui: &mut ecs::egui::Ui) {
-        ui.label("StreamHandle");
+impl Debug for AudioOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioOutput")
+            .field("mixer", &self.mixer)
+            .field("started", &self.pending.is_none())
+            .finish()
     }
 }
 
-unsafe impl Sync for StreamHandle {}
-unsafe impl Send for StreamHandle {}
+impl AudioOutput {
+    /// Plays through the default output device at its own sample rate and channel count, or
+    /// renders to a [`NullBackend`] with `config` if there is none.
+    pub fn new(config: MixerConfig) -> Self {
+        Self::with_backend(config, CpalBackend::default())
+    }
+
+    /// Opens `backend`, building the [`Mixer`] with the config the backend runs at.
+    pub fn with_backend(config: MixerConfig, mut backend: impl AudioBackend) -> Self {
+        let (config, backend): (_, Box<dyn AudioBackend>) = match backend.open(&config) {
+            Ok(config) => (config, Box::new(backend)),
+            Err(e) => {
+                warn!("could not open audio backend, falling back to the null backend: {e:?}");
+                (config, Box::new(NullBackend::default()))
+            }
+        };
+        let (mixer, handle) = Mixer::new(config);
+
+        Self {
+            mixer: handle,
+            pending: Some(mixer),
+            backend,
+        }
+    }
+
+    pub fn mixer(&self) -> &MixerHandle {
+        &self.mixer
+    }
+
+    fn start(&mut self) -> Result<(), Error> {
+        let Some(mixer) = self.pending.take() else {
+            return Ok(());
+        };
 
-pub enum StreamCommand {
-    Play,
-    Pause,
-    Stop,
+        self.backend.start(mixer)
+    }
 }
 
-#[derive(WinnyComponent, WinnyAsEgui)]
-pub struct AudioPlayback {
-    handle: StreamHandle,
-    // commands: Sender<StreamCommand>,
-    // path: String,
+fn update_audio_backend(mut output: ResMut<AudioOutput>, delta: Res<DeltaTime>) {
+    output
+        .backend
+        .update(Duration::from_secs_f32(delta.delta.max(0.0)));
 }
 
-impl Drop for AudioPlayback {
-    fn drop(&mut self) {
-        // info!("exiting audio stream");
+fn start_output_stream(mut output: ResMut<AudioOutput>, global_audio: Res<GlobalAudio>) {
+    #[cfg(target_arch = "wasm32")]
+    if !global_audio.wasm_initialized {
+        return;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    let _ = &global_audio;
+
+    if let Err(e) = output.start() {
+        error!("could not start audio output stream: {e:?}");
     }
 }
 
+/// A sound played by the [`Mixer`]. Inserted on entities with an [`AudioBundle`] or
+/// [`AudioStreamBundle`] once its source is loaded.
+#[derive(WinnyComponent, WinnyAsEgui)]
+pub struct AudioPlayback {
+    voice: VoiceId,
+    mixer: MixerHandle,
+}
+
 impl AudioPlayback {
     pub fn new(
         source: &AudioSource,
         playback_settings: PlaybackSettings,
-        global_audio: &mut GlobalAudio,
-    ) -> Result<Self, Error> {
-        // let (commands_tx, commands_rx) = std::sync::mpsc::channel();
-
-        let device = device()?;
-        let config = config(&device)?;
+        bus: AudioBus,
+        mixer: &MixerHandle,
+    ) -> Self {
+        Self {
+            voice: mixer.play(source, playback_settings, bus),
+            mixer: mixer.clone(),
+        }
+    }
 
-        let handle = source.stream(device, config, global_audio, playback_settings)?;
-        handle.0.play().unwrap();
+    pub fn from_stream(
+        stream: &AudioStream,
+        playback_settings: PlaybackSettings,
+        bus: AudioBus,
+        mixer: &MixerHandle,
+    ) -> Self {
+        Self {
+            voice: mixer.play_stream(stream, playback_settings, bus),
+            mixer: mixer.clone(),
+        }
+    }
 
-        Ok(Self {
-            handle,
-            // commands: commands_tx,
-            // path: source.path.clone(),
-        })
+    pub fn voice(&self) -> VoiceId {
+        self.voice
     }
 
     pub fn play(&self) {
-        // info!("playing stream: {}", self.path);
-        if let Err(e) = self.handle.0.play() {
-            error!("{e}");
-        }
+        self.mixer.resume(self.voice);
     }
 
     pub fn pause(&self) {
-        // info!("pausing stream: {}", self.path);
-        if let Err(e) = self.handle.0.pause() {
-            error!("{e}");
-        }
+        self.mixer.pause(self.voice);
     }
 
-    pub fn stop(self) {}
+    /// The entity is despawned once the [`Mixer`] removes the voice.
+    pub fn stop(&self) {
+        self.mixer.stop(self.voice);
+    }
+
+    /// Replaces the effects run on this sound before it is mixed into its bus.
+    ///
+    /// ```ignore
+    /// playback.set_effects(vec![Box::new(Biquad::low_pass(400.0, 0.7)), Box::new(Reverb::default())]);
+    /// ```
+    pub fn set_effects(&self, effects: Vec<Box<dyn AudioEffect>>) {
+        self.mixer.set_voice_effects(self.voice, effects);
+    }
 }
 
 #[derive(WinnyBundle, Clone)]
 pub struct AudioBundle {
     pub handle: Handle<AudioSource>,
     pub playback_settings: PlaybackSettings,
+    pub bus: AudioBus,
 }
 
-#[cfg(not(target_arch = "wasm32"))]
+/// Plays an [`AudioStream`], decoding it while it plays.
+#[derive(WinnyBundle, Clone)]
+pub struct AudioStreamBundle {
+    pub handle: Handle<AudioStream>,
+    pub playback_settings: PlaybackSettings,
+    pub bus: AudioBus,
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 fn init_audio_bundle_streams(
     mut commands: Commands,
-    bundles: Query<(Entity, Handle<AudioSource>, PlaybackSettings), Without<AudioPlayback>>,
+    bundles: Query<
+        (
+            Entity,
+            Handle<AudioSource>,
+            PlaybackSettings,
+            Option<AudioBus>,
+        ),
+        Without<AudioPlayback>,
+    >,
+    stream_bundles: Query<
+        (
+            Entity,
+            Handle<AudioStream>,
+            PlaybackSettings,
+            Option<AudioBus>,
+        ),
+        Without<AudioPlayback>,
+    >,
+    spatial: Query<(SpatialAudio, Transform)>,
+    listeners: Query<(Transform, AudioListener)>,
     sources: Res<Assets<AudioSource>>,
-    mut global_audio: ResMut<GlobalAudio>,
+    streams: Res<Assets<AudioStream>>,
+    output: Res<AudioOutput>,
+    global_audio: Res<GlobalAudio>,
 ) {
-    for (entity, handle, playback_settings) in bundles.iter() {
-        if let Some(source) = sources.get(handle) {
-            match AudioPlayback::new(source, *playback_settings, &mut global_audio) {
-                Ok(playback) => {
-                    commands.get_entity(entity).insert(playback);
-                }
-                Err(e) => {
-                    error!("could not create playback for audio bundle: {e:?}")
-                }
+    #[cfg(target_arch = "wasm32")]
+    if !global_audio.wasm_initialized {
+        return;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    let _ = &global_audio;
+
+    let listener = listeners.iter().next().map(|(transform, _)| transform);
+    let settings =
+        |entity: Entity, settings: PlaybackSettings| match (spatial.get(entity), listener) {
+            (Some((spatial, transform)), Some(listener)) => {
+                spatial::spatialize(settings, spatial, transform, listener)
             }
+            _ => settings,
+        };
+
+    for (entity, handle, playback_settings, bus) in bundles.iter() {
+        if let Some(source) = sources.get(handle) {
+            let bus = bus.copied().unwrap_or_default();
+            let playback_settings = settings(entity, *playback_settings);
+            let playback = AudioPlayback::new(source, playback_settings, bus, output.mixer());
+            commands.get_entity(entity).insert(playback);
+        }
+    }
+
+    for (entity, handle, playback_settings, bus) in stream_bundles.iter() {
+        if let Some(stream) = streams.get(handle) {
+            let bus = bus.copied().unwrap_or_default();
+            let playback_settings = settings(entity, *playback_settings);
+            let playback =
+                AudioPlayback::from_stream(stream, playback_settings, bus, output.mixer());
+            commands.get_entity(entity).insert(playback);
         }
     }
 }
@@ -467,67 +563,208 @@ fn init_wasm_audio(mut global_audio: ResMut<GlobalAudio>, user_gestures: EventRe
     }
 }
 
-#[cfg(target_arch = "wasm32")]
-fn init_audio_bundle_streams(
-    mut commands: Commands,
-    bundles: Query<(Entity, Handle<AudioSource>, PlaybackSettings), Without<AudioPlayback>>,
-    sources: Res<Assets<AudioSource>>,
-    mut global_audio: ResMut<GlobalAudio>,
-) {
-    if global_audio.wasm_initialized == false {
-        return;
-    }
-    for (entity, handle, playback_settings) in bundles.iter() {
-        if let Some(source) = sources.get(handle) {
-            match AudioPlayback::new(source, *playback_settings, &mut global_audio) {
-                Ok(playback) => {
-                    commands.get_entity(entity).insert(playback);
-                }
-                Err(e) => {
-                    error!("could not create playback for audio bundle: {e:?}")
-                }
-            }
-        }
-    }
-}
-
+/// Prefer [`AudioFinished`], which is sent at the same time.
 #[derive(WinnyEvent, Clone)]
 pub struct ExitingStream(pub Entity);
 
+fn forward_mixer_events(output: Res<AudioOutput>, mut writer: EventWriter<MixerEvent>) {
+    for event in output.mixer().events() {
+        writer.send(event);
+    }
+}
+
 fn flush_finished_streams(
     mut commands: Commands,
+    events: EventReader<MixerEvent>,
     streams: Query<(Entity, AudioPlayback)>,
     mut writer: EventWriter<ExitingStream>,
+    mut finished: EventWriter<AudioFinished>,
 ) {
-    for (e, playback) in streams.iter() {
-        if playback.handle.1.try_recv().is_ok() {
+    for event in events.peak_read() {
+        if let Some((e, _)) = streams
+            .iter()
+            .find(|(_, playback)| playback.voice == event.voice())
+        {
             commands.get_entity(e).despawn();
             writer.send(ExitingStream(e));
+            finished.send(AudioFinished(e));
         }
     }
 }
 
-struct AudioAssetLoader;
+pub struct AudioAssetLoader;
 
 impl AssetLoader for AudioAssetLoader {
     type Asset = AudioSource;
     type Settings = ();
 
     async fn load(
-        reader: asset::reader::ByteReader<std::io::Cursor<Vec<u8>>>,
+        mut reader: asset::reader::ByteReader<std::io::Cursor<Vec<u8>>>,
+        _settings: Self::Settings,
+        _path: String,
+        ext: &str,
+        context: &mut asset::LoadContext,
+    ) -> Result<Self::Asset, AssetLoaderError> {
+        if !AUDIO_EXTENSIONS.contains(&ext) {
+            return Err(AssetLoaderError::UnsupportedFileExtension);
+        }
+        let bytes = reader
+            .read_all()
+            .map_err(|_| AssetLoaderError::FailedToParse)?;
+
+        context
+            .compute({
+                let ext = ext.to_owned();
+                move || AudioSource::decode(bytes, &ext)
+            })
+            .await?
+            .map_err(|e| AssetLoaderError::Failed(e.to_string()))
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        AUDIO_EXTENSIONS
+    }
+}
+
+pub struct AudioStreamLoader;
+
+impl AssetLoader for AudioStreamLoader {
+    type Asset = AudioStream;
+    type Settings = ();
+
+    async fn load(
+        mut reader: asset::reader::ByteReader<std::io::Cursor<Vec<u8>>>,
         _settings: Self::Settings,
         _path: String,
         ext: &str,
+        context: &mut asset::LoadContext,
     ) -> Result<Self::Asset, AssetLoaderError> {
-        match ext {
-            "wav" => AudioSource::new(reader),
-            _ => Err(AssetLoaderError::UnsupportedFileExtension),
+        if !AUDIO_EXTENSIONS.contains(&ext) {
+            return Err(AssetLoaderError::UnsupportedFileExtension);
         }
+
+        let stream = if cfg!(target_arch = "wasm32") {
+            let bytes = reader
+                .read_all()
+                .map_err(|_| AssetLoaderError::FailedToParse)?;
+            AudioStream::new(bytes, ext)
+        } else {
+            AudioStream::from_file(context.file().clone(), ext).await
+        };
+
+        stream.map_err(|e| AssetLoaderError::Failed(e.to_string()))
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        AUDIO_EXTENSIONS
+    }
+
+    /// Files are fetched whole over HTTP on wasm.
+    fn streamed(&self) -> bool {
+        !cfg!(target_arch = "wasm32")
     }
+}
+
+/// Resamples WAV files to [`AudioProcessorSettings::sample_rate`] while processing. The output
+/// stream may run at a different rate, in which case playback is resampled again.
+pub struct AudioProcessor;
+
+/// [`AudioProcessor`] settings, read from the `[processor]` table of a sound's `.meta` file.
+#[derive(WinnyToValue, WinnyFromValue, Debug)]
+pub struct AudioProcessorSettings {
+    /// Defaults to 44100 Hz.
+    pub sample_rate: u32,
+}
+
+impl Default for AudioProcessorSettings {
+    fn default() -> Self {
+        Self { sample_rate: 44100 }
+    }
+}
+
+impl AssetProcessor for AudioProcessor {
+    type Settings = AudioProcessorSettings;
 
     fn extensions(&self) -> &'static [&'static str] {
         &["wav"]
     }
+
+    fn process(
+        &self,
+        bytes: Vec<u8>,
+        settings: &Self::Settings,
+        _ext: &str,
+    ) -> Result<Vec<u8>, ProcessError> {
+        let mut reader =
+            WavReader::new(Cursor::new(&bytes)).map_err(|e| ProcessError::Failed(e.to_string()))?;
+        let spec = reader.spec();
+        if spec.sample_rate == settings.sample_rate {
+            return Ok(bytes);
+        }
+
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Float => reader
+                .samples::<f32>()
+                .map(|s| s.map(|s| s as f64))
+                .collect::<Result<Vec<_>, _>>(),
+            hound::SampleFormat::Int => reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f64))
+                .collect::<Result<Vec<_>, _>>(),
+        }
+        .map_err(|e| ProcessError::Failed(e.to_string()))?;
+        let resampled = resample_linear(
+            &samples,
+            spec.channels as usize,
+            spec.sample_rate,
+            settings.sample_rate,
+        );
+
+        let spec = WavSpec {
+            sample_rate: settings.sample_rate,
+            ..spec
+        };
+        let mut wav = Vec::new();
+        let mut writer = hound::WavWriter::new(Cursor::new(&mut wav), spec)
+            .map_err(|e| ProcessError::Failed(e.to_string()))?;
+        for sample in resampled {
+            match spec.sample_format {
+                hound::SampleFormat::Float => writer.write_sample(sample as f32),
+                hound::SampleFormat::Int => writer.write_sample(sample.round() as i32),
+            }
+            .map_err(|e| ProcessError::Failed(e.to_string()))?;
+        }
+        writer
+            .finalize()
+            .map_err(|e| ProcessError::Failed(e.to_string()))?;
+
+        Ok(wav)
+    }
+}
+
+/// Linearly interpolates interleaved samples from one sample rate to another.
+fn resample_linear(samples: &[f64], channels: usize, from: u32, to: u32) -> Vec<f64> {
+    let frames = samples.len() / channels;
+    if frames == 0 {
+        return Vec::new();
+    }
+
+    let ratio = from as f64 / to as f64;
+    let resampled_frames = (frames as f64 / ratio).floor() as usize;
+    let mut resampled = Vec::with_capacity(resampled_frames * channels);
+    for frame in 0..resampled_frames {
+        let position = frame as f64 * ratio;
+        let index = position as usize;
+        let next = (index + 1).min(frames - 1);
+        let t = position - index as f64;
+        for channel in 0..channels {
+            let s1 = samples[index * channels + channel];
+            let s2 = samples[next * channels + channel];
+            resampled.push(s1 + (s2 - s1) * t);
+        }
+    }
+
+    resampled
 }
 
 // impl From<crate::wav::Error> for AssetLoaderError {
@@ -598,4 +835,5 @@ pub enum Error {
     OutputConfigNotSupported,
     BuildStream,
     WavReader,
+    WavWriter,
 }
@@ -0,0 +1,1241 @@
+//! Software mixer. Every playing sound is a voice of the [`Mixer`], which sums them into a single
+//! output stream.
+//!
+//! The [`Mixer`] runs on the audio thread and is controlled through a [`MixerHandle`]. Commands
+//! and events are passed through bounded channels, so the audio thread never blocks.
+
+use crate::{
+    decoder::{StreamBlock, StreamDecoder},
+    AudioBus, AudioEffect, AudioSource, AudioStream, PlaybackSettings,
+};
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use ecs::{egui_widget::Widget, WinnyEvent};
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use util::tracing::error;
+
+/// Voices beyond the limit steal the oldest voice.
+pub const DEFAULT_MAX_VOICES: usize = 32;
+const QUEUE_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MixerConfig {
+    pub sample_rate: u32,
+    /// Of the output. Mono sources are played on every channel.
+    pub channels: u16,
+    pub max_voices: usize,
+}
+
+impl MixerConfig {
+    /// Output frames played in `duration`.
+    pub fn frames(&self, duration: Duration) -> u64 {
+        (duration.as_secs_f64() * self.sample_rate as f64) as u64
+    }
+
+    /// Time taken to play `frames` output frames.
+    pub fn duration(&self, frames: u64) -> Duration {
+        Duration::from_secs_f64(frames as f64 / self.sample_rate.max(1) as f64)
+    }
+}
+
+impl Default for MixerConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44100,
+            channels: 2,
+            max_voices: DEFAULT_MAX_VOICES,
+        }
+    }
+}
+
+/// Identifies a sound played by the [`Mixer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoiceId(u64);
+
+impl Widget for VoiceId {
+    fn display(&mut self, ui: &mut ecs::egui::Ui) {
+        ui.label(format!("Voice {}", self.0));
+    }
+}
+
+enum MixerCommand {
+    Play {
+        id: VoiceId,
+        source: VoiceSource,
+        settings: PlaybackSettings,
+        bus: AudioBus,
+    },
+    Pause(VoiceId),
+    Resume(VoiceId),
+    Stop(VoiceId),
+    SetVolume(VoiceId, f32),
+    SetSpeed(VoiceId, f32),
+    RampSpeed {
+        id: VoiceId,
+        speed: f32,
+        duration: Duration,
+    },
+    Fade {
+        id: VoiceId,
+        volume: f32,
+        duration: Duration,
+        stop: bool,
+    },
+    Seek(VoiceId, Duration),
+    SetSpatial {
+        id: VoiceId,
+        gain: f32,
+        pan: f32,
+        pitch: f32,
+    },
+    Observe(VoiceId, VoicePosition),
+    SetVoiceEffects(VoiceId, Vec<Box<dyn AudioEffect>>),
+    SetBusGain(AudioBus, f32),
+    SetBusOutput(AudioBus, AudioBus),
+    SetBusEffects(AudioBus, Vec<Box<dyn AudioEffect>>),
+}
+
+/// Sent by the [`Mixer`] when a voice is removed. Forwarded as an [`ecs::Event`] by the
+/// [`crate::AudioPlugin`].
+#[derive(WinnyEvent, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MixerEvent {
+    /// The voice played to the end of its source, or was stopped.
+    Finished(VoiceId),
+    /// The voice was removed to make room for a new voice.
+    Stolen(VoiceId),
+}
+
+impl MixerEvent {
+    pub fn voice(&self) -> VoiceId {
+        match self {
+            Self::Finished(voice) | Self::Stolen(voice) => *voice,
+        }
+    }
+}
+
+/// Playback position of a voice, updated by the [`Mixer`] after every rendered block. Created with
+/// [`MixerHandle::observe`].
+#[derive(Debug, Default, Clone)]
+pub struct VoicePosition(Arc<AtomicU64>);
+
+impl VoicePosition {
+    /// Position in the voice's source.
+    pub fn get(&self) -> Duration {
+        Duration::from_secs_f64(f64::from_bits(self.0.load(Ordering::Relaxed)))
+    }
+
+    fn set(&self, seconds: f64) {
+        self.0.store(seconds.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Controls a [`Mixer`] from any thread. Commands take effect at the start of the next rendered
+/// block.
+#[derive(Clone)]
+pub struct MixerHandle {
+    commands: Sender<MixerCommand>,
+    events: Receiver<MixerEvent>,
+    next_id: Arc<AtomicU64>,
+    clock: Arc<AtomicU64>,
+    config: MixerConfig,
+}
+
+impl Debug for MixerHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MixerHandle")
+            .field("config", &self.config)
+            .field("queued", &self.commands.len())
+            .finish()
+    }
+}
+
+impl Widget for MixerHandle {
+    fn display(&mut self, ui: &mut ecs::egui::Ui) {
+        ui.label("MixerHandle");
+    }
+}
+
+impl MixerHandle {
+    pub fn config(&self) -> MixerConfig {
+        self.config
+    }
+
+    /// Output frames rendered by the [`Mixer`]. Sounds are scheduled against this clock with
+    /// [`PlaybackSettings::start_at`].
+    ///
+    /// ```ignore
+    /// // Play on the next beat, at 120 beats per minute
+    /// let beat = mixer.config().frames(Duration::from_millis(500));
+    /// let settings = PlaybackSettings::default().start_at((mixer.clock() / beat + 1) * beat);
+    /// ```
+    pub fn clock(&self) -> u64 {
+        self.clock.load(Ordering::Relaxed)
+    }
+
+    /// Starts a voice on `bus`, paused unless [`PlaybackSettings::play_on_creation`] is set.
+    pub fn play(&self, source: &AudioSource, settings: PlaybackSettings, bus: AudioBus) -> VoiceId {
+        let id = VoiceId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.send(MixerCommand::Play {
+            id,
+            source: VoiceSource::Buffered(source.clone()),
+            settings,
+            bus,
+        });
+
+        id
+    }
+
+    /// Starts a voice which decodes `stream` while it plays.
+    pub fn play_stream(
+        &self,
+        stream: &AudioStream,
+        settings: PlaybackSettings,
+        bus: AudioBus,
+    ) -> VoiceId {
+        let id = VoiceId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        match stream.decoder() {
+            Ok(decoder) => self.send(MixerCommand::Play {
+                id,
+                source: VoiceSource::Streamed(StreamBuffer::new(StreamDecoder::new(decoder))),
+                settings,
+                bus,
+            }),
+            // The stream decoded when it was loaded, so this is unlikely
+            Err(e) => error!("Could not decode audio stream: {e}"),
+        }
+
+        id
+    }
+
+    pub fn pause(&self, voice: VoiceId) {
+        self.send(MixerCommand::Pause(voice));
+    }
+
+    pub fn resume(&self, voice: VoiceId) {
+        self.send(MixerCommand::Resume(voice));
+    }
+
+    pub fn stop(&self, voice: VoiceId) {
+        self.send(MixerCommand::Stop(voice));
+    }
+
+    pub fn set_volume(&self, voice: VoiceId, volume: f32) {
+        self.send(MixerCommand::SetVolume(voice, volume));
+    }
+
+    pub fn set_speed(&self, voice: VoiceId, speed: f32) {
+        self.send(MixerCommand::SetSpeed(voice, speed));
+    }
+
+    /// Ramps the speed of `voice` to `speed` over `duration`.
+    pub fn ramp_speed(&self, voice: VoiceId, speed: f32, duration: Duration) {
+        self.send(MixerCommand::RampSpeed {
+            id: voice,
+            speed,
+            duration,
+        });
+    }
+
+    /// Ramps the volume of `voice` to `volume` over `duration`.
+    pub fn fade(&self, voice: VoiceId, volume: f32, duration: Duration) {
+        self.send(MixerCommand::Fade {
+            id: voice,
+            volume,
+            duration,
+            stop: false,
+        });
+    }
+
+    /// Ramps the volume of `voice` to silence over `duration`, then stops it.
+    pub fn fade_out(&self, voice: VoiceId, duration: Duration) {
+        self.send(MixerCommand::Fade {
+            id: voice,
+            volume: 0.0,
+            duration,
+            stop: true,
+        });
+    }
+
+    /// Attenuates `voice` by `gain`, pans it from -1 (left) to 1 (right) and scales its speed by
+    /// `pitch`. Applied on top of the voice's volume and speed.
+    pub fn set_spatial(&self, voice: VoiceId, gain: f32, pan: f32, pitch: f32) {
+        self.send(MixerCommand::SetSpatial {
+            id: voice,
+            gain,
+            pan,
+            pitch,
+        });
+    }
+
+    /// Continues playing `voice` from `position` in its source.
+    pub fn seek(&self, voice: VoiceId, position: Duration) {
+        self.send(MixerCommand::Seek(voice, position));
+    }
+
+    /// Tracks the playback position of `voice`.
+    pub fn observe(&self, voice: VoiceId) -> VoicePosition {
+        let position = VoicePosition::default();
+        self.send(MixerCommand::Observe(voice, position.clone()));
+
+        position
+    }
+
+    /// Replaces the effects of `voice`, which are run in order on the voice before it is mixed
+    /// into its bus.
+    pub fn set_voice_effects(&self, voice: VoiceId, effects: Vec<Box<dyn AudioEffect>>) {
+        self.send(MixerCommand::SetVoiceEffects(voice, effects));
+    }
+
+    /// Gain applied to every voice on `bus`. Kept in sync with [`crate::AudioBuses`] by the
+    /// [`crate::AudioPlugin`].
+    pub fn set_bus_gain(&self, bus: AudioBus, gain: f32) {
+        self.send(MixerCommand::SetBusGain(bus, gain));
+    }
+
+    /// Mixes `bus` into `output` after its effects. Kept in sync with [`crate::AudioBuses`] by the
+    /// [`crate::AudioPlugin`].
+    pub fn set_bus_output(&self, bus: AudioBus, output: AudioBus) {
+        self.send(MixerCommand::SetBusOutput(bus, output));
+    }
+
+    /// Replaces the effects of `bus`, which are run in order on everything mixed into the bus.
+    ///
+    /// ```ignore
+    /// // Muffle the game while the pause menu is open
+    /// output.mixer().set_bus_effects(AudioBus::Sfx, vec![Box::new(Biquad::low_pass(600.0, 0.7))]);
+    /// ```
+    pub fn set_bus_effects(&self, bus: AudioBus, effects: Vec<Box<dyn AudioEffect>>) {
+        self.send(MixerCommand::SetBusEffects(bus, effects));
+    }
+
+    /// Events sent since the last call.
+    pub fn events(&self) -> impl Iterator<Item = MixerEvent> + '_ {
+        self.events.try_iter()
+    }
+
+    fn send(&self, command: MixerCommand) {
+        // Disconnected if there is no output stream, which is reported when it fails to start
+        if let Err(TrySendError::Full(_)) = self.commands.try_send(command) {
+            error!("Audio command queue is full");
+        }
+    }
+}
+
+/// Decoded frames of an [`AudioStream`]. Frames are decoded ahead by a [`StreamDecoder`] and
+/// released once the voice has passed them.
+struct StreamBuffer {
+    decoder: StreamDecoder,
+    samples: VecDeque<f32>,
+    /// Frame of the first sample in `samples`.
+    start: usize,
+    ended: bool,
+    /// The last sample requested has not been decoded yet.
+    pending: bool,
+    /// Wait for the decoder instead of stalling, see [`Mixer::set_offline`].
+    wait: bool,
+}
+
+impl StreamBuffer {
+    fn new(decoder: StreamDecoder) -> Self {
+        // Room for the head and the queued blocks, so playback does not allocate
+        let mut samples = VecDeque::with_capacity(decoder.head().len() * 2);
+        samples.extend(decoder.head());
+
+        Self {
+            ended: decoder.head_ended(),
+            decoder,
+            samples,
+            start: 0,
+            pending: false,
+            wait: false,
+        }
+    }
+
+    fn channels(&self) -> usize {
+        self.decoder.channels().max(1) as usize
+    }
+
+    fn sample(&mut self, frame: usize, channel: usize) -> Option<f32> {
+        let channels = self.channels();
+        let offset = frame.checked_sub(self.start)?;
+        self.pending = false;
+        while (offset + 1) * channels > self.samples.len() {
+            if self.ended {
+                return None;
+            }
+            match self.decoder.next(self.wait) {
+                StreamBlock::Samples(block) => {
+                    self.samples.extend(&block);
+                    self.decoder.recycle(block);
+                }
+                StreamBlock::End => self.ended = true,
+                StreamBlock::Pending => {
+                    self.pending = true;
+                    return None;
+                }
+            }
+        }
+
+        Some(self.samples[offset * channels + channel])
+    }
+
+    /// Releases the frames before `frame`.
+    fn release(&mut self, frame: usize) {
+        let frames = frame
+            .saturating_sub(self.start)
+            .min(self.samples.len() / self.channels());
+        self.samples.drain(..frames * self.channels());
+        self.start += frames;
+    }
+
+    /// Frames within the head are copied from it, so looping back to the start does not wait on
+    /// the decoder.
+    fn seek(&mut self, frame: usize) {
+        let channels = self.channels();
+        let head = self.decoder.head();
+        let head_frames = head.len() / channels;
+
+        self.samples.clear();
+        if frame < head_frames {
+            self.samples.extend(&head[frame * channels..]);
+        }
+        self.start = frame;
+        self.pending = false;
+        self.ended = self.decoder.head_ended();
+        if !self.ended {
+            self.decoder.seek(frame.max(head_frames) as u64);
+        }
+    }
+}
+
+enum VoiceSource {
+    Buffered(AudioSource),
+    Streamed(StreamBuffer),
+}
+
+impl VoiceSource {
+    fn channels(&self) -> usize {
+        match self {
+            Self::Buffered(source) => source.channels.max(1) as usize,
+            Self::Streamed(stream) => stream.channels(),
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        match self {
+            Self::Buffered(source) => source.sample_rate,
+            Self::Streamed(stream) => stream.decoder.sample_rate(),
+        }
+    }
+
+    /// Returns `None` past the end of the source.
+    fn sample(&mut self, frame: usize, channel: usize) -> Option<f32> {
+        match self {
+            Self::Buffered(source) => source
+                .samples
+                .get(frame * source.channels.max(1) as usize + channel)
+                .copied(),
+            Self::Streamed(stream) => stream.sample(frame, channel),
+        }
+    }
+
+    /// The source stalled on its decoder at the last sample requested.
+    fn is_pending(&self) -> bool {
+        match self {
+            Self::Buffered(_) => false,
+            Self::Streamed(stream) => stream.pending,
+        }
+    }
+
+    /// Length in frames, once it is known.
+    fn frames(&self) -> Option<usize> {
+        match self {
+            Self::Buffered(source) => Some(source.frames()),
+            Self::Streamed(stream) => stream
+                .ended
+                .then(|| stream.start + stream.samples.len() / stream.channels()),
+        }
+    }
+
+    fn release(&mut self, frame: usize) {
+        if let Self::Streamed(stream) = self {
+            stream.release(frame);
+        }
+    }
+
+    fn seek(&mut self, frame: usize) {
+        if let Self::Streamed(stream) = self {
+            stream.seek(frame);
+        }
+    }
+}
+
+/// Volume or speed ramp of a [`Voice`].
+struct Fade {
+    /// Change per output frame.
+    step: f32,
+    /// Output frames until `target` is reached.
+    frames: u64,
+    target: f32,
+    /// Stop the voice once a volume ramp is finished.
+    stop: bool,
+}
+
+impl Fade {
+    fn new(from: f32, target: f32, duration: Duration, config: &MixerConfig, stop: bool) -> Self {
+        let frames = config.frames(duration);
+        Self {
+            step: (target - from) / frames.max(1) as f32,
+            frames,
+            target,
+            stop,
+        }
+    }
+
+    /// Steps `value` by one frame. Returns true once `target` is reached.
+    fn update(&mut self, value: &mut f32) -> bool {
+        *value += self.step;
+        self.frames = self.frames.saturating_sub(1);
+        if self.frames > 0 {
+            return false;
+        }
+
+        *value = self.target;
+        true
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Spatial {
+    gain: f32,
+    pan: f32,
+    pitch: f32,
+}
+
+impl Default for Spatial {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            pan: 0.0,
+            pitch: 1.0,
+        }
+    }
+}
+
+impl Spatial {
+    /// Gain of an output channel. Stereo outputs are panned by attenuating the opposite side.
+    fn channel_gain(&self, channel: usize, channels: usize) -> f32 {
+        let pan = match (channels, channel) {
+            (2, 0) => (1.0 - self.pan).min(1.0),
+            (2, 1) => (1.0 + self.pan).min(1.0),
+            _ => 1.0,
+        };
+
+        self.gain * pan
+    }
+}
+
+struct Voice {
+    id: VoiceId,
+    source: VoiceSource,
+    /// In frames of the source.
+    position: f64,
+    volume: f32,
+    speed: f32,
+    looping: bool,
+    paused: bool,
+    bus: AudioBus,
+    /// Set by [`MixerHandle::set_spatial`].
+    spatial: Spatial,
+    fade: Option<Fade>,
+    speed_fade: Option<Fade>,
+    observer: Option<VoicePosition>,
+    effects: Vec<Box<dyn AudioEffect>>,
+    /// Frame of the mixer clock the voice starts at.
+    start: u64,
+    /// Order in which voices started. The oldest voice is stolen first.
+    age: u64,
+}
+
+impl Voice {
+    /// Adds the voice into the interleaved `output`, which starts at frame `clock` of the mixer
+    /// clock. Returns true once the source has ended, or a fade out has finished.
+    fn mix(&mut self, output: &mut [f32], config: &MixerConfig, bus_gain: f32, clock: u64) -> bool {
+        if self.paused {
+            return false;
+        }
+
+        let offset = self.start.saturating_sub(clock) as usize * config.channels as usize;
+        if offset >= output.len() {
+            return false;
+        }
+        let output = &mut output[offset..];
+
+        let ended = self.mix_frames(output, config, bus_gain);
+        if let Some(observer) = &self.observer {
+            observer.set(self.position / self.source.sample_rate().max(1) as f64);
+        }
+
+        ended
+    }
+
+    fn mix_frames(&mut self, output: &mut [f32], config: &MixerConfig, bus_gain: f32) -> bool {
+        let source_channels = self.source.channels();
+        let rate = self.source.sample_rate() as f64 / config.sample_rate as f64;
+        let channels = config.channels as usize;
+        for frame in output.chunks_exact_mut(config.channels as usize) {
+            let mut index = self.position as usize;
+            if self.source.sample(index, 0).is_none() {
+                // Silent until the decoder catches up
+                if self.source.is_pending() {
+                    return false;
+                }
+                match self.source.frames() {
+                    Some(frames) if self.looping && frames > 0 => {
+                        self.position %= frames as f64;
+                        self.source.seek(0);
+                        index = self.position as usize;
+                    }
+                    _ => return true,
+                }
+            }
+
+            let gain = self.volume * bus_gain;
+            let t = (self.position - index as f64) as f32;
+            for (output_channel, sample) in frame.iter_mut().enumerate() {
+                let channel = output_channel.min(source_channels - 1);
+                let s1 = self.source.sample(index, channel).unwrap_or_default();
+                let s2 = match self.source.sample(index + 1, channel) {
+                    Some(s2) => s2,
+                    None if self.looping => self.source.sample(0, channel).unwrap_or(s1),
+                    None => s1,
+                };
+                let pan = self.spatial.channel_gain(output_channel, channels);
+                *sample += (s1 + (s2 - s1) * t) * gain * pan;
+            }
+
+            self.position += (self.speed * self.spatial.pitch).max(0.0) as f64 * rate;
+            self.source.release(self.position as usize);
+            if self.update_fades() {
+                return true;
+            }
+        }
+
+        !self.looping
+            && self.source.sample(self.position as usize, 0).is_none()
+            && !self.source.is_pending()
+    }
+
+    /// Steps the fades by one frame. Returns true once a fade out has finished.
+    fn update_fades(&mut self) -> bool {
+        if let Some(fade) = &mut self.speed_fade {
+            if fade.update(&mut self.speed) {
+                self.speed_fade = None;
+            }
+        }
+
+        let Some(fade) = &mut self.fade else {
+            return false;
+        };
+        if !fade.update(&mut self.volume) {
+            return false;
+        }
+        let stop = fade.stop;
+        self.fade = None;
+
+        stop
+    }
+
+    fn seek(&mut self, position: Duration) {
+        let frame = position.as_secs_f64() * self.source.sample_rate() as f64;
+        let frame = match self.source.frames() {
+            Some(frames) => frame.min(frames as f64),
+            None => frame,
+        };
+        self.position = frame;
+        self.source.seek(frame as usize);
+    }
+}
+
+/// Voices mixed into a bus, before they are mixed into the bus's output.
+struct Bus {
+    buffer: Vec<f32>,
+    effects: Vec<Box<dyn AudioEffect>>,
+    output: AudioBus,
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self {
+            buffer: Vec::new(),
+            effects: Vec::new(),
+            output: AudioBus::Master,
+        }
+    }
+}
+
+/// Sums voices into interleaved output. Render offline by calling [`Mixer::render`] directly.
+pub struct Mixer {
+    config: MixerConfig,
+    voices: Vec<Voice>,
+    commands: Receiver<MixerCommand>,
+    events: Sender<MixerEvent>,
+    bus_gains: [f32; AudioBus::COUNT],
+    buses: [Bus; AudioBus::COUNT],
+    /// Block of a voice with effects.
+    scratch: Vec<f32>,
+    /// Output frames rendered.
+    clock: Arc<AtomicU64>,
+    next_age: u64,
+    offline: bool,
+}
+
+impl Debug for Mixer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mixer")
+            .field("config", &self.config)
+            .field("voices", &self.voices.len())
+            .field("bus_gains", &self.bus_gains)
+            .field("clock", &self.clock)
+            .finish()
+    }
+}
+
+impl Mixer {
+    pub fn new(config: MixerConfig) -> (Self, MixerHandle) {
+        let (commands_tx, commands_rx) = crossbeam_channel::bounded(QUEUE_CAPACITY);
+        let (events_tx, events_rx) = crossbeam_channel::bounded(QUEUE_CAPACITY);
+        let clock = Arc::new(AtomicU64::new(0));
+
+        let mixer = Self {
+            config,
+            voices: Vec::with_capacity(config.max_voices),
+            commands: commands_rx,
+            events: events_tx,
+            bus_gains: [1.0; AudioBus::COUNT],
+            buses: Default::default(),
+            scratch: Vec::new(),
+            clock: clock.clone(),
+            next_age: 0,
+            offline: false,
+        };
+        let handle = MixerHandle {
+            commands: commands_tx,
+            events: events_rx,
+            next_id: Arc::new(AtomicU64::new(0)),
+            clock,
+            config,
+        };
+
+        (mixer, handle)
+    }
+
+    pub fn config(&self) -> MixerConfig {
+        self.config
+    }
+
+    pub fn voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Offline mixers wait for streams to decode, rather than playing silence until they catch
+    /// up. For mixers which are not rendered in real time, such as when recording to a file.
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+        for voice in self.voices.iter_mut() {
+            if let VoiceSource::Streamed(stream) = &mut voice.source {
+                stream.wait = offline;
+            }
+        }
+    }
+
+    /// Applies queued commands, then overwrites `output` with the next block of interleaved
+    /// frames.
+    pub fn render(&mut self, output: &mut [f32]) {
+        while let Ok(command) = self.commands.try_recv() {
+            self.apply(command);
+        }
+
+        for bus in self.buses.iter_mut() {
+            bus.buffer.clear();
+            bus.buffer.resize(output.len(), 0.0);
+        }
+
+        let channels = self.config.channels as usize;
+        let sample_rate = self.config.sample_rate;
+        let clock = self.clock.load(Ordering::Relaxed);
+        let mut i = 0;
+        while i < self.voices.len() {
+            let voice = &mut self.voices[i];
+            let gain = self.bus_gains[voice.bus.index()];
+            let buffer = &mut self.buses[voice.bus.index()].buffer;
+            let ended = match voice.effects.is_empty() {
+                true => voice.mix(buffer, &self.config, gain, clock),
+                false => {
+                    self.scratch.clear();
+                    self.scratch.resize(output.len(), 0.0);
+                    let ended = voice.mix(&mut self.scratch, &self.config, gain, clock);
+                    for effect in voice.effects.iter_mut() {
+                        effect.process(&mut self.scratch, channels, sample_rate);
+                    }
+                    add(buffer, &self.scratch);
+                    ended
+                }
+            };
+
+            if ended {
+                let voice = self.voices.swap_remove(i);
+                self.send(MixerEvent::Finished(voice.id));
+            } else {
+                i += 1;
+            }
+        }
+
+        // Buses furthest from master first, so every bus has been mixed into before its effects run
+        let mut order = AudioBus::ALL;
+        order.sort_by_key(|bus| std::cmp::Reverse(self.depth(*bus)));
+        for bus in order {
+            let Bus {
+                buffer, effects, ..
+            } = &mut self.buses[bus.index()];
+            for effect in effects.iter_mut() {
+                effect.process(buffer, channels, sample_rate);
+            }
+
+            if bus != AudioBus::Master {
+                let buffer = std::mem::take(&mut self.buses[bus.index()].buffer);
+                let output = self.buses[bus.index()].output;
+                add(&mut self.buses[output.index()].buffer, &buffer);
+                self.buses[bus.index()].buffer = buffer;
+            }
+        }
+
+        output.copy_from_slice(&self.buses[AudioBus::Master.index()].buffer);
+        self.clock
+            .store(clock + (output.len() / channels) as u64, Ordering::Relaxed);
+    }
+
+    /// Number of buses between `bus` and [`AudioBus::Master`].
+    fn depth(&self, bus: AudioBus) -> usize {
+        // Routes are acyclic, so every path reaches master within COUNT steps
+        std::iter::successors(Some(bus), |bus| match bus {
+            AudioBus::Master => None,
+            bus => Some(self.buses[bus.index()].output),
+        })
+        .take(AudioBus::COUNT)
+        .count()
+    }
+
+    fn apply(&mut self, command: MixerCommand) {
+        match command {
+            MixerCommand::Play {
+                id,
+                mut source,
+                settings,
+                bus,
+            } => {
+                if let VoiceSource::Streamed(stream) = &mut source {
+                    stream.wait = self.offline;
+                }
+                if self.config.max_voices == 0 {
+                    self.send(MixerEvent::Stolen(id));
+                    return;
+                }
+                if self.voices.len() >= self.config.max_voices {
+                    if let Some(oldest) = self
+                        .voices
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, voice)| voice.age)
+                        .map(|(i, _)| i)
+                    {
+                        let voice = self.voices.swap_remove(oldest);
+                        self.send(MixerEvent::Stolen(voice.id));
+                    }
+                }
+
+                self.voices.push(Voice {
+                    id,
+                    source,
+                    position: 0.0,
+                    volume: settings.volume,
+                    speed: settings.speed,
+                    looping: settings.loop_track,
+                    paused: !settings.play_on_creation,
+                    bus,
+                    spatial: Spatial {
+                        gain: settings.spatial_gain,
+                        pan: settings.spatial_pan,
+                        ..Default::default()
+                    },
+                    fade: None,
+                    speed_fade: None,
+                    observer: None,
+                    effects: Vec::new(),
+                    start: settings.start_at.unwrap_or_default(),
+                    age: self.next_age,
+                });
+                self.next_age += 1;
+            }
+            MixerCommand::Pause(id) => {
+                if let Some(voice) = self.voice_mut(id) {
+                    voice.paused = true;
+                }
+            }
+            MixerCommand::Resume(id) => {
+                if let Some(voice) = self.voice_mut(id) {
+                    voice.paused = false;
+                }
+            }
+            MixerCommand::Stop(id) => {
+                if let Some(i) = self.voices.iter().position(|voice| voice.id == id) {
+                    self.voices.swap_remove(i);
+                    self.send(MixerEvent::Finished(id));
+                }
+            }
+            MixerCommand::SetVolume(id, volume) => {
+                if let Some(voice) = self.voice_mut(id) {
+                    voice.volume = volume;
+                    voice.fade = None;
+                }
+            }
+            MixerCommand::SetSpeed(id, speed) => {
+                if let Some(voice) = self.voice_mut(id) {
+                    voice.speed = speed;
+                    voice.speed_fade = None;
+                }
+            }
+            MixerCommand::RampSpeed {
+                id,
+                speed,
+                duration,
+            } => {
+                let config = self.config;
+                if let Some(voice) = self.voice_mut(id) {
+                    voice.speed_fade =
+                        Some(Fade::new(voice.speed, speed, duration, &config, false));
+                }
+            }
+            MixerCommand::Fade {
+                id,
+                volume,
+                duration,
+                stop,
+            } => {
+                let config = self.config;
+                if let Some(voice) = self.voice_mut(id) {
+                    voice.fade = Some(Fade::new(voice.volume, volume, duration, &config, stop));
+                }
+            }
+            MixerCommand::Seek(id, position) => {
+                if let Some(voice) = self.voice_mut(id) {
+                    voice.seek(position);
+                }
+            }
+            MixerCommand::SetSpatial {
+                id,
+                gain,
+                pan,
+                pitch,
+            } => {
+                if let Some(voice) = self.voice_mut(id) {
+                    voice.spatial = Spatial {
+                        gain,
+                        pan: pan.clamp(-1.0, 1.0),
+                        pitch,
+                    };
+                }
+            }
+            MixerCommand::Observe(id, position) => {
+                if let Some(voice) = self.voice_mut(id) {
+                    voice.observer = Some(position);
+                }
+            }
+            MixerCommand::SetVoiceEffects(id, effects) => {
+                if let Some(voice) = self.voice_mut(id) {
+                    voice.effects = effects;
+                }
+            }
+            MixerCommand::SetBusGain(bus, gain) => self.bus_gains[bus.index()] = gain,
+            MixerCommand::SetBusOutput(bus, output) => {
+                if bus != AudioBus::Master {
+                    self.buses[bus.index()].output = output;
+                }
+            }
+            MixerCommand::SetBusEffects(bus, effects) => self.buses[bus.index()].effects = effects,
+        }
+    }
+
+    fn voice_mut(&mut self, id: VoiceId) -> Option<&mut Voice> {
+        self.voices.iter_mut().find(|voice| voice.id == id)
+    }
+
+    fn send(&self, event: MixerEvent) {
+        if self.events.try_send(event).is_err() {
+            error!("Audio event queue is full");
+        }
+    }
+}
+
+fn add(output: &mut [f32], input: &[f32]) {
+    for (output, input) in output.iter_mut().zip(input) {
+        *output += input;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant(value: f32, frames: usize) -> AudioSource {
+        AudioSource {
+            samples: vec![value; frames].into(),
+            channels: 1,
+            sample_rate: 10,
+        }
+    }
+
+    fn mixer(max_voices: usize) -> (Mixer, MixerHandle) {
+        Mixer::new(MixerConfig {
+            sample_rate: 10,
+            channels: 2,
+            max_voices,
+        })
+    }
+
+    #[test]
+    fn mix_voices() {
+        let (mut mixer, handle) = mixer(4);
+        handle.play(
+            &constant(0.5, 10),
+            PlaybackSettings::default(),
+            AudioBus::Sfx,
+        );
+        let quiet = handle.play(
+            &constant(0.5, 10),
+            PlaybackSettings::default().with_volume(0.5),
+            AudioBus::Sfx,
+        );
+
+        let mut output = [0.0; 8];
+        mixer.render(&mut output);
+        assert_eq!(output, [0.75; 8]);
+
+        handle.stop(quiet);
+        handle.set_bus_gain(AudioBus::Sfx, 2.0);
+        mixer.render(&mut output);
+        assert_eq!(output, [1.0; 8]);
+        assert_eq!(
+            handle.events().collect::<Vec<_>>(),
+            [MixerEvent::Finished(quiet)]
+        );
+    }
+
+    #[test]
+    fn speed_and_looping() {
+        let (mut mixer, handle) = mixer(4);
+        let fast = handle.play(
+            &constant(1.0, 4),
+            PlaybackSettings::default().with_speed(2.0),
+            AudioBus::Sfx,
+        );
+        handle.play(
+            &constant(1.0, 3),
+            PlaybackSettings::default().loop_track(),
+            AudioBus::Music,
+        );
+
+        // 3 frames: the fast voice ends after 2, the looping voice wraps
+        let mut output = [0.0; 6];
+        mixer.render(&mut output);
+        assert_eq!(output, [2.0, 2.0, 2.0, 2.0, 1.0, 1.0]);
+        assert_eq!(
+            handle.events().collect::<Vec<_>>(),
+            [MixerEvent::Finished(fast)]
+        );
+
+        mixer.render(&mut output);
+        assert_eq!(output, [1.0; 6]);
+        assert_eq!(mixer.voices(), 1);
+    }
+
+    #[test]
+    fn steal_oldest_voice() {
+        let (mut mixer, handle) = mixer(2);
+        let settings = PlaybackSettings::default().loop_track();
+        let oldest = handle.play(&constant(0.25, 4), settings, AudioBus::Sfx);
+        handle.play(&constant(0.25, 4), settings, AudioBus::Sfx);
+        handle.play(&constant(0.25, 4), settings, AudioBus::Sfx);
+
+        let mut output = [0.0; 2];
+        mixer.render(&mut output);
+        assert_eq!(output, [0.5; 2]);
+        assert_eq!(mixer.voices(), 2);
+        assert_eq!(
+            handle.events().collect::<Vec<_>>(),
+            [MixerEvent::Stolen(oldest)]
+        );
+    }
+
+    #[test]
+    fn stream_voice() {
+        let stream =
+            AudioStream::new(include_bytes!("../fixtures/sine.flac").as_slice(), "flac").unwrap();
+        let (mut mixer, handle) = Mixer::new(MixerConfig {
+            sample_rate: 44100,
+            channels: 2,
+            max_voices: 4,
+        });
+        mixer.set_offline(true);
+        let once = handle.play_stream(&stream, PlaybackSettings::default(), AudioBus::Music);
+        handle.play_stream(
+            &stream,
+            PlaybackSettings::default().loop_track(),
+            AudioBus::Music,
+        );
+
+        // The fixture is 8820 frames long
+        let mut output = vec![0.0; 2 * 10_000];
+        mixer.render(&mut output);
+        assert_eq!(
+            handle.events().collect::<Vec<_>>(),
+            [MixerEvent::Finished(once)]
+        );
+
+        mixer.render(&mut output);
+        assert_eq!(mixer.voices(), 1);
+        assert!(output.iter().any(|s| s.abs() > 0.4));
+    }
+
+    #[test]
+    fn fade_and_seek() {
+        let (mut mixer, handle) = mixer(4);
+        let voice = handle.play(
+            &constant(1.0, 100),
+            PlaybackSettings::default(),
+            AudioBus::Sfx,
+        );
+        let position = handle.observe(voice);
+        handle.seek(voice, Duration::from_secs(5));
+        handle.fade_out(voice, Duration::from_millis(400));
+
+        // 4 frames to fade out, at 10 frames per second
+        let mut output = [0.0; 12];
+        mixer.render(&mut output);
+        assert_eq!(output[..8], [1.0, 1.0, 0.75, 0.75, 0.5, 0.5, 0.25, 0.25]);
+        assert_eq!(output[8..], [0.0; 4]);
+        assert_eq!(position.get(), Duration::from_secs_f64(5.4));
+        assert_eq!(
+            handle.events().collect::<Vec<_>>(),
+            [MixerEvent::Finished(voice)]
+        );
+    }
+
+    #[test]
+    fn speed_ramp() {
+        let (mut mixer, handle) = mixer(4);
+        let voice = handle.play(
+            &constant(1.0, 100),
+            PlaybackSettings::default(),
+            AudioBus::Sfx,
+        );
+        let position = handle.observe(voice);
+        handle.ramp_speed(voice, 3.0, Duration::from_millis(200));
+
+        // 1 frame of the source, then 2, then 3 for the rest
+        let mut output = [0.0; 8];
+        mixer.render(&mut output);
+        assert_eq!(position.get(), Duration::from_secs_f64(0.9));
+    }
+
+    #[test]
+    fn scheduled_start() {
+        let (mut mixer, handle) = mixer(4);
+        let mut output = [0.0; 8];
+        mixer.render(&mut output);
+        assert_eq!(handle.clock(), 4);
+
+        // Starts 2 frames into the third block
+        handle.play(
+            &constant(1.0, 10),
+            PlaybackSettings::default().start_at(10),
+            AudioBus::Sfx,
+        );
+        mixer.render(&mut output);
+        assert_eq!(output, [0.0; 8]);
+        mixer.render(&mut output);
+        assert_eq!(output, [0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(handle.clock(), 12);
+    }
+
+    #[test]
+    fn spatial_pan() {
+        let (mut mixer, handle) = mixer(4);
+        let voice = handle.play(
+            &constant(1.0, 10),
+            PlaybackSettings::default(),
+            AudioBus::Sfx,
+        );
+        handle.set_spatial(voice, 0.5, 0.5, 2.0);
+        let position = handle.observe(voice);
+
+        let mut output = [0.0; 4];
+        mixer.render(&mut output);
+        assert_eq!(output, [0.25, 0.5, 0.25, 0.5]);
+        // Twice the speed: 2 frames of the source per output frame
+        assert_eq!(position.get(), Duration::from_secs_f64(0.4));
+
+        // Panned from the first block
+        handle.stop(voice);
+        handle.play(
+            &constant(1.0, 10),
+            PlaybackSettings::default().with_spatial(0.5, -0.5),
+            AudioBus::Sfx,
+        );
+        mixer.render(&mut output);
+        assert_eq!(output, [0.5, 0.25, 0.5, 0.25]);
+    }
+
+    struct Gain(f32);
+
+    impl AudioEffect for Gain {
+        fn process(&mut self, samples: &mut [f32], _: usize, _: u32) {
+            samples.iter_mut().for_each(|sample| *sample *= self.0);
+        }
+    }
+
+    #[test]
+    fn voice_and_bus_effects() {
+        let (mut mixer, handle) = mixer(4);
+        let voice = handle.play(
+            &constant(1.0, 10),
+            PlaybackSettings::default(),
+            AudioBus::Voice,
+        );
+        handle.play(
+            &constant(1.0, 10),
+            PlaybackSettings::default(),
+            AudioBus::Music,
+        );
+        handle.set_voice_effects(voice, vec![Box::new(Gain(0.5))]);
+        handle.set_bus_output(AudioBus::Voice, AudioBus::Music);
+        handle.set_bus_effects(AudioBus::Music, vec![Box::new(Gain(0.5))]);
+        handle.set_bus_effects(AudioBus::Master, vec![Box::new(Gain(0.5))]);
+
+        // The voice bus is mixed into the music bus before its effects run
+        let mut output = [0.0; 4];
+        mixer.render(&mut output);
+        assert_eq!(output, [0.375; 4]);
+
+        handle.set_bus_effects(AudioBus::Music, Vec::new());
+        mixer.render(&mut output);
+        assert_eq!(output, [0.75; 4]);
+    }
+}
@@ -0,0 +1,474 @@
+//! Background music. The [`MusicPlayer`] streams one track at a time from a playlist and
+//! crossfades between tracks.
+
+use crate::{
+    mixer::{MixerEvent, VoiceId, VoicePosition},
+    AudioBus, AudioOutput, AudioStream, PlaybackSettings,
+};
+use asset::{Assets, Handle};
+use ecs::{EventReader, Res, ResMut, WinnyResource};
+use rand::seq::SliceRandom;
+use std::time::Duration;
+
+/// What plays after the last track of the playlist.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// Stop after the last track.
+    #[default]
+    Off,
+    /// Play the current track again.
+    One,
+    /// Start the playlist again.
+    All,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MusicRequest {
+    /// Start the track at this index of the play order.
+    Start(usize),
+    Pause,
+    Resume,
+    Stop,
+    Seek(Duration),
+}
+
+#[derive(Debug)]
+struct Track {
+    voice: VoiceId,
+    position: VoicePosition,
+    duration: Option<Duration>,
+    /// Set once the next track has been started.
+    advanced: bool,
+}
+
+/// Plays a playlist of [`AudioStream`]s, which are decoded while they play.
+///
+/// ```ignore
+/// fn start_music(mut music: ResMut<MusicPlayer>, server: Res<AssetServer>) {
+///     music.set_playlist(vec![server.load("music/day.ogg"), server.load("music/night.ogg")]);
+///     music.set_shuffle(true);
+///     music.repeat = Repeat::All;
+/// }
+/// ```
+#[derive(WinnyResource, Debug)]
+pub struct MusicPlayer {
+    /// Overlap of two tracks when changing tracks.
+    pub crossfade: Duration,
+    pub volume: f32,
+    /// Takes effect on the next track.
+    pub bus: AudioBus,
+    pub repeat: Repeat,
+    playlist: Vec<Handle<AudioStream>>,
+    /// Indices into `playlist` in the order they are played.
+    order: Vec<usize>,
+    /// Index into `order`.
+    current: Option<usize>,
+    shuffle: bool,
+    paused: bool,
+    requests: Vec<MusicRequest>,
+    /// Index into `order` of the track waiting for its stream to load.
+    pending: Option<usize>,
+    track: Option<Track>,
+}
+
+impl Default for MusicPlayer {
+    fn default() -> Self {
+        Self {
+            crossfade: Duration::from_secs(2),
+            volume: 1.0,
+            bus: AudioBus::Music,
+            repeat: Repeat::Off,
+            playlist: Vec::new(),
+            order: Vec::new(),
+            current: None,
+            shuffle: false,
+            paused: false,
+            requests: Vec::new(),
+            pending: None,
+            track: None,
+        }
+    }
+}
+
+impl MusicPlayer {
+    /// Crossfades to `track`, replacing the playlist.
+    pub fn play(&mut self, track: Handle<AudioStream>) {
+        self.set_playlist(vec![track]);
+    }
+
+    /// Crossfades to the first track of `playlist`.
+    pub fn set_playlist(&mut self, playlist: Vec<Handle<AudioStream>>) {
+        self.playlist = playlist;
+        // Indexed the old play order
+        self.current = None;
+        self.order = (0..self.playlist.len()).collect();
+        if self.shuffle {
+            self.order.shuffle(&mut rand::thread_rng());
+        }
+
+        match self.playlist.is_empty() {
+            true => self.stop(),
+            false => self.requests.push(MusicRequest::Start(0)),
+        }
+    }
+
+    /// Adds `track` to the end of the playlist, starting it if nothing is playing.
+    pub fn queue(&mut self, track: Handle<AudioStream>) {
+        self.playlist.push(track);
+        self.order.push(self.playlist.len() - 1);
+        let starting = self
+            .requests
+            .iter()
+            .any(|request| matches!(request, MusicRequest::Start(_)));
+        if self.current.is_none() && !starting {
+            self.requests
+                .push(MusicRequest::Start(self.order.len() - 1));
+        }
+    }
+
+    pub fn playlist(&self) -> &[Handle<AudioStream>] {
+        &self.playlist
+    }
+
+    /// Crossfades to the next track, following [`MusicPlayer::repeat`].
+    pub fn next(&mut self) {
+        if let Some(next) = self.next_index() {
+            self.requests.push(MusicRequest::Start(next));
+        }
+    }
+
+    /// Crossfades to the previous track in the play order.
+    pub fn previous(&mut self) {
+        if let Some(current) = self.current.filter(|current| *current < self.order.len()) {
+            let previous = match current {
+                0 if self.repeat == Repeat::All => self.order.len() - 1,
+                0 => 0,
+                current => current - 1,
+            };
+            self.requests.push(MusicRequest::Start(previous));
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.requests.push(MusicRequest::Pause);
+    }
+
+    pub fn resume(&mut self) {
+        self.requests.push(MusicRequest::Resume);
+    }
+
+    /// Fades out the current track over [`MusicPlayer::crossfade`].
+    pub fn stop(&mut self) {
+        self.requests.push(MusicRequest::Stop);
+    }
+
+    /// Continues the current track from `position`.
+    pub fn seek(&mut self, position: Duration) {
+        self.requests.push(MusicRequest::Seek(position));
+    }
+
+    /// Shuffles the tracks after the current track.
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        self.shuffle = shuffle;
+        let start = self.current.map(|current| current + 1).unwrap_or_default();
+        let rest = &mut self.order[start.min(self.playlist.len())..];
+        match shuffle {
+            true => rest.shuffle(&mut rand::thread_rng()),
+            false => rest.sort_unstable(),
+        }
+    }
+
+    pub fn shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    /// The track which is playing or loading.
+    pub fn current(&self) -> Option<&Handle<AudioStream>> {
+        self.current
+            .and_then(|current| self.order.get(current))
+            .map(|track| &self.playlist[*track])
+    }
+
+    /// Position in the current track.
+    pub fn position(&self) -> Option<Duration> {
+        self.track.as_ref().map(|track| track.position.get())
+    }
+
+    /// Length of the current track, if its container stores it.
+    pub fn duration(&self) -> Option<Duration> {
+        self.track.as_ref().and_then(|track| track.duration)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.track.is_some() && !self.paused
+    }
+
+    fn next_index(&self) -> Option<usize> {
+        let current = self.current?;
+        match self.repeat {
+            Repeat::One => Some(current),
+            _ if current + 1 < self.order.len() => Some(current + 1),
+            Repeat::All if !self.order.is_empty() => Some(0),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn update_music_player(
+    mut player: ResMut<MusicPlayer>,
+    output: Res<AudioOutput>,
+    streams: Res<Assets<AudioStream>>,
+    events: EventReader<MixerEvent>,
+) {
+    let player = &mut *player;
+    let mixer = output.mixer();
+
+    for request in std::mem::take(&mut player.requests) {
+        match request {
+            // Requested before the playlist was replaced
+            MusicRequest::Start(index) if index >= player.order.len() => {}
+            MusicRequest::Start(index) => {
+                player.current = Some(index);
+                player.pending = Some(index);
+            }
+            MusicRequest::Pause => {
+                if let Some(track) = &player.track {
+                    mixer.pause(track.voice);
+                }
+                player.paused = true;
+            }
+            MusicRequest::Resume => {
+                if let Some(track) = &player.track {
+                    mixer.resume(track.voice);
+                }
+                player.paused = false;
+            }
+            MusicRequest::Stop => {
+                if let Some(track) = player.track.take() {
+                    mixer.fade_out(track.voice, player.crossfade);
+                }
+                player.current = None;
+                player.pending = None;
+                player.paused = false;
+            }
+            MusicRequest::Seek(position) => {
+                if let Some(track) = &player.track {
+                    mixer.seek(track.voice, position);
+                }
+            }
+        }
+    }
+
+    // Start the next track ahead of the end of the current track, so they overlap
+    let crossfade = player.crossfade;
+    let (advance, finished) = match &mut player.track {
+        Some(track) => {
+            let ending = !crossfade.is_zero()
+                && track
+                    .duration
+                    .is_some_and(|duration| track.position.get() + crossfade >= duration);
+            let finished = events.peak_read().any(|event| event.voice() == track.voice);
+
+            (
+                (ending || finished) && !std::mem::replace(&mut track.advanced, true),
+                finished,
+            )
+        }
+        None => (false, false),
+    };
+    if advance {
+        if let Some(next) = player.next_index() {
+            player.current = Some(next);
+            player.pending = Some(next);
+        }
+    }
+    if finished {
+        player.track = None;
+        if player.pending.is_none() {
+            player.current = None;
+        }
+    }
+
+    let Some(index) = player.pending else {
+        return;
+    };
+    let Some(&track) = player.order.get(index) else {
+        player.pending = None;
+        return;
+    };
+    let Some(stream) = streams.get(&player.playlist[track]) else {
+        return;
+    };
+    player.pending = None;
+    player.paused = false;
+
+    let fade_in = !player.crossfade.is_zero() && player.track.is_some();
+    if let Some(track) = player.track.take() {
+        mixer.fade_out(track.voice, player.crossfade);
+    }
+    let settings = PlaybackSettings::default().with_volume(match fade_in {
+        true => 0.0,
+        false => player.volume,
+    });
+    let voice = mixer.play_stream(stream, settings, player.bus);
+    if fade_in {
+        mixer.fade(voice, player.volume, player.crossfade);
+    }
+    player.track = Some(Track {
+        voice,
+        position: mixer.observe(voice),
+        duration: stream.duration(),
+        advanced: false,
+    });
+}
+
+pub(crate) fn update_music_volume(
+    player: Res<MusicPlayer>,
+    output: Res<AudioOutput>,
+    mut volume: ecs::Local<Option<f32>>,
+) {
+    if *volume == Some(player.volume) {
+        return;
+    }
+    if let Some(track) = &player.track {
+        if volume.is_some() {
+            output.mixer().set_volume(track.voice, player.volume);
+        }
+    }
+    *volume = Some(player.volume);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mixer::Mixer, MixerConfig, NullBackend};
+    use ecs::{schedule::SystemId, Events, World};
+
+    fn playlist(tracks: usize) -> MusicPlayer {
+        let mut player = MusicPlayer::default();
+        player.set_playlist((0..tracks).map(|_| Handle::dangling()).collect());
+        player.current = Some(0);
+
+        player
+    }
+
+    #[test]
+    fn playlist_order() {
+        let mut player = playlist(3);
+        assert_eq!(player.next_index(), Some(1));
+        player.current = Some(2);
+        assert_eq!(player.next_index(), None);
+        player.repeat = Repeat::All;
+        assert_eq!(player.next_index(), Some(0));
+        player.repeat = Repeat::One;
+        assert_eq!(player.next_index(), Some(2));
+
+        let mut player = playlist(50);
+        player.set_shuffle(true);
+        assert_eq!(player.order[0], 0);
+        let mut order = player.order.clone();
+        order.sort_unstable();
+        assert_eq!(order, (0..50).collect::<Vec<_>>());
+        player.set_shuffle(false);
+        assert_eq!(player.order, order);
+    }
+
+    #[test]
+    fn replace_playlist() {
+        let mut player = playlist(3);
+        player.current = Some(2);
+        player.set_playlist(vec![Handle::dangling()]);
+        assert!(player.current().is_none());
+        player.previous();
+
+        player.set_playlist(Vec::new());
+        assert!(player.current().is_none());
+        assert_eq!(player.next_index(), None);
+    }
+
+    struct Music {
+        world: World,
+        mixer: Mixer,
+        forward: SystemId,
+        update: SystemId,
+    }
+
+    impl Music {
+        fn new(player: MusicPlayer, streams: Assets<AudioStream>) -> Self {
+            let config = MixerConfig {
+                sample_rate: 44100,
+                channels: 2,
+                max_voices: 4,
+            };
+            let mut output = AudioOutput::with_backend(config, NullBackend::default());
+            let mut mixer = output.pending.take().unwrap();
+            mixer.set_offline(true);
+
+            let mut world = World::default();
+            world.insert_resource(output);
+            world.insert_resource(streams);
+            world.insert_resource(player);
+            world.register_event::<MixerEvent>();
+            let forward = world.register_system(crate::forward_mixer_events);
+            let update = world.register_system(update_music_player);
+            world.run_system(update);
+
+            Self {
+                world,
+                mixer,
+                forward,
+                update,
+            }
+        }
+
+        /// Renders `frames`, then updates the player with the mixer's events.
+        fn render(&mut self, frames: usize) {
+            self.world.resource_mut::<Events<MixerEvent>>().flush();
+            self.mixer.render(&mut vec![0.0; frames * 2]);
+            self.world.run_system(self.forward);
+            self.world.run_system(self.update);
+        }
+
+        fn player(&self) -> ecs::Res<'_, MusicPlayer> {
+            self.world.resource::<MusicPlayer>()
+        }
+    }
+
+    #[test]
+    fn crossfade_and_advance() {
+        let stream =
+            AudioStream::new(include_bytes!("../fixtures/sine.flac").as_slice(), "flac").unwrap();
+        let mut streams = Assets::default();
+        let first = streams.add(stream.clone());
+        let second = streams.add(stream);
+
+        let mut player = MusicPlayer {
+            crossfade: Duration::from_millis(50),
+            ..Default::default()
+        };
+        player.set_playlist(vec![first.clone(), second.clone()]);
+        let mut music = Music::new(player, streams);
+        assert_eq!(music.player().current(), Some(&first));
+
+        // The fixture is 8820 frames long, so the second track starts 2205 frames before the end
+        music.render(7000);
+        assert_eq!(music.player().current(), Some(&second));
+        music.render(1000);
+        assert_eq!(music.mixer.voices(), 2);
+
+        // The first track has faded out
+        music.render(2000);
+        assert_eq!(music.mixer.voices(), 1);
+        assert!(music.player().is_playing());
+
+        // Repeat is off, so the player stops after the second track
+        music.render(10_000);
+        assert_eq!(music.mixer.voices(), 0);
+        assert!(music.player().current().is_none());
+        assert!(!music.player().is_playing());
+    }
+}
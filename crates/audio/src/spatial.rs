@@ -0,0 +1,238 @@
+//! 2D spatial audio. Sounds with [`SpatialAudio`] are attenuated and panned by their distance to
+//! the [`AudioListener`].
+
+use crate::{AudioOutput, AudioPlayback, PlaybackSettings};
+use app::time::DeltaTime;
+use ecs::{egui, egui_widget::Widget, Entity, Local, Query, Res, WinnyAsEgui, WinnyComponent};
+use gfx::transform::Transform;
+use math::vector::Vec2f;
+use std::collections::HashMap;
+
+/// Hears [`SpatialAudio`] sounds from its [`Transform`]. Only the first listener is used.
+#[derive(WinnyComponent, WinnyAsEgui, Debug, Clone, Copy)]
+pub struct AudioListener {
+    /// In world units per second. Used for doppler.
+    pub speed_of_sound: f32,
+}
+
+impl Default for AudioListener {
+    fn default() -> Self {
+        Self {
+            speed_of_sound: 3430.0,
+        }
+    }
+}
+
+/// How the gain of a [`SpatialAudio`] sound falls off between its minimum and maximum distance.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Rolloff {
+    #[default]
+    Linear,
+    /// `min_distance / distance`, like a point source.
+    Inverse,
+    /// `(min_distance / distance) ^ exponent`.
+    Exponential(f32),
+}
+
+impl Widget for Rolloff {
+    fn display(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("{self:?}"));
+    }
+}
+
+/// Attenuates and pans a sound by the position of its [`Transform`] relative to the
+/// [`AudioListener`]. Spawned with an [`crate::AudioBundle`].
+///
+/// ```ignore
+/// commands.spawn((
+///     AudioBundle {
+///         handle: server.load("sounds/explosion.ogg"),
+///         playback_settings: PlaybackSettings::default(),
+///         bus: AudioBus::Sfx,
+///     },
+///     SpatialAudio::default(),
+///     Transform::default(),
+/// ));
+/// ```
+#[derive(WinnyComponent, WinnyAsEgui, Debug, Clone, Copy)]
+pub struct SpatialAudio {
+    /// Full volume within this distance.
+    pub min_distance: f32,
+    /// Silent beyond this distance.
+    pub max_distance: f32,
+    pub rolloff: Rolloff,
+    /// How far the sound pans to the side it is on, from 0 (centered) to 1 (one speaker only).
+    pub pan_width: f32,
+    /// Scales the pitch shift from the velocity of the sound and listener. 0 disables doppler.
+    pub doppler: f32,
+}
+
+impl Default for SpatialAudio {
+    fn default() -> Self {
+        Self {
+            min_distance: 100.0,
+            max_distance: 1000.0,
+            rolloff: Rolloff::Linear,
+            pan_width: 0.8,
+            doppler: 0.0,
+        }
+    }
+}
+
+impl SpatialAudio {
+    /// Gain of a sound at `distance` from the listener.
+    pub fn gain(&self, distance: f32) -> f32 {
+        if distance <= self.min_distance {
+            return 1.0;
+        }
+        if distance >= self.max_distance {
+            return 0.0;
+        }
+
+        let min = self.min_distance.max(f32::EPSILON);
+        match self.rolloff {
+            Rolloff::Linear => 1.0 - (distance - min) / (self.max_distance - min),
+            Rolloff::Inverse => min / distance,
+            Rolloff::Exponential(exponent) => (min / distance).powf(exponent),
+        }
+    }
+
+    /// Pan of a sound at `offset` from the listener, from -1 (left) to 1 (right). Sounds within
+    /// the minimum distance are panned less.
+    pub fn pan(&self, offset: Vec2f) -> f32 {
+        let distance = offset.magnitude().max(self.min_distance);
+        if distance <= 0.0 {
+            return 0.0;
+        }
+
+        (offset.x / distance).clamp(-1.0, 1.0) * self.pan_width.clamp(0.0, 1.0)
+    }
+
+    /// Pitch of a sound at `offset` from the listener, moving with the relative `velocity`.
+    pub fn pitch(&self, offset: Vec2f, velocity: Vec2f, speed_of_sound: f32) -> f32 {
+        let distance = offset.magnitude();
+        if self.doppler == 0.0 || distance <= 0.0 || speed_of_sound <= 0.0 {
+            return 1.0;
+        }
+
+        // Positive when the sound moves towards the listener
+        let approach = -(offset.x * velocity.x + offset.y * velocity.y) / distance;
+        let approach = (approach * self.doppler).clamp(-0.5 * speed_of_sound, 0.5 * speed_of_sound);
+
+        speed_of_sound / (speed_of_sound - approach)
+    }
+}
+
+fn position(transform: &Transform) -> Vec2f {
+    Vec2f::new(transform.translation.x, transform.translation.y)
+}
+
+fn velocity(from: Vec2f, to: Vec2f, dt: f32) -> Vec2f {
+    let moved = to - from;
+    Vec2f::new(moved.x / dt, moved.y / dt)
+}
+
+/// Starts a [`SpatialAudio`] sound at `transform` with its gain and pan, rather than centered at
+/// full volume until [`update_spatial_audio`] runs.
+pub(crate) fn spatialize(
+    settings: PlaybackSettings,
+    spatial: &SpatialAudio,
+    transform: &Transform,
+    listener: &Transform,
+) -> PlaybackSettings {
+    let offset = position(transform) - position(listener);
+    settings.with_spatial(spatial.gain(offset.magnitude()), spatial.pan(offset))
+}
+
+pub(crate) fn update_spatial_audio(
+    listeners: Query<(Transform, AudioListener)>,
+    sounds: Query<(Entity, AudioPlayback, SpatialAudio, Transform)>,
+    output: Res<AudioOutput>,
+    delta: Res<DeltaTime>,
+    mut last_positions: Local<Option<(Vec2f, HashMap<Entity, Vec2f>)>>,
+) {
+    let Some((listener_transform, listener)) = listeners.iter().next() else {
+        return;
+    };
+    let listener_position = position(listener_transform);
+    let (last_listener, last_sounds) =
+        last_positions.get_or_insert_with(|| (listener_position, HashMap::new()));
+    let dt = delta.delta.max(f32::EPSILON);
+    let listener_velocity = velocity(*last_listener, listener_position, dt);
+    *last_listener = listener_position;
+
+    let mut positions = HashMap::with_capacity(last_sounds.len());
+    for (entity, playback, spatial, transform) in sounds.iter() {
+        let sound_position = position(transform);
+        let offset = sound_position - listener_position;
+        let relative_velocity = match last_sounds.get(&entity) {
+            Some(last) => velocity(*last, sound_position, dt) - listener_velocity,
+            None => Vec2f::zero(),
+        };
+        positions.insert(entity, sound_position);
+
+        output.mixer().set_spatial(
+            playback.voice(),
+            spatial.gain(offset.magnitude()),
+            spatial.pan(offset),
+            spatial.pitch(offset, relative_velocity, listener.speed_of_sound),
+        );
+    }
+    *last_sounds = positions;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gain_pan_and_pitch() {
+        let spatial = SpatialAudio {
+            min_distance: 10.0,
+            max_distance: 110.0,
+            pan_width: 0.5,
+            doppler: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(spatial.gain(5.0), 1.0);
+        assert_eq!(spatial.gain(60.0), 0.5);
+        assert_eq!(spatial.gain(200.0), 0.0);
+        let inverse = SpatialAudio {
+            rolloff: Rolloff::Inverse,
+            ..spatial
+        };
+        assert_eq!(inverse.gain(20.0), 0.5);
+
+        assert_eq!(spatial.pan(Vec2f::new(-100.0, 0.0)), -0.5);
+        assert_eq!(spatial.pan(Vec2f::new(5.0, 0.0)), 0.25);
+        assert_eq!(spatial.pan(Vec2f::new(0.0, 100.0)), 0.0);
+
+        // Approaching from the right at a third of the speed of sound
+        let pitch = spatial.pitch(Vec2f::new(100.0, 0.0), Vec2f::new(-100.0, 0.0), 300.0);
+        assert_eq!(pitch, 1.5);
+        assert_eq!(
+            spatial.pitch(Vec2f::new(100.0, 0.0), Vec2f::zero(), 300.0),
+            1.0
+        );
+    }
+
+    #[test]
+    fn spatialize_settings() {
+        let spatial = SpatialAudio {
+            min_distance: 10.0,
+            max_distance: 110.0,
+            pan_width: 1.0,
+            ..Default::default()
+        };
+        let mut transform = Transform::default();
+        transform.translation.x = -60.0;
+        let mut listener = Transform::default();
+        listener.translation.y = 20.0;
+
+        let settings = spatialize(PlaybackSettings::default(), &spatial, &transform, &listener);
+        let offset = Vec2f::new(-60.0, -20.0);
+        assert_eq!(settings.spatial_gain, spatial.gain(offset.magnitude()));
+        assert_eq!(settings.spatial_pan, spatial.pan(offset));
+        assert!(settings.spatial_gain < 1.0 && settings.spatial_pan < -0.9);
+    }
+}
@@ -0,0 +1,505 @@
+//! Sounds synthesized at runtime, for retro-style games. [`Sfx`] generates sfxr-style effects from
+//! a handful of parameters.
+//!
+//! ```ignore
+//! fn coin(mut sources: ResMut<Assets<AudioSource>>, mut commands: Commands) {
+//!     let handle = sources.add(Sfx::pickup(&mut rand::thread_rng()).generate());
+//!     commands.spawn(AudioBundle {
+//!         handle,
+//!         playback_settings: PlaybackSettings::default(),
+//!         bus: AudioBus::Sfx,
+//!     });
+//! }
+//! ```
+
+use crate::{AudioEffect, AudioSource, Biquad};
+use rand::Rng;
+use std::{f32::consts::TAU, time::Duration};
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// Steps of noise per period, so noise has a pitch like in sfxr.
+const NOISE_STEPS: f32 = 32.0;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    #[default]
+    Sine,
+    Square,
+    Saw,
+    Noise,
+}
+
+/// Generates a waveform one sample at a time.
+#[derive(Debug, Clone)]
+pub struct Oscillator {
+    pub waveform: Waveform,
+    /// In hertz.
+    pub frequency: f32,
+    pub amplitude: f32,
+    /// Fraction of each period a square wave is high.
+    pub duty: f32,
+    /// Position in the period, from 0 to 1.
+    phase: f32,
+    noise: f32,
+    noise_step: u32,
+    /// State of the xorshift generator for noise, so sounds are reproducible.
+    seed: u32,
+}
+
+impl Oscillator {
+    pub fn new(waveform: Waveform, frequency: f32) -> Self {
+        Self {
+            waveform,
+            frequency,
+            amplitude: 1.0,
+            duty: 0.5,
+            phase: 0.0,
+            noise: 0.0,
+            noise_step: u32::MAX,
+            seed: 0x9e37_79b9,
+        }
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    pub fn with_duty(mut self, duty: f32) -> Self {
+        self.duty = duty;
+        self
+    }
+
+    /// Returns the next sample and advances by one frame.
+    pub fn sample(&mut self, sample_rate: u32) -> f32 {
+        let sample = match self.waveform {
+            Waveform::Sine => (self.phase * TAU).sin(),
+            Waveform::Square => match self.phase < self.duty {
+                true => 1.0,
+                false => -1.0,
+            },
+            Waveform::Saw => 1.0 - 2.0 * self.phase,
+            Waveform::Noise => {
+                let step = (self.phase * NOISE_STEPS) as u32;
+                if step != self.noise_step {
+                    self.noise_step = step;
+                    self.noise = self.random();
+                }
+                self.noise
+            }
+        };
+
+        self.phase = (self.phase + self.frequency / sample_rate as f32).fract();
+
+        sample * self.amplitude
+    }
+
+    /// Renders `duration` of the waveform as a mono source.
+    pub fn render(&mut self, duration: Duration, sample_rate: u32) -> AudioSource {
+        let frames = frames(duration, sample_rate);
+        AudioSource {
+            samples: (0..frames).map(|_| self.sample(sample_rate)).collect(),
+            channels: 1,
+            sample_rate,
+        }
+    }
+
+    /// From -1 to 1.
+    fn random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+
+        self.seed as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+fn frames(duration: Duration, sample_rate: u32) -> usize {
+    (duration.as_secs_f64() * sample_rate as f64) as usize
+}
+
+/// Attack, decay, sustain and release envelope.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adsr {
+    pub attack: Duration,
+    pub decay: Duration,
+    /// Level held after the decay, from 0 to 1.
+    pub sustain: f32,
+    pub release: Duration,
+}
+
+impl Default for Adsr {
+    fn default() -> Self {
+        Self {
+            attack: Duration::from_millis(10),
+            decay: Duration::from_millis(100),
+            sustain: 0.7,
+            release: Duration::from_millis(200),
+        }
+    }
+}
+
+impl Adsr {
+    /// Gain at `time` after the start of a note which is released after `held`.
+    pub fn gain(&self, time: Duration, held: Duration) -> f32 {
+        let level = |time: Duration| {
+            if time < self.attack {
+                time.as_secs_f32() / self.attack.as_secs_f32()
+            } else if time < self.attack + self.decay {
+                let decayed = (time - self.attack).as_secs_f32() / self.decay.as_secs_f32();
+                1.0 - (1.0 - self.sustain) * decayed
+            } else {
+                self.sustain
+            }
+        };
+
+        if time < held {
+            return level(time);
+        }
+        let released = match self.release.is_zero() {
+            true => 1.0,
+            false => (time - held).as_secs_f32() / self.release.as_secs_f32(),
+        };
+
+        level(held) * (1.0 - released).max(0.0)
+    }
+
+    /// Renders a note of `oscillator`, released after `held`, as a mono source.
+    pub fn render(
+        &self,
+        oscillator: &mut Oscillator,
+        held: Duration,
+        sample_rate: u32,
+    ) -> AudioSource {
+        let frames = frames(held + self.release, sample_rate);
+        AudioSource {
+            samples: (0..frames)
+                .map(|i| {
+                    let time = Duration::from_secs_f64(i as f64 / sample_rate as f64);
+                    oscillator.sample(sample_rate) * self.gain(time, held)
+                })
+                .collect(),
+            channels: 1,
+            sample_rate,
+        }
+    }
+}
+
+/// Parameters of an sfxr-style sound effect. Start from a preset such as [`Sfx::pickup`] and
+/// tweak it, then [`Sfx::generate`] the sound.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sfx {
+    pub waveform: Waveform,
+    /// Starting frequency, in hertz.
+    pub frequency: f32,
+    /// The sound ends once it slides below this frequency.
+    pub min_frequency: f32,
+    /// In octaves per second.
+    pub slide: f32,
+    /// Change in slide, in octaves per second per second.
+    pub delta_slide: f32,
+    /// Depth of the vibrato, as a fraction of the frequency.
+    pub vibrato_depth: f32,
+    /// In hertz.
+    pub vibrato_speed: f32,
+    /// Multiplies the frequency once `arpeggio_time` has passed. 1 disables the arpeggio.
+    pub arpeggio: f32,
+    pub arpeggio_time: Duration,
+    /// Duty of a square wave.
+    pub duty: f32,
+    /// Change in duty per second.
+    pub duty_sweep: f32,
+    pub attack: Duration,
+    pub sustain: Duration,
+    /// Extra volume at the start of the sustain, fading over the sustain.
+    pub punch: f32,
+    pub decay: Duration,
+    /// Cutoff of a low-pass filter, in hertz.
+    pub low_pass: Option<f32>,
+    /// Cutoff of a high-pass filter, in hertz.
+    pub high_pass: Option<f32>,
+    pub volume: f32,
+    pub sample_rate: u32,
+}
+
+impl Default for Sfx {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Square,
+            frequency: 440.0,
+            min_frequency: 20.0,
+            slide: 0.0,
+            delta_slide: 0.0,
+            vibrato_depth: 0.0,
+            vibrato_speed: 0.0,
+            arpeggio: 1.0,
+            arpeggio_time: Duration::ZERO,
+            duty: 0.5,
+            duty_sweep: 0.0,
+            attack: Duration::ZERO,
+            sustain: Duration::from_millis(100),
+            punch: 0.0,
+            decay: Duration::from_millis(200),
+            low_pass: None,
+            high_pass: None,
+            volume: 0.5,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+        }
+    }
+}
+
+fn seconds(rng: &mut impl Rng, min: f32, max: f32) -> Duration {
+    Duration::from_secs_f32(rng.gen_range(min..max))
+}
+
+impl Sfx {
+    /// Coin or item pickup: a short blip jumping up in pitch.
+    pub fn pickup(rng: &mut impl Rng) -> Self {
+        Self {
+            frequency: rng.gen_range(700.0..1400.0),
+            arpeggio: rng.gen_range(1.25..1.6),
+            arpeggio_time: seconds(rng, 0.04, 0.1),
+            sustain: seconds(rng, 0.03, 0.1),
+            punch: rng.gen_range(0.3..0.6),
+            decay: seconds(rng, 0.1, 0.3),
+            ..Default::default()
+        }
+    }
+
+    /// Shot: a falling tone.
+    pub fn laser(rng: &mut impl Rng) -> Self {
+        let waveform = [Waveform::Square, Waveform::Saw, Waveform::Sine][rng.gen_range(0..3)];
+        Self {
+            waveform,
+            frequency: rng.gen_range(800.0..2400.0),
+            min_frequency: rng.gen_range(100.0..300.0),
+            slide: rng.gen_range(-8.0..-3.0),
+            duty: rng.gen_range(0.2..0.5),
+            duty_sweep: rng.gen_range(0.0..1.0),
+            sustain: seconds(rng, 0.05, 0.15),
+            decay: seconds(rng, 0.1, 0.3),
+            ..Default::default()
+        }
+    }
+
+    pub fn explosion(rng: &mut impl Rng) -> Self {
+        Self {
+            waveform: Waveform::Noise,
+            frequency: rng.gen_range(60.0..400.0),
+            slide: rng.gen_range(-1.5..0.0),
+            sustain: seconds(rng, 0.1, 0.3),
+            punch: rng.gen_range(0.2..0.6),
+            decay: seconds(rng, 0.3, 0.6),
+            low_pass: rng.gen_bool(0.5).then(|| rng.gen_range(1000.0..4000.0)),
+            ..Default::default()
+        }
+    }
+
+    /// A rising tone with vibrato.
+    pub fn powerup(rng: &mut impl Rng) -> Self {
+        let waveform = [Waveform::Square, Waveform::Saw][rng.gen_range(0..2)];
+        Self {
+            waveform,
+            frequency: rng.gen_range(300.0..600.0),
+            slide: rng.gen_range(1.0..3.0),
+            vibrato_depth: rng.gen_range(0.0..0.15),
+            vibrato_speed: rng.gen_range(8.0..20.0),
+            sustain: seconds(rng, 0.1, 0.3),
+            decay: seconds(rng, 0.2, 0.4),
+            ..Default::default()
+        }
+    }
+
+    /// Hurt or hit: a short falling crunch.
+    pub fn hit(rng: &mut impl Rng) -> Self {
+        let waveform = [Waveform::Square, Waveform::Saw, Waveform::Noise][rng.gen_range(0..3)];
+        Self {
+            waveform,
+            frequency: rng.gen_range(200.0..800.0),
+            slide: rng.gen_range(-6.0..-3.0),
+            sustain: seconds(rng, 0.02, 0.08),
+            decay: seconds(rng, 0.1, 0.2),
+            high_pass: rng.gen_bool(0.5).then(|| rng.gen_range(100.0..600.0)),
+            ..Default::default()
+        }
+    }
+
+    pub fn jump(rng: &mut impl Rng) -> Self {
+        Self {
+            frequency: rng.gen_range(300.0..600.0),
+            slide: rng.gen_range(2.0..4.0),
+            duty: rng.gen_range(0.2..0.6),
+            sustain: seconds(rng, 0.1, 0.2),
+            decay: seconds(rng, 0.1, 0.2),
+            ..Default::default()
+        }
+    }
+
+    /// Menu selection.
+    pub fn blip(rng: &mut impl Rng) -> Self {
+        let waveform = [Waveform::Square, Waveform::Sine][rng.gen_range(0..2)];
+        Self {
+            waveform,
+            frequency: rng.gen_range(500.0..1500.0),
+            duty: rng.gen_range(0.2..0.5),
+            sustain: seconds(rng, 0.05, 0.1),
+            decay: seconds(rng, 0.01, 0.05),
+            high_pass: Some(100.0),
+            ..Default::default()
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.attack + self.sustain + self.decay
+    }
+
+    /// Synthesizes the sound as a mono source.
+    pub fn generate(&self) -> AudioSource {
+        let sample_rate = self.sample_rate.max(1);
+        let rate = sample_rate as f32;
+        let attack = frames(self.attack, sample_rate);
+        let sustain = frames(self.sustain, sample_rate);
+        let decay = frames(self.decay, sample_rate);
+        let arpeggio = frames(self.arpeggio_time, sample_rate);
+
+        let mut oscillator = Oscillator::new(self.waveform, self.frequency);
+        let mut frequency = self.frequency;
+        let mut slide = self.slide;
+        let mut samples = Vec::with_capacity(attack + sustain + decay);
+        for i in 0..attack + sustain + decay {
+            let envelope = if i < attack {
+                i as f32 / attack as f32
+            } else if i < attack + sustain {
+                1.0 + self.punch * (1.0 - (i - attack) as f32 / sustain as f32)
+            } else {
+                1.0 - (i - attack - sustain) as f32 / decay as f32
+            };
+
+            slide += self.delta_slide / rate;
+            frequency *= (slide / rate).exp2();
+            if i == arpeggio && self.arpeggio != 1.0 {
+                frequency *= self.arpeggio;
+            }
+            if frequency < self.min_frequency {
+                break;
+            }
+
+            let time = i as f32 / rate;
+            let vibrato = 1.0 + self.vibrato_depth * (TAU * self.vibrato_speed * time).sin();
+            oscillator.frequency = frequency * vibrato;
+            oscillator.duty = (self.duty + self.duty_sweep * time).clamp(0.05, 0.95);
+            samples.push(oscillator.sample(sample_rate) * envelope * self.volume);
+        }
+
+        let filters = [
+            self.low_pass.map(|cutoff| Biquad::low_pass(cutoff, 0.707)),
+            self.high_pass
+                .map(|cutoff| Biquad::high_pass(cutoff, 0.707)),
+        ];
+        for mut filter in filters.into_iter().flatten() {
+            filter.process(&mut samples, 1, sample_rate);
+        }
+
+        AudioSource {
+            samples: samples.into(),
+            channels: 1,
+            sample_rate,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Frequency estimated from the rising zero crossings.
+    fn frequency(source: &AudioSource) -> f32 {
+        let crossings = source
+            .samples
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count();
+
+        crossings as f32 / source.duration().as_secs_f32()
+    }
+
+    #[test]
+    fn oscillators() {
+        for waveform in [Waveform::Sine, Waveform::Square, Waveform::Saw] {
+            let source = Oscillator::new(waveform, 441.0)
+                .with_amplitude(0.5)
+                .render(Duration::from_secs(1), 44100);
+            assert_eq!(source.frames(), 44100);
+            assert!((frequency(&source) - 441.0).abs() <= 1.0, "{waveform:?}");
+            let peak = source
+                .samples
+                .iter()
+                .fold(0.0, |peak, s| f32::max(peak, s.abs()));
+            assert!((peak - 0.5).abs() < 0.01, "{waveform:?}");
+        }
+
+        let noise = Oscillator::new(Waveform::Noise, 1000.0).render(Duration::from_secs(1), 44100);
+        let mean = noise.samples.iter().sum::<f32>() / noise.samples.len() as f32;
+        assert!(mean.abs() < 0.1);
+        assert!(noise.samples.iter().all(|s| (-1.0..=1.0).contains(s)));
+    }
+
+    #[test]
+    fn envelope() {
+        let adsr = Adsr {
+            attack: Duration::from_millis(100),
+            decay: Duration::from_millis(100),
+            sustain: 0.5,
+            release: Duration::from_millis(200),
+        };
+        let ms = Duration::from_millis;
+        let held = ms(500);
+        assert_eq!(adsr.gain(ms(50), held), 0.5);
+        assert_eq!(adsr.gain(ms(150), held), 0.75);
+        assert_eq!(adsr.gain(ms(400), held), 0.5);
+        assert_eq!(adsr.gain(ms(600), held), 0.25);
+        assert_eq!(adsr.gain(ms(800), held), 0.0);
+        // Released during the attack
+        assert_eq!(adsr.gain(ms(150), ms(50)), 0.25);
+
+        let note = adsr.render(&mut Oscillator::new(Waveform::Square, 100.0), held, 1000);
+        assert_eq!(note.frames(), 700);
+    }
+
+    #[test]
+    fn sfx() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for sfx in [
+            Sfx::pickup(&mut rng),
+            Sfx::laser(&mut rng),
+            Sfx::explosion(&mut rng),
+            Sfx::powerup(&mut rng),
+            Sfx::hit(&mut rng),
+            Sfx::jump(&mut rng),
+            Sfx::blip(&mut rng),
+        ] {
+            let source = sfx.generate();
+            assert!(source.duration() <= sfx.duration(), "{sfx:?}");
+            assert!(!source.samples.is_empty(), "{sfx:?}");
+            assert!(source
+                .samples
+                .iter()
+                .all(|s| s.is_finite() && s.abs() < 1.0));
+        }
+
+        // The same parameters give the same sound
+        let pickup = Sfx::pickup(&mut StdRng::seed_from_u64(1));
+        assert_eq!(pickup.generate().samples, pickup.generate().samples);
+
+        // Slides below the minimum frequency end the sound early
+        let falling = Sfx {
+            slide: -10.0,
+            min_frequency: 220.0,
+            ..Default::default()
+        };
+        let source = falling.generate();
+        assert!((source.duration().as_secs_f32() - 0.1).abs() < 0.001);
+    }
+}
@@ -180,6 +180,235 @@ fn parse_d(input: TokenStream, path_to_cereal: proc_macro2::TokenStream) -> Toke
     .into()
 }
 
+#[proc_macro_derive(ToValue, attributes(skip))]
+pub fn to_value(input: TokenStream) -> TokenStream {
+    parse_to_value(input, quote! { winny::cereal })
+}
+
+#[proc_macro_derive(WinnyToValue, attributes(skip))]
+pub fn winny_to_value(input: TokenStream) -> TokenStream {
+    parse_to_value(input, quote! { ::cereal })
+}
+
+fn is_option(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// `SomeVariant` -> `some_variant`
+fn snake_case(ident: &syn::Ident) -> String {
+    let mut name = String::new();
+    for (i, c) in ident.to_string().chars().enumerate() {
+        if c.is_uppercase() {
+            if i != 0 {
+                name.push('_');
+            }
+            name.extend(c.to_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+
+    name
+}
+
+fn unit_variants(data: &syn::DataEnum) -> Vec<(&syn::Ident, String)> {
+    data.variants
+        .iter()
+        .map(|variant| match variant.fields {
+            Fields::Unit => (&variant.ident, snake_case(&variant.ident)),
+            _ => panic!("only unit enum variants are supported"),
+        })
+        .collect()
+}
+
+fn parse_to_value(input: TokenStream, path_to_cereal: proc_macro2::TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let generics = &input.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let to_value = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let fields = fields
+                    .named
+                    .iter()
+                    .filter(|field| !field.attrs.iter().any(|attr| attr.path().is_ident("skip")))
+                    .map(|field| {
+                        let field_name = &field.ident;
+                        let key = field_name.as_ref().unwrap().to_string();
+
+                        if is_option(&field.ty) {
+                            quote! {
+                                if let Some(value) = &self.#field_name {
+                                    entries.push((#key.to_owned(), value.to_value()));
+                                }
+                            }
+                        } else {
+                            quote! {
+                                entries.push((#key.to_owned(), self.#field_name.to_value()));
+                            }
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                quote! {
+                    let mut entries = Vec::new();
+                    #(#fields)*
+                    #path_to_cereal::Value::Table(entries)
+                }
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                quote! { self.0.to_value() }
+            }
+            Fields::Unnamed(fields) => {
+                let fields = (0..fields.unnamed.len())
+                    .map(|i| {
+                        let index = syn::Index::from(i);
+                        quote! { self.#index.to_value(), }
+                    })
+                    .collect::<Vec<_>>();
+
+                quote! { #path_to_cereal::Value::Array(vec![#(#fields)*]) }
+            }
+            Fields::Unit => quote! { #path_to_cereal::Value::Table(Vec::new()) },
+        },
+        Data::Enum(data) => {
+            let variants = unit_variants(data)
+                .into_iter()
+                .map(|(ident, key)| quote! { Self::#ident => #key, })
+                .collect::<Vec<_>>();
+
+            quote! {
+                #path_to_cereal::Value::String(match self { #(#variants)* }.to_owned())
+            }
+        }
+        _ => panic!("ToValue may only be derived for structs or enums"),
+    };
+
+    quote! {
+        impl #impl_generics #path_to_cereal::ToValue for #name #ty_generics #where_clause {
+            fn to_value(&self) -> #path_to_cereal::Value {
+                use #path_to_cereal::ToValue;
+                #to_value
+            }
+        }
+    }
+    .into()
+}
+
+#[proc_macro_derive(FromValue, attributes(skip))]
+pub fn from_value(input: TokenStream) -> TokenStream {
+    parse_from_value(input, quote! { winny::cereal })
+}
+
+#[proc_macro_derive(WinnyFromValue, attributes(skip))]
+pub fn winny_from_value(input: TokenStream) -> TokenStream {
+    parse_from_value(input, quote! { ::cereal })
+}
+
+fn parse_from_value(input: TokenStream, path_to_cereal: proc_macro2::TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let generics = &input.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let from_value = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let fields = fields
+                    .named
+                    .iter()
+                    .filter(|field| !field.attrs.iter().any(|attr| attr.path().is_ident("skip")))
+                    .map(|field| {
+                        let field_name = &field.ident;
+                        let ty = &field.ty;
+                        let key = field_name.as_ref().unwrap().to_string();
+
+                        quote! {
+                            if let Some(value) = value.get(#key) {
+                                result.#field_name = <#ty as #path_to_cereal::FromValue>::from_value(value)
+                                    .map_err(|e| e.within(#path_to_cereal::ValuePath::Key(#key.to_owned())))?;
+                            }
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                quote! {
+                    value.as_table()?;
+                    let mut result = <#name #ty_generics as Default>::default();
+                    #(#fields)*
+                    Ok(result)
+                }
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+                quote! { Ok(#name(<#ty as #path_to_cereal::FromValue>::from_value(value)?)) }
+            }
+            Fields::Unnamed(fields) => {
+                let len = fields.unnamed.len();
+                let fields = fields
+                    .unnamed
+                    .iter()
+                    .enumerate()
+                    .map(|(i, field)| {
+                        let ty = &field.ty;
+                        quote! {
+                            <#ty as #path_to_cereal::FromValue>::from_value(&values[#i])
+                                .map_err(|e| e.within(#path_to_cereal::ValuePath::Index(#i)))?,
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                quote! {
+                    let values = value.as_array()?;
+                    if values.len() != #len {
+                        return Err(#path_to_cereal::ValueError::new(
+                            #path_to_cereal::ValueErrorKind::OutOfRange,
+                        ));
+                    }
+                    Ok(#name(#(#fields)*))
+                }
+            }
+            Fields::Unit => quote! { Ok(#name) },
+        },
+        Data::Enum(data) => {
+            let variants = unit_variants(data)
+                .into_iter()
+                .map(|(ident, key)| quote! { #key => Ok(Self::#ident), })
+                .collect::<Vec<_>>();
+
+            quote! {
+                match value.as_str()? {
+                    #(#variants)*
+                    variant => Err(#path_to_cereal::ValueError::new(
+                        #path_to_cereal::ValueErrorKind::UnknownVariant(variant.to_owned()),
+                    )),
+                }
+            }
+        }
+        _ => panic!("FromValue may only be derived for structs or enums"),
+    };
+
+    quote! {
+        impl #impl_generics #path_to_cereal::FromValue for #name #ty_generics #where_clause {
+            fn from_value(value: &#path_to_cereal::Value) -> Result<Self, #path_to_cereal::ValueError> {
+                #from_value
+            }
+        }
+    }
+    .into()
+}
+
 // impl Serialize for SomeData {
 //     fn serialize(&self, serializer: &mut Serializer<'_>) {
 //         self.x.serialize(serializer);
@@ -1,7 +1,8 @@
 pub mod deserialize;
 pub mod serialize;
+pub mod value;
 
-pub use crate::{deserialize::*, serialize::*};
+pub use crate::{deserialize::*, serialize::*, value::*};
 extern crate cereal_macro;
 extern crate self as cereal;
 pub use cereal_macro::*;
@@ -0,0 +1,390 @@
+use std::{collections::HashMap, fmt::Display};
+
+/// Self-describing data. Used to read and write types from text formats, such as TOML.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Keys are kept in insertion order.
+    Table(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "bool",
+            Self::Integer(_) => "integer",
+            Self::Float(_) => "float",
+            Self::String(_) => "string",
+            Self::Array(_) => "array",
+            Self::Table(_) => "table",
+        }
+    }
+
+    /// Returns the value stored at `key` if this is a [`Value::Table`].
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Table(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Result<&[(String, Value)], ValueError> {
+        match self {
+            Self::Table(entries) => Ok(entries),
+            _ => Err(ValueError::invalid_type("table", self)),
+        }
+    }
+
+    pub fn as_array(&self) -> Result<&[Value], ValueError> {
+        match self {
+            Self::Array(values) => Ok(values),
+            _ => Err(ValueError::invalid_type("array", self)),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str, ValueError> {
+        match self {
+            Self::String(s) => Ok(s),
+            _ => Err(ValueError::invalid_type("string", self)),
+        }
+    }
+}
+
+/// Segment of the path to a [`Value`] from the root [`Value::Table`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValuePath {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueErrorKind {
+    InvalidType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    OutOfRange,
+    UnknownVariant(String),
+}
+
+/// Failed to convert a [`Value`] with [`FromValue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueError {
+    pub kind: ValueErrorKind,
+    /// Location of the offending [`Value`], starting from the root.
+    pub path: Vec<ValuePath>,
+}
+
+impl ValueError {
+    pub fn new(kind: ValueErrorKind) -> Self {
+        Self {
+            kind,
+            path: Vec::new(),
+        }
+    }
+
+    pub fn invalid_type(expected: &'static str, found: &Value) -> Self {
+        Self::new(ValueErrorKind::InvalidType {
+            expected,
+            found: found.type_name(),
+        })
+    }
+
+    /// Prepends a segment to the path. Called while unwinding out of nested values.
+    pub fn within(mut self, segment: ValuePath) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+
+    pub fn path_string(&self) -> String {
+        let mut path = String::new();
+        for segment in self.path.iter() {
+            match segment {
+                ValuePath::Key(key) => {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(key);
+                }
+                ValuePath::Index(index) => path.push_str(&format!("[{index}]")),
+            }
+        }
+
+        path
+    }
+}
+
+impl Display for ValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ValueErrorKind::InvalidType { expected, found } => {
+                write!(f, "expected {expected}, found {found}")?
+            }
+            ValueErrorKind::OutOfRange => write!(f, "value out of range")?,
+            ValueErrorKind::UnknownVariant(variant) => write!(f, "unknown variant `{variant}`")?,
+        }
+
+        if !self.path.is_empty() {
+            write!(f, " at `{}`", self.path_string())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+/// Converts a type into a [`Value`].
+pub trait ToValue {
+    fn to_value(&self) -> Value;
+}
+
+/// Builds a type from a [`Value`].
+///
+/// Derived for named structs by starting from [`Default::default`], so missing keys keep
+/// their default.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, ValueError>;
+}
+
+impl ToValue for Value {
+    fn to_value(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        Ok(value.clone())
+    }
+}
+
+impl ToValue for bool {
+    fn to_value(&self) -> Value {
+        Value::Bool(*self)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        match value {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(ValueError::invalid_type("bool", value)),
+        }
+    }
+}
+
+macro_rules! value_int {
+    ($($t:ty),*) => {
+        $(
+            impl ToValue for $t {
+                fn to_value(&self) -> Value {
+                    Value::Integer(*self as i64)
+                }
+            }
+
+            impl FromValue for $t {
+                fn from_value(value: &Value) -> Result<Self, ValueError> {
+                    match value {
+                        Value::Integer(i) => <$t>::try_from(*i)
+                            .map_err(|_| ValueError::new(ValueErrorKind::OutOfRange)),
+                        _ => Err(ValueError::invalid_type("integer", value)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+value_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+macro_rules! value_float {
+    ($($t:ty),*) => {
+        $(
+            impl ToValue for $t {
+                fn to_value(&self) -> Value {
+                    Value::Float(*self as f64)
+                }
+            }
+
+            impl FromValue for $t {
+                fn from_value(value: &Value) -> Result<Self, ValueError> {
+                    match value {
+                        Value::Float(f) => Ok(*f as $t),
+                        Value::Integer(i) => Ok(*i as $t),
+                        _ => Err(ValueError::invalid_type("float", value)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+value_float!(f32, f64);
+
+impl ToValue for String {
+    fn to_value(&self) -> Value {
+        Value::String(self.clone())
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        value.as_str().map(|s| s.to_owned())
+    }
+}
+
+impl ToValue for () {
+    fn to_value(&self) -> Value {
+        Value::Table(Vec::new())
+    }
+}
+
+impl FromValue for () {
+    fn from_value(_value: &Value) -> Result<Self, ValueError> {
+        Ok(())
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        T::from_value(value).map(Some)
+    }
+}
+
+impl<T: ToValue> ToValue for Box<T> {
+    fn to_value(&self) -> Value {
+        (**self).to_value()
+    }
+}
+
+impl<T: FromValue> FromValue for Box<T> {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        T::from_value(value).map(Box::new)
+    }
+}
+
+impl<T: ToValue> ToValue for Vec<T> {
+    fn to_value(&self) -> Value {
+        Value::Array(self.iter().map(|v| v.to_value()).collect())
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        value
+            .as_array()?
+            .iter()
+            .enumerate()
+            .map(|(i, v)| T::from_value(v).map_err(|e| e.within(ValuePath::Index(i))))
+            .collect()
+    }
+}
+
+impl<T: ToValue, const LEN: usize> ToValue for [T; LEN] {
+    fn to_value(&self) -> Value {
+        Value::Array(self.iter().map(|v| v.to_value()).collect())
+    }
+}
+
+impl<T: FromValue, const LEN: usize> FromValue for [T; LEN] {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        let values = Vec::<T>::from_value(value)?;
+        values
+            .try_into()
+            .map_err(|_| ValueError::new(ValueErrorKind::OutOfRange))
+    }
+}
+
+impl<T: ToValue> ToValue for HashMap<String, T> {
+    fn to_value(&self) -> Value {
+        let mut entries = self
+            .iter()
+            .map(|(k, v)| (k.clone(), v.to_value()))
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Value::Table(entries)
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        value
+            .as_table()?
+            .iter()
+            .map(|(k, v)| {
+                T::from_value(v)
+                    .map(|v| (k.clone(), v))
+                    .map_err(|e| e.within(ValuePath::Key(k.clone())))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cereal_macro::{WinnyFromValue, WinnyToValue};
+
+    #[derive(WinnyToValue, WinnyFromValue, Debug, Default, PartialEq)]
+    enum Filter {
+        #[default]
+        Nearest,
+        LinearMipmap,
+    }
+
+    #[derive(WinnyToValue, WinnyFromValue, Debug, Default, PartialEq)]
+    struct Settings {
+        filter: Filter,
+        scale: f32,
+        tags: Vec<String>,
+        label: Option<String>,
+    }
+
+    #[test]
+    fn round_trip() {
+        let settings = Settings {
+            filter: Filter::LinearMipmap,
+            scale: 2.0,
+            tags: vec!["ui".into()],
+            label: None,
+        };
+
+        let value = settings.to_value();
+        assert_eq!(
+            value.get("filter"),
+            Some(&Value::String("linear_mipmap".into()))
+        );
+        assert!(value.get("label").is_none());
+        assert_eq!(Settings::from_value(&value), Ok(settings));
+    }
+
+    #[test]
+    fn missing_keys_are_default() {
+        let value = Value::Table(vec![("scale".into(), Value::Integer(3))]);
+        let settings = Settings::from_value(&value).unwrap();
+
+        assert_eq!(settings.scale, 3.0);
+        assert_eq!(settings.filter, Filter::Nearest);
+    }
+
+    #[test]
+    fn error_path() {
+        let value = Value::Table(vec![(
+            "tags".into(),
+            Value::Array(vec![Value::String("a".into()), Value::Bool(true)]),
+        )]);
+        let error = Settings::from_value(&value).unwrap_err();
+
+        assert_eq!(error.path_string(), "tags[1]");
+        assert_eq!(
+            error.to_string(),
+            "expected string, found bool at `tags[1]`"
+        );
+    }
+}
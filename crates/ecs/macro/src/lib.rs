@@ -3,6 +3,7 @@ use proc_macro::TokenStream;
 use proc_macro2::Ident;
 use quote::{format_ident, quote, ToTokens};
 use std::hash::{DefaultHasher, Hash, Hasher};
+use syn::visit_mut::VisitMut;
 use syn::Data;
 use syn::{
     parse::{Parse, ParseStream},
@@ -152,6 +153,129 @@ fn parse_bundle(input: TokenStream, path_to_ecs: proc_macro2::TokenStream) -> To
     }
 }
 
+#[proc_macro_derive(SystemParam)]
+pub fn system_param_impl(input: TokenStream) -> TokenStream {
+    parse_system_param(input, quote! { winny::ecs })
+}
+
+#[proc_macro_derive(WinnySystemParam)]
+pub fn winny_system_param_impl(input: TokenStream) -> TokenStream {
+    parse_system_param(input, quote! { ::ecs })
+}
+
+#[proc_macro_derive(InternalSystemParam)]
+pub fn internal_system_param_impl(input: TokenStream) -> TokenStream {
+    parse_system_param(input, quote! { crate })
+}
+
+// Replaces the lifetimes of a `SystemParam` struct with `'static` so that the field types can be
+// named in `SystemParam::State`.
+struct StaticLifetimes<'a>(&'a [syn::Lifetime]);
+
+impl VisitMut for StaticLifetimes<'_> {
+    fn visit_lifetime_mut(&mut self, lifetime: &mut syn::Lifetime) {
+        if self.0.contains(lifetime) {
+            *lifetime = syn::Lifetime::new("'static", lifetime.span());
+        }
+    }
+}
+
+fn parse_system_param(input: TokenStream, path_to_ecs: proc_macro2::TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let syn::Data::Struct(data) = &input.data else {
+        panic!("SystemParam must be a Struct: {}", name.to_string());
+    };
+
+    let lifetimes = input
+        .generics
+        .lifetimes()
+        .map(|l| l.lifetime.clone())
+        .collect::<Vec<_>>();
+    if lifetimes.len() > 2 {
+        panic!(
+            "SystemParam can have at most a 'world and 'state lifetime: {}",
+            name.to_string()
+        );
+    }
+
+    let mut members = Vec::new();
+    let mut tys = Vec::new();
+    for (i, field) in data.fields.iter().enumerate() {
+        members.push(match &field.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(i.into()),
+        });
+
+        let mut ty = field.ty.clone();
+        StaticLifetimes(&lifetimes).visit_type_mut(&mut ty);
+        tys.push(ty);
+    }
+    let indexes = (0..tys.len()).map(syn::Index::from).collect::<Vec<_>>();
+
+    let mut lifetime_args = [quote! { '__w }, quote! { '__s }].into_iter();
+    let item_args = input
+        .generics
+        .params
+        .iter()
+        .map(|param| match param {
+            syn::GenericParam::Lifetime(_) => lifetime_args.next().unwrap(),
+            syn::GenericParam::Type(t) => t.ident.to_token_stream(),
+            syn::GenericParam::Const(c) => c.ident.to_token_stream(),
+        })
+        .collect::<Vec<_>>();
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #path_to_ecs::systems::system_param::SystemParam for #name #ty_generics #where_clause {
+            type State = (#(<#tys as #path_to_ecs::systems::system_param::SystemParam>::State,)*);
+            type Item<'__w, '__s> = #name<#(#item_args),*>;
+
+            fn access(world: &mut #path_to_ecs::world::World) -> #path_to_ecs::systems::access::SystemAccess {
+                let mut access = #path_to_ecs::systems::access::SystemAccess::default();
+                #(
+                    access = access.with(<#tys as #path_to_ecs::systems::system_param::SystemParam>::access(world));
+                )*
+                access
+            }
+
+            fn init_state(world: &mut #path_to_ecs::world::World) -> Self::State {
+                (#(<#tys as #path_to_ecs::systems::system_param::SystemParam>::init_state(world),)*)
+            }
+
+            fn new_archetype(archetype: &#path_to_ecs::storage::Archetype, state: &mut Self::State) {
+                #(
+                    <#tys as #path_to_ecs::systems::system_param::SystemParam>::new_archetype(archetype, &mut state.#indexes);
+                )*
+            }
+
+            fn to_param<'__w, '__s>(
+                state: &'__s mut Self::State,
+                world: #path_to_ecs::world::unsafe_world::UnsafeWorldCell<'__w>,
+            ) -> Self::Item<'__w, '__s> {
+                #name {
+                    #(
+                        #members: <#tys as #path_to_ecs::systems::system_param::SystemParam>::to_param(&mut state.#indexes, world),
+                    )*
+                }
+            }
+
+            fn apply_deffered(
+                world: &mut #path_to_ecs::world::World,
+                state: &mut Self::State,
+                one_shot_systems: &mut #path_to_ecs::schedule::OneShotSystems,
+            ) {
+                #(
+                    <#tys as #path_to_ecs::systems::system_param::SystemParam>::apply_deffered(world, &mut state.#indexes, one_shot_systems);
+                )*
+            }
+        }
+    }
+    .into()
+}
+
 #[proc_macro_derive(ScheduleLabel)]
 pub fn schedule_label_impl(input: TokenStream) -> TokenStream {
     parse_schedule_label(input, quote! { winny::ecs })
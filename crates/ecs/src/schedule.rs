@@ -36,6 +36,8 @@ impl Scheduler {
             let _span = util::tracing::trace_span!("schedule", name = ?schedule).entered();
             executer.run(world);
             executer.apply_deffered(world, &mut self.one_shot_systems);
+            self.one_shot_systems
+                .append(world.registered_systems.take_pending());
         }
     }
 }
@@ -128,8 +130,160 @@ impl OneShotSystems {
     }
 
     pub fn append(&mut self, other: OneShotSystems) {
+        self.num_systems += other.num_systems;
         for set in other.systems.into_iter() {
-            self.systems.push(set);
+            self.systems.insert_in_first_empty(set);
+        }
+    }
+}
+
+/// Identifies a system registered with [`World::register_system`].
+///
+/// Ids are generational: once a system is unregistered, its id will not run the system that
+/// reuses its slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SystemId {
+    index: usize,
+    generation: u32,
+}
+
+impl SystemId {
+    pub fn id(&self) -> usize {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl SparseArrayIndex for SystemId {
+    fn index(&self) -> usize {
+        self.id()
+    }
+}
+
+#[derive(Debug)]
+struct RegisteredSystem {
+    // `None` while the system is running
+    system: Option<StoredSystem>,
+    archetypes_len: usize,
+    generation: u32,
+}
+
+/// Systems registered with [`World::register_system`]. Their state is initialized once and
+/// cached between runs.
+#[derive(Debug, Default)]
+pub struct RegisteredSystems {
+    systems: SparseArray<SystemId, RegisteredSystem>,
+    // Generation of the next system stored in each slot
+    generations: Vec<u32>,
+    // One shot systems queued by registered systems run outside of a schedule
+    pending: OneShotSystems,
+}
+
+impl RegisteredSystems {
+    pub fn len(&self) -> usize {
+        self.systems.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, id: SystemId) -> bool {
+        self.systems
+            .get(&id)
+            .is_some_and(|s| s.generation == id.generation)
+    }
+
+    fn get_mut(&mut self, id: SystemId) -> Option<&mut RegisteredSystem> {
+        self.systems
+            .get_mut(&id)
+            .filter(|s| s.generation == id.generation)
+    }
+
+    pub(crate) fn insert(&mut self, system: StoredSystem, archetypes_len: usize) -> SystemId {
+        let index = self.systems.insert_in_first_empty(RegisteredSystem {
+            system: Some(system),
+            archetypes_len,
+            generation: 0,
+        });
+        if index >= self.generations.len() {
+            self.generations.resize(index + 1, 0);
+        }
+
+        let generation = self.generations[index];
+        let id = SystemId { index, generation };
+        self.systems.get_mut(&id).expect("just inserted").generation = generation;
+
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: SystemId) -> Option<StoredSystem> {
+        if self.contains(id) {
+            self.generations[id.index()] += 1;
+            self.systems.take(id.index()).and_then(|s| s.system)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn take_pending(&mut self) -> OneShotSystems {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+impl World {
+    /// Registers a system to be run on demand with [`World::run_system`] or
+    /// [`Commands::run_system`].
+    ///
+    /// Panics if the system's access is invalid.
+    pub fn register_system<M>(&mut self, system: impl IntoSystem<M>) -> SystemId {
+        let mut system = system.into_system();
+        system.access(self).validate_or_panic();
+        system.init_state(self);
+
+        let archetypes_len = self.archetypes.len();
+        self.registered_systems
+            .insert(Box::new(system), archetypes_len)
+    }
+
+    /// Removes a registered system, returning it if it was registered and not currently running.
+    pub fn unregister_system(&mut self, id: SystemId) -> Option<StoredSystem> {
+        self.registered_systems.remove(id)
+    }
+
+    /// Runs a registered system and applies its deferred commands.
+    pub fn run_system(&mut self, id: SystemId) {
+        let mut one_shot_systems = self.registered_systems.take_pending();
+        self.run_system_with(id, &mut one_shot_systems);
+        self.registered_systems.pending.append(one_shot_systems);
+    }
+
+    pub(crate) fn run_system_with(&mut self, id: SystemId, one_shot_systems: &mut OneShotSystems) {
+        let Some(registered) = self.registered_systems.get_mut(id) else {
+            util::tracing::error!("run_system: {id:?} is not registered, ignoring");
+            return;
+        };
+        let Some(mut system) = registered.system.take() else {
+            util::tracing::error!("run_system: {id:?} is already running, ignoring");
+            return;
+        };
+
+        let archetypes_len = self.archetypes.len();
+        for arch_id in registered.archetypes_len..archetypes_len {
+            let arch = self.archetypes.get(ArchId::new(arch_id)).expect("valid id");
+            system.new_archetype(arch);
+        }
+
+        system.run_unsafe(unsafe { self.as_unsafe_world() });
+        system.apply_deffered(self, one_shot_systems);
+
+        // The system may have unregistered itself
+        if let Some(registered) = self.registered_systems.get_mut(id) {
+            registered.system = Some(system);
+            registered.archetypes_len = archetypes_len;
         }
     }
 }
@@ -187,6 +341,91 @@ mod tests {
         Render,
     }
 
+    fn add_weight(mut weight: ResMut<Weight>, mut runs: Local<u32>) {
+        *runs += 1;
+        weight.0 += *runs;
+    }
+
+    fn add_size(mut weight: ResMut<Weight>) {
+        weight.0 += 100;
+    }
+
+    #[derive(Debug, InternalResource)]
+    struct Registered(SystemId);
+
+    fn run_registered(mut commands: Commands, registered: Res<Registered>) {
+        commands.run_system(registered.0);
+    }
+
+    #[test]
+    fn register_and_run_system() {
+        let mut world = World::default();
+        world.insert_resource(Weight(0));
+
+        let id = world.register_system(add_weight);
+        assert!(world.registered_systems.contains(id));
+
+        // Local state is cached between runs
+        world.run_system(id);
+        world.run_system(id);
+        assert_eq!(world.resource::<Weight>().0, 1 + 2);
+    }
+
+    #[test]
+    fn run_registered_system_with_commands() {
+        let mut world = World::default();
+        world.insert_resource(Weight(0));
+        let id = world.register_system(add_weight);
+        world.insert_resource(Registered(id));
+
+        let mut scheduler = Scheduler::default();
+        scheduler.add_systems(TestLabel::Render, run_registered);
+        scheduler.init_schedule(&mut world);
+        scheduler.run_schedule(&mut world, TestLabel::Render);
+        scheduler.run_schedule(&mut world, TestLabel::Render);
+
+        assert_eq!(world.resource::<Weight>().0, 1 + 2);
+    }
+
+    #[test]
+    fn unregister_system() {
+        let mut world = World::default();
+        world.insert_resource(Weight(0));
+
+        let id = world.register_system(add_weight);
+        assert!(world.unregister_system(id).is_some());
+        assert!(!world.registered_systems.contains(id));
+        assert!(world.unregister_system(id).is_none());
+        world.run_system(id);
+        assert_eq!(world.resource::<Weight>().0, 0);
+
+        // The new system reuses the slot, but the stale id must not run it
+        let new_id = world.register_system(add_size);
+        assert_eq!(new_id.id(), id.id());
+        assert_ne!(new_id, id);
+        world.run_system(id);
+        assert_eq!(world.resource::<Weight>().0, 0);
+        world.run_system(new_id);
+        assert_eq!(world.resource::<Weight>().0, 100);
+    }
+
+    #[test]
+    fn append_one_shot_systems() {
+        let mut world = World::default();
+        world.insert_resource(Weight(0));
+
+        let mut systems = OneShotSystems::default();
+        let mut other = OneShotSystems::default();
+        other.insert::<(), ()>(add_size.into_system(), should_run.into_system());
+        other.insert::<(), ()>(add_size.into_system(), should_run.into_system());
+        systems.append(other);
+
+        assert_eq!(systems.len(), 2);
+        assert_eq!(systems.iter_mut().count(), 2);
+        systems.remove_indexes([0, 1].into_iter());
+        assert!(systems.is_empty());
+    }
+
     // #[test]
     // fn schedule_labels() {
     //     let render = Schedule::Render;
@@ -12,6 +12,8 @@ pub mod access;
 pub mod sets;
 pub mod system_param;
 
+pub use system_param::Local;
+
 pub type StoredSystem = Box<dyn System<Out = ()>>;
 pub type StoredCondition = Box<dyn System<Out = bool>>;
 
@@ -1,3 +1,5 @@
+use std::ops::{Deref, DerefMut};
+
 use crate::{
     access::{AccessType, ResourceAccess, SystemAccess},
     Archetype, CommandQueue, Commands, Event, EventReader, EventWriter, Events, Filter,
@@ -317,6 +319,69 @@ impl<T: 'static + QueryData, F: 'static + Filter> SystemParam for Query<'_, '_,
     }
 }
 
+/// State owned by a single system, initialized with [`Default`] the first time the system's
+/// state is created.
+#[derive(Debug)]
+pub struct Local<'s, T> {
+    value: &'s mut T,
+}
+
+impl<T> Deref for Local<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<T> DerefMut for Local<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Default + Send + Sync + 'static> SystemParam for Local<'_, T> {
+    type State = T;
+    type Item<'world, 'state> = Local<'state, T>;
+
+    fn access(_world: &mut World) -> SystemAccess {
+        SystemAccess::default()
+    }
+
+    fn init_state(_world: &mut World) -> Self::State {
+        T::default()
+    }
+
+    fn to_param<'w, 's>(
+        state: &'s mut Self::State,
+        _world: UnsafeWorldCell<'w>,
+    ) -> Self::Item<'w, 's> {
+        Local { value: state }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl<T: Default + 'static> SystemParam for Local<'_, T> {
+    type State = T;
+    type Item<'world, 'state> = Local<'state, T>;
+
+    fn access(_world: &mut World) -> SystemAccess {
+        SystemAccess::default()
+    }
+
+    fn init_state(_world: &mut World) -> Self::State {
+        T::default()
+    }
+
+    fn to_param<'w, 's>(
+        state: &'s mut Self::State,
+        _world: UnsafeWorldCell<'w>,
+    ) -> Self::Item<'w, 's> {
+        Local { value: state }
+    }
+}
+
 macro_rules! expr {
     ($x:expr) => {
         $x
@@ -393,3 +458,67 @@ impl_system_param!(
         Q, 15
     )
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs_macro::{InternalComponent, InternalResource, InternalSystemParam};
+
+    #[derive(Debug, InternalComponent)]
+    struct Health(u32);
+
+    #[derive(Debug, InternalComponent)]
+    struct Armor;
+
+    #[derive(Debug, Default, InternalResource)]
+    struct Total(u32);
+
+    fn count_runs(mut total: ResMut<Total>, mut runs: Local<u32>) {
+        *runs += 1;
+        total.0 += *runs;
+    }
+
+    #[test]
+    fn local_state_persists() {
+        let mut world = World::default();
+        world.insert_resource(Total::default());
+
+        // Each system has its own state
+        let first = world.register_system(count_runs);
+        let second = world.register_system(count_runs);
+        world.run_system(first);
+        world.run_system(first);
+        world.run_system(second);
+        assert_eq!(world.resource::<Total>().0, 1 + 2 + 1);
+    }
+
+    #[derive(InternalSystemParam)]
+    struct Counter<'w, 's> {
+        healths: Query<'w, 's, Health>,
+        total: ResMut<'w, Total>,
+        runs: Local<'s, u32>,
+    }
+
+    fn count_health(mut counter: Counter) {
+        *counter.runs += 1;
+        counter.total.0 =
+            counter.healths.iter().map(|health| health.0).sum::<u32>() * *counter.runs;
+    }
+
+    #[test]
+    fn derived_param() {
+        let mut world = World::default();
+        world.insert_resource(Total::default());
+        world.spawn(Health(1));
+        world.spawn(Health(2));
+
+        let id = world.register_system(count_health);
+        world.run_system(id);
+        assert_eq!(world.resource::<Total>().0, 3);
+
+        // The query of the derived param sees new archetypes
+        world.spawn((Health(4), Armor));
+        world.run_system(id);
+        assert_eq!(world.resource::<Total>().0, 7 * 2);
+    }
+}
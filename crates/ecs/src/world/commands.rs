@@ -1,4 +1,4 @@
-use crate::{IntoCondition, IntoSystem, OneShotSystems, System};
+use crate::{IntoCondition, IntoSystem, OneShotSystems, System, SystemId};
 
 use super::*;
 
@@ -39,6 +39,12 @@ impl<'w, 's> Commands<'w, 's> {
         self
     }
 
+    /// Runs a system registered with [`World::register_system`] when commands are applied.
+    pub fn run_system(&mut self, id: SystemId) -> &mut Self {
+        self.push(run_system(id));
+        self
+    }
+
     fn push<C: Command>(&mut self, command: C) {
         self.queue.push(|world, one_shot_systems| {
             command.apply(world, one_shot_systems);
@@ -136,6 +142,12 @@ fn run_system_when<S, C>(
     }
 }
 
+fn run_system(id: SystemId) -> impl Command {
+    move |world: &mut World, one_shot_systems: &mut OneShotSystems| {
+        world.run_system_with(id, one_shot_systems);
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 trait EntityCommand: 'static + Send + Sync {
     fn apply(self, entity: Entity, world: &mut World);
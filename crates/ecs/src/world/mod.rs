@@ -5,7 +5,7 @@ pub mod unsafe_world;
 pub use commands::*;
 pub use entity::*;
 
-use crate::{Event, Events, Res, ResMut, Resource, Resources};
+use crate::{Event, Events, RegisteredSystems, Res, ResMut, Resource, Resources};
 
 use crate::storage::*;
 
@@ -19,6 +19,7 @@ pub struct World {
     pub components: Components,
     pub entities: Entities,
     pub bundles: Bundles,
+    pub registered_systems: RegisteredSystems,
 }
 
 impl World {
@@ -3,7 +3,10 @@ use ecs::{
     egui_widget::EguiRegistery, Components, Entities, Tables, UnsafeWorldCell, WinnyResource, *,
 };
 use egui_dock::{DockArea, DockState, NodeIndex, Style};
-use gfx::{camera::Camera, gui::EguiRenderer};
+use gfx::{
+    camera::{Camera, RenderTarget},
+    gui::EguiRenderer,
+};
 use std::any::TypeId;
 
 #[derive(Debug)]
@@ -20,11 +23,7 @@ impl Plugin for EditorPlugin {
     }
 }
 
-fn update_camera_viewport(mut camera: Query<Mut<Camera>>, ui: Res<Editor>) {
-    let Ok(camera) = camera.get_single_mut() else {
-        return;
-    };
-
+fn update_camera_viewport(mut cameras: Query<Mut<Camera>>, ui: Res<Editor>) {
     if ui.viewport_rect == egui::Rect::ZERO {
         return;
     }
@@ -34,7 +33,12 @@ fn update_camera_viewport(mut camera: Query<Mut<Camera>>, ui: Res<Editor>) {
         max: [ui.viewport_rect.max.x, ui.viewport_rect.max.y].into(),
     };
 
-    camera.viewport = Some(viewport);
+    for camera in cameras
+        .iter_mut()
+        .filter(|camera| camera.target == RenderTarget::Window)
+    {
+        camera.viewport = Some(viewport);
+    }
 }
 
 fn render(world: &mut World) {
@@ -1,34 +1,98 @@
-use crate::render_pipeline::bind_group::{self, AsBindGroup};
+use crate::render::{ClearColorConfig, RenderLayers, RenderView};
+use crate::render_pipeline::bind_group::{self, AsBindGroup, AssetBindGroups, BindGroup};
+use crate::render_pipeline::render_assets::{RenderAsset, RenderAssets};
+use crate::texture::{Image, Texture};
 use crate::{render_pipeline::buffer::AsGpuBuffer, transform::Transform};
 use app::plugins::Plugin;
+use app::prelude::*;
 use app::render_util::{Dimensions, RenderContext};
 use app::window::ViewPort;
 use app::window::Window;
-use ecs::{AsEgui, WinnyAsEgui, WinnyBundle, WinnyComponent, WinnyResource};
+use asset::{AssetId, Assets, Handle};
+use ecs::{
+    AsEgui, Entity, Mut, Query, Res, ResMut, WinnyAsEgui, WinnyBundle, WinnyComponent,
+    WinnyResource,
+};
 use math::matrix::Matrix4x4f;
+use math::vector::Vec2f;
 
 #[derive(Debug)]
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&mut self, app: &mut app::prelude::App) {
-        app.egui_component::<Camera>();
+        app.egui_component::<Camera>()
+            .egui_component::<OrthographicProjection>()
+            .register_resource::<RenderCameras>()
+            .insert_resource(RenderCameras::default())
+            .add_systems(Schedule::PreUpdate, update_projections)
+            .add_systems(AppSchedule::PrepareRender, prepare_cameras);
     }
 }
 
 #[derive(WinnyBundle, Default)]
 pub struct Camera2dBundle {
     camera: Camera,
+    projection: OrthographicProjection,
     transform: Transform,
 }
 
-/// Defines what [`ViewPort`] the world should be drawn to.
+/// Defines what [`ViewPort`] the world should be drawn to, and which [`RenderTarget`] it is drawn
+/// onto. The world is projected with the camera's [`OrthographicProjection`], centered on the
+/// translation of its [`Transform`].
 ///
-/// At the moment, only _one_ camera may exist at a time.
+/// Any number of cameras may exist. Cameras drawing to an [`Image`] are drawn before the cameras
+/// drawing to the window, each from lowest to highest `order`, so an [`Image`] drawn by a camera
+/// should be displayed by cameras drawing to the window or with a higher `order`.
+/// Add [`RenderLayers`] to a camera to choose which entities it draws, e.g. a UI camera overlaying
+/// the world camera with [`ClearColorConfig::None`].
 #[derive(WinnyComponent, WinnyAsEgui, Default)]
 pub struct Camera {
-    // Window viewport if None.
+    /// Area of the target drawn to in pixels, the entire target if None.
     pub viewport: Option<ViewPort>,
+    pub target: RenderTarget,
+    pub order: isize,
+    pub clear_color: ClearColorConfig,
+}
+
+/// Texture a [`Camera`] draws to.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum RenderTarget {
+    /// The swapchain [`RenderView`].
+    #[default]
+    Window,
+    /// An [`Image`] created with [`Image::render_target`]. Sprites textured with this image are
+    /// not drawn by the camera itself.
+    Image(Handle<Image>),
+}
+
+impl ecs::egui_widget::Widget for RenderTarget {
+    fn display(&mut self, ui: &mut ecs::egui::Ui) {
+        match self {
+            Self::Window => ui.label("Window"),
+            Self::Image(handle) => ui.label(format!("Image({:?})", handle.id())),
+        };
+    }
+}
+
+impl RenderTarget {
+    /// Viewport covering the entire target. `None` if the [`Image`] is not loaded or is not a
+    /// render target.
+    pub fn viewport(&self, window: &Window, images: &Assets<Image>) -> Option<ViewPort> {
+        match self {
+            Self::Window => Some(window.viewport),
+            Self::Image(handle) => images
+                .get(handle)
+                .filter(|image| image.is_render_target())
+                .map(|image| {
+                    let dimensions = image.dimensions();
+                    ViewPort::new(
+                        Vec2f::zero(),
+                        Vec2f::new(dimensions.width() as f32, dimensions.height() as f32),
+                    )
+                }),
+        }
+    }
 }
 
 impl Camera {
@@ -38,6 +102,169 @@ impl Camera {
             None => context.window_viewport(),
         }
     }
+
+    /// Converts `position`, in pixels of the [`RenderTarget`], to world space. Use it with the
+    /// cursor position to find what is under the mouse.
+    pub fn viewport_to_world(
+        &self,
+        transform: &Transform,
+        projection: &OrthographicProjection,
+        position: Vec2f,
+    ) -> Vec2f {
+        let offset = self.viewport.map(|v| v.min).unwrap_or_else(Vec2f::zero);
+        let normalized = Vec2f::new(
+            (position.x - offset.x) / projection.viewport.x - 0.5,
+            (position.y - offset.y) / projection.viewport.y - 0.5,
+        );
+
+        // World space points down, like the viewport
+        let area = projection.area();
+        Vec2f::new(
+            transform.translation.x + normalized.x * area.width(),
+            transform.translation.y + normalized.y * area.height(),
+        )
+    }
+
+    /// Converts `position` in world space to pixels of the [`RenderTarget`].
+    pub fn world_to_viewport(
+        &self,
+        transform: &Transform,
+        projection: &OrthographicProjection,
+        position: Vec2f,
+    ) -> Vec2f {
+        let area = projection.area();
+        let normalized = Vec2f::new(
+            (position.x - transform.translation.x) / area.width() + 0.5,
+            (position.y - transform.translation.y) / area.height() + 0.5,
+        );
+
+        let offset = self.viewport.map(|v| v.min).unwrap_or_else(Vec2f::zero);
+        Vec2f::new(
+            offset.x + normalized.x * projection.viewport.x,
+            offset.y + normalized.y * projection.viewport.y,
+        )
+    }
+
+    /// Size of the viewport in pixels. `None` if the [`RenderTarget`] is not available.
+    fn viewport_size(&self, window: &Window, images: &Assets<Image>) -> Option<Vec2f> {
+        // Checked even with a viewport, so images which are loading are not drawn to
+        let target = self.target.viewport(window, images)?;
+        let viewport = self.viewport.unwrap_or(target);
+
+        Some(Vec2f::new(viewport.width(), viewport.height()))
+    }
+}
+
+/// How an [`OrthographicProjection`] fits the world into the viewport of a [`Camera`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ScalingMode {
+    /// One world unit per pixel.
+    #[default]
+    WindowSize,
+    /// Shows this many world units vertically. The width follows the aspect ratio.
+    FixedVertical(f32),
+    /// Shows this many world units horizontally. The height follows the aspect ratio.
+    FixedHorizontal(f32),
+    /// Keeps the aspect ratio while showing at least `min_width` by `min_height` world units.
+    AutoMin { min_width: f32, min_height: f32 },
+}
+
+impl ecs::egui_widget::Widget for ScalingMode {
+    fn display(&mut self, ui: &mut ecs::egui::Ui) {
+        match self {
+            Self::WindowSize => {
+                ui.label("WindowSize");
+            }
+            Self::FixedVertical(height) => height.display(ui),
+            Self::FixedHorizontal(width) => width.display(ui),
+            Self::AutoMin {
+                min_width,
+                min_height,
+            } => {
+                min_width.display(ui);
+                min_height.display(ui);
+            }
+        }
+    }
+}
+
+impl ScalingMode {
+    /// World units shown in a `viewport` of this many pixels.
+    pub fn size(&self, viewport: Vec2f) -> Vec2f {
+        let aspect_ratio = viewport.x / viewport.y;
+        match *self {
+            Self::WindowSize => viewport,
+            Self::FixedVertical(height) => Vec2f::new(height * aspect_ratio, height),
+            Self::FixedHorizontal(width) => Vec2f::new(width, width / aspect_ratio),
+            Self::AutoMin {
+                min_width,
+                min_height,
+            } => {
+                if min_width / min_height > aspect_ratio {
+                    Vec2f::new(min_width, min_width / aspect_ratio)
+                } else {
+                    Vec2f::new(min_height * aspect_ratio, min_height)
+                }
+            }
+        }
+    }
+}
+
+/// Projects the world onto the viewport of a [`Camera`]. The rotation and scale of the camera's
+/// [`Transform`] are ignored, zoom with `scale` instead.
+#[derive(WinnyComponent, WinnyAsEgui, Debug, Clone, Copy)]
+pub struct OrthographicProjection {
+    /// Multiplies the world units shown by the [`ScalingMode`]. Values above 1 zoom out.
+    pub scale: f32,
+    pub scaling_mode: ScalingMode,
+    /// Entities with a translation `z` outside of `near..=far` are clipped. Defaults to -1e6,
+    /// wide enough for any `z` used to layer sprites.
+    pub near: f32,
+    /// Defaults to 1e6.
+    pub far: f32,
+    /// Size of the camera's viewport in pixels, updated every frame.
+    viewport: Vec2f,
+}
+
+impl Default for OrthographicProjection {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            scaling_mode: ScalingMode::default(),
+            near: -1e6,
+            far: 1e6,
+            viewport: Vec2f::one(),
+        }
+    }
+}
+
+impl OrthographicProjection {
+    /// Area of the world shown by the camera, relative to its translation.
+    pub fn area(&self) -> ViewPort {
+        let size = self.size(self.viewport);
+        ViewPort::new(
+            Vec2f::new(-size.x / 2.0, -size.y / 2.0),
+            Vec2f::new(size.x / 2.0, size.y / 2.0),
+        )
+    }
+
+    /// World units shown in a `viewport` of this many pixels.
+    fn size(&self, viewport: Vec2f) -> Vec2f {
+        let size = self.scaling_mode.size(viewport);
+        Vec2f::new(size.x * self.scale, size.y * self.scale)
+    }
+}
+
+fn update_projections(
+    mut cameras: Query<(Camera, Mut<OrthographicProjection>)>,
+    window: Res<Window>,
+    images: Res<Assets<Image>>,
+) {
+    for (camera, projection) in cameras.iter_mut() {
+        if let Some(viewport) = camera.viewport_size(&window, &images) {
+            projection.viewport = viewport;
+        }
+    }
 }
 
 impl AsBindGroup for &[CameraUniform] {
@@ -55,27 +282,296 @@ pub(crate) struct CameraUniform {
 }
 
 impl CameraUniform {
-    pub fn from_camera(camera: &Camera, transform: &Transform, window: &Window) -> Self {
-        let viewport = camera.viewport.unwrap_or_else(|| window.viewport);
-        let mut transform = transform.as_matrix();
-
-        let viewport_center = viewport.center();
-        let screen_center = window.viewport.center();
-        let offset = screen_center - viewport_center;
-        transform.m[0][3] += offset.x;
-        transform.m[1][3] += offset.y;
+    /// `viewport` is the size of the camera's viewport in pixels.
+    pub fn from_projection(
+        transform: &Transform,
+        projection: &OrthographicProjection,
+        viewport: Vec2f,
+        window: &Window,
+    ) -> Self {
+        // Vertices are scaled to the window in pixels before the projection is applied, and
+        // translations are converted to clip space on the GPU, with the y axis pointing down.
+        let window_dimensions = Dimensions::new(window.viewport.width(), window.viewport.height());
+        let size = projection.size(viewport);
+        let scale = Vec2f::new(
+            window_dimensions.width() / size.x,
+            window_dimensions.height() / size.y,
+        );
+        let depth = projection.far - projection.near;
 
-        transform.m[0][3] *= -viewport.width() / window.viewport.width();
-        transform.m[1][3] *= -viewport.height() / window.viewport.height();
+        #[rustfmt::skip]
+        let transform = Matrix4x4f {
+            m: [
+                [scale.x, 0.,      0.,          -transform.translation.x * scale.x],
+                [0.,      scale.y, 0.,          -transform.translation.y * scale.y],
+                [0.,      0.,      1. / depth,  -projection.near / depth          ],
+                [0.,      0.,      0.,          1.                                ],
+            ],
+        };
 
         Self {
             transform,
-            viewport_dimensions: Dimensions::new(viewport.width(), viewport.height()),
-            window_dimensions: Dimensions::new(window.viewport.width(), window.viewport.height()),
+            viewport_dimensions: window_dimensions,
+            window_dimensions,
         }
     }
 }
 
+/// [`Camera`]s prepared for the render schedules, sorted by [`Camera::order`].
+#[derive(WinnyResource, Default)]
+pub struct RenderCameras(Vec<RenderCamera>);
+
+impl RenderCameras {
+    pub fn iter(&self) -> impl Iterator<Item = &RenderCamera> {
+        self.0.iter()
+    }
+}
+
+/// Uniform and target of a [`Camera`].
+pub struct RenderCamera {
+    pub entity: Entity,
+    /// Position in [`RenderCameras`].
+    index: usize,
+    pub target: RenderTarget,
+    pub order: isize,
+    pub clear_color: ClearColorConfig,
+    pub layers: RenderLayers,
+    /// Area of the target drawn to in pixels, the entire target if None.
+    pub viewport: Option<ViewPort>,
+    binding: BindGroup,
+    /// `None` when drawing to the [`RenderView`].
+    view: Option<wgpu::TextureView>,
+}
+
+impl RenderCamera {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn binding(&self) -> &wgpu::BindGroup {
+        self.binding.binding()
+    }
+
+    /// Whether an entity with `layers` is drawn by this camera.
+    pub fn draws(&self, layers: Option<&RenderLayers>) -> bool {
+        self.layers.draws(layers)
+    }
+
+    /// Restricts `render_pass` to the camera's viewport.
+    pub fn set_viewport(&self, render_pass: &mut wgpu::RenderPass) {
+        if let Some(viewport) = self.viewport {
+            render_pass.set_viewport(
+                viewport.min.x,
+                viewport.min.y,
+                viewport.width(),
+                viewport.height(),
+                0.0,
+                1.0,
+            );
+        }
+    }
+
+    /// The [`wgpu::TextureView`] of the camera's [`RenderTarget`].
+    pub fn view<'a>(&'a self, window: &'a RenderView) -> &'a wgpu::TextureView {
+        self.view.as_ref().unwrap_or(window)
+    }
+
+    /// The [`Image`] this camera draws to. Its texture cannot be sampled in the same pass.
+    pub fn target_image(&self) -> Option<&Handle<Image>> {
+        match &self.target {
+            RenderTarget::Window => None,
+            RenderTarget::Image(handle) => Some(handle),
+        }
+    }
+
+    /// Whether `image` is the camera's target, which cannot be sampled while it is drawn to.
+    pub fn is_target(&self, image: AssetId) -> bool {
+        self.target_image()
+            .is_some_and(|target| target.id() == image)
+    }
+}
+
+type CameraQuery = (
+    Entity,
+    Camera,
+    Transform,
+    Option<OrthographicProjection>,
+    Option<RenderLayers>,
+);
+
+fn prepare_cameras(
+    mut render_cameras: ResMut<RenderCameras>,
+    cameras: Query<CameraQuery>,
+    context: Res<RenderContext>,
+    window: Res<Window>,
+    images: Res<Assets<Image>>,
+    mut textures: ResMut<RenderAssets<Texture>>,
+    mut bind_groups: Option<ResMut<AssetBindGroups>>,
+) {
+    let mut previous = std::mem::take(&mut render_cameras.0);
+    for (entity, camera, transform, projection, layers) in cameras.iter() {
+        let Some(viewport_size) = camera.viewport_size(&window, &images) else {
+            continue;
+        };
+
+        let (view, target_size) = match &camera.target {
+            RenderTarget::Window => (None, context.config.dimensions),
+            RenderTarget::Image(handle) => {
+                let Some(image) = images.get(handle) else {
+                    continue;
+                };
+                // Resizing the image does not reload it, so the texture is replaced here
+                let dimensions = image.dimensions();
+                let resized = textures.get(handle).is_some_and(|texture| {
+                    texture.width() != dimensions.width() || texture.height() != dimensions.height()
+                });
+                if resized {
+                    textures.remove(handle);
+                    if let Some(bind_groups) = &mut bind_groups {
+                        bind_groups.remove(handle);
+                    }
+                }
+                let texture = textures
+                    .entry(handle.clone_weak())
+                    .or_insert_with(|| Texture::prepare_asset(image, &context));
+                (Some(texture.create_view()), image.dimensions())
+            }
+        };
+
+        // Render passes panic if the viewport exceeds the target
+        let viewport = camera.viewport.map(|viewport| {
+            let clamp = |v: Vec2f| {
+                Vec2f::new(
+                    v.x.clamp(0.0, target_size.width() as f32),
+                    v.y.clamp(0.0, target_size.height() as f32),
+                )
+            };
+            ViewPort::new(clamp(viewport.min), clamp(viewport.max))
+        });
+        if viewport.is_some_and(|v| v.width() <= 0.0 || v.height() <= 0.0) {
+            continue;
+        }
+
+        // Every camera has its own uniform buffer, because the buffer writes are all submitted
+        // before the frame is rendered.
+        let binding = match previous.iter().position(|c| c.entity == entity) {
+            Some(index) => previous.swap_remove(index).binding,
+            None => <&[CameraUniform] as AsBindGroup>::as_entire_binding_empty(
+                &context,
+                &[],
+                std::mem::size_of::<CameraUniform>() as u64,
+                wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            ),
+        };
+        CameraUniform::write_buffer(
+            &context,
+            binding.single_buffer(),
+            &[CameraUniform::from_projection(
+                transform,
+                &projection.copied().unwrap_or_default(),
+                viewport_size,
+                &window,
+            )],
+        );
+
+        render_cameras.0.push(RenderCamera {
+            entity,
+            index: 0,
+            target: camera.target.clone(),
+            order: camera.order,
+            clear_color: camera.clear_color,
+            layers: layers.copied().unwrap_or_default(),
+            viewport,
+            binding,
+            view,
+        });
+    }
+
+    render_cameras
+        .0
+        .sort_by_key(|camera| draw_order(&camera.target, camera.order));
+    for (index, camera) in render_cameras.0.iter_mut().enumerate() {
+        camera.index = index;
+    }
+}
+
+/// Cameras drawing to an [`Image`] are drawn first, so that it is up to date when displayed.
+fn draw_order(target: &RenderTarget, order: isize) -> (bool, isize) {
+    (*target == RenderTarget::Window, order)
+}
+
 unsafe impl AsGpuBuffer for Dimensions<f32> {}
 unsafe impl AsGpuBuffer for Dimensions<u32> {}
 unsafe impl AsGpuBuffer for CameraUniform {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::SamplerFilterType;
+    use math::vector::Vec3f;
+
+    #[test]
+    fn scaling_modes() {
+        let viewport = Vec2f::new(800.0, 400.0);
+        let size = |mode: ScalingMode| {
+            let size = mode.size(viewport);
+            (size.x, size.y)
+        };
+
+        assert_eq!(size(ScalingMode::WindowSize), (800.0, 400.0));
+        assert_eq!(size(ScalingMode::FixedVertical(100.0)), (200.0, 100.0));
+        assert_eq!(size(ScalingMode::FixedHorizontal(100.0)), (100.0, 50.0));
+        let auto_min = |min_width, min_height| {
+            size(ScalingMode::AutoMin {
+                min_width,
+                min_height,
+            })
+        };
+        assert_eq!(auto_min(100.0, 100.0), (200.0, 100.0));
+        assert_eq!(auto_min(400.0, 100.0), (400.0, 200.0));
+    }
+
+    #[test]
+    fn image_targets_drawn_first() {
+        let mut images = Assets::default();
+        let image =
+            RenderTarget::Image(images.add(Image::render_target(1, 1, SamplerFilterType::Nearest)));
+        let window = RenderTarget::Window;
+
+        let mut cameras = [(&window, -1), (&image, 2), (&window, 0), (&image, 1)];
+        cameras.sort_by_key(|(target, order)| draw_order(target, *order));
+        assert_eq!(
+            cameras,
+            [(&image, 1), (&image, 2), (&window, -1), (&window, 0)]
+        );
+    }
+
+    #[test]
+    fn viewport_world_conversion() {
+        let camera = Camera {
+            viewport: Some(ViewPort::new(
+                Vec2f::new(100.0, 0.0),
+                Vec2f::new(500.0, 200.0),
+            )),
+            ..Default::default()
+        };
+        let transform = Transform {
+            translation: Vec3f::new(10.0, -20.0, 0.0),
+            ..Default::default()
+        };
+        let projection = OrthographicProjection {
+            scale: 2.0,
+            viewport: Vec2f::new(400.0, 200.0),
+            ..Default::default()
+        };
+
+        let center = camera.viewport_to_world(&transform, &projection, Vec2f::new(300.0, 100.0));
+        assert_eq!((center.x, center.y), (10.0, -20.0));
+
+        let corner = camera.viewport_to_world(&transform, &projection, Vec2f::new(100.0, 0.0));
+        assert_eq!((corner.x, corner.y), (-390.0, -220.0));
+
+        let viewport = camera.world_to_viewport(&transform, &projection, corner);
+        assert_eq!((viewport.x, viewport.y), (100.0, 0.0));
+    }
+}
@@ -202,7 +202,7 @@ impl EguiRenderer {
         self.renderer.update_buffers(
             device.deref(),
             queue.deref(),
-            &mut encoder.0,
+            encoder,
             &tris,
             &screen_descriptor,
         );
@@ -1,11 +1,12 @@
 use std::{cmp::Ordering, fmt::Debug, marker::PhantomData};
 
 use crate::{
-    camera::{Camera, CameraUniform},
+    camera::{CameraUniform, RenderCameras},
     render_pipeline::buffer::AsGpuBuffer,
     AsBindGroup, AsVertexBuffer, AsWgpuResources, BindGroup, FragmentShader, FragmentShaderSource,
-    Image, Material, RenderAsset, RenderAssetApp, RenderAssets, RenderEncoder, RenderPipeline2d,
-    RenderView, Texture, Transform, Vertex, VertexBuffer, VertexShader, VertexUv, WgpuResource,
+    Image, Material, RenderAsset, RenderAssetApp, RenderAssets, RenderEncoder, RenderLayers,
+    RenderPipeline2d, RenderView, Texture, Transform, Vertex, VertexBuffer, VertexShader, VertexUv,
+    WgpuResource,
 };
 use app::{
     core::{AppSchedule, Schedule},
@@ -13,7 +14,9 @@ use app::{
     render_util::RenderContext,
     window::Window,
 };
-use asset::{server::AssetServer, Asset, AssetApp, AssetLoader, Assets, Handle};
+use asset::{
+    server::AssetServer, Asset, AssetApp, AssetId, AssetLoader, Assets, CerealAssetSaver, Handle,
+};
 use cereal::{Deserialize, Deserializer, Serialize, WinnyDeserialize, WinnySerialize};
 use ecs::*;
 use ecs::{egui_widget::AsEgui, WinnyAsEgui};
@@ -32,7 +35,8 @@ impl Plugin for Mesh2dPlugin {
         app.egui_component::<BindedGpuMesh2d>()
             .register_asset::<Mesh2d>()
             .register_render_asset::<GpuMesh2d>()
-            .register_asset_loader::<Mesh2d>(Mesh2dAssetLoader);
+            .register_asset_loader::<Mesh2d>(Mesh2dAssetLoader)
+            .register_asset_saver(CerealAssetSaver::<Mesh2d>::new(&["msh"]));
     }
 }
 
@@ -78,10 +82,11 @@ impl AssetLoader for Mesh2dAssetLoader {
         _settings: Self::Settings,
         _path: String,
         _ext: &str,
+        _context: &mut asset::LoadContext,
     ) -> Result<Self::Asset, asset::AssetLoaderError> {
         let mut bytes = reader.read_all()?;
         let mut d = Deserializer::new(&mut bytes);
-        Ok(Mesh2d::deserialize(&mut d).unwrap())
+        Mesh2d::deserialize(&mut d).ok_or(asset::AssetLoaderError::FailedToParse)
     }
 }
 
@@ -273,6 +278,8 @@ pub struct Mesh2dPipeline<M: Material> {
     pipeline: RenderPipeline2d,
     camera: BindGroup,
     pub material: BindGroup,
+    /// Of the material bound to every mesh.
+    texture: Option<AssetId>,
     transforms: VertexBuffer,
     _phantom: PhantomData<M>,
 }
@@ -294,6 +301,7 @@ impl<M: Material> Mesh2dPipeline<M> {
             wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         );
 
+        let texture = material.texture().map(|texture| texture.id());
         let material = <M as AsBindGroup>::as_entire_binding(context, material.clone(), state);
 
         let transforms = <Matrix4x4f as AsVertexBuffer<1>>::as_entire_buffer_empty(
@@ -320,6 +328,7 @@ impl<M: Material> Mesh2dPipeline<M> {
             pipeline,
             camera,
             material,
+            texture,
             transforms,
             _phantom: PhantomData,
         }
@@ -399,7 +408,7 @@ fn bind_new_mesh_bundles<M: Material>(
             if let Some(mesh) = meshes.get(handle) {
                 info!("generating new gpu_mesh: [{mesh:?}]");
                 let mesh = GpuMesh2d::prepare_asset(mesh, &params);
-                gpu_meshes.insert(handle.clone(), mesh);
+                gpu_meshes.insert(handle.clone_weak(), mesh);
                 commands.get_entity(entity).insert(BindedGpuMesh2d);
             }
         }
@@ -413,21 +422,11 @@ fn prepare_render_pass<M: Material>(
     meshes: Query<(Transform, M), With<(Handle<Mesh2d>, BindedGpuMesh2d)>>,
     mut gpu_meshes: ResMut<RenderAssets<GpuMesh2d>>,
     params: <GpuMesh2d as RenderAsset>::Params<'_>,
-    camera: Query<(Camera, Transform)>,
-    window: Res<Window>,
 ) {
     let Some(mut pipeline) = pipeline else {
         return;
     };
 
-    if let Ok((camera, transform)) = camera.get_single() {
-        CameraUniform::write_buffer(
-            &context,
-            pipeline.camera.single_buffer(),
-            &[CameraUniform::from_camera(camera, transform, &window)],
-        );
-    }
-
     let transform_data = meshes
         .iter()
         .map(|(t, _)| t.as_matrix())
@@ -446,8 +445,9 @@ fn prepare_render_pass<M: Material>(
 fn render_pass<M: Material>(
     mut encoder: ResMut<RenderEncoder>,
     view: Res<RenderView>,
+    cameras: Res<RenderCameras>,
     pipeline: Option<Res<Mesh2dPipeline<M>>>,
-    meshes: Query<(Handle<Mesh2d>, M), With<(BindedGpuMesh2d, Transform)>>,
+    meshes: Query<(Handle<Mesh2d>, M, Option<RenderLayers>), With<(BindedGpuMesh2d, Transform)>>,
     gpu_meshes: Res<RenderAssets<GpuMesh2d>>,
     context: Res<RenderContext>,
 ) {
@@ -455,29 +455,47 @@ fn render_pass<M: Material>(
         return;
     };
 
-    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-        label: Some("draw to output"),
-        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-            view: &view,
-            resolve_target: None,
-            ops: wgpu::Operations {
-                load: wgpu::LoadOp::Load,
-                store: wgpu::StoreOp::Store,
-            },
-        })],
-        depth_stencil_attachment: None,
-        occlusion_query_set: None,
-        timestamp_writes: None,
-    });
+    for camera in cameras.iter() {
+        // The target cannot be sampled while it is drawn to
+        if pipeline
+            .texture
+            .is_some_and(|texture| camera.is_target(texture))
+        {
+            continue;
+        }
+
+        let mut render_pass =
+            encoder
+                .camera(camera)
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("draw to output"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: camera.view(&view),
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+        camera.set_viewport(&mut render_pass);
 
-    render_pass.set_pipeline(&pipeline.pipeline.0);
-    render_pass.set_vertex_buffer(1, pipeline.transforms.buffer().slice(..));
-    render_pass.set_bind_group(0, pipeline.camera.binding(), &[]);
-    render_pass.set_bind_group(1, pipeline.material.binding(), &[]);
+        render_pass.set_pipeline(&pipeline.pipeline.0);
+        render_pass.set_vertex_buffer(1, pipeline.transforms.buffer().slice(..));
+        render_pass.set_bind_group(0, camera.binding(), &[]);
+        render_pass.set_bind_group(1, pipeline.material.binding(), &[]);
 
-    for (i, (mesh, material)) in meshes.iter().enumerate() {
-        let gpu_mesh = gpu_meshes.get(mesh).unwrap();
-        render_pass.set_vertex_buffer(0, gpu_mesh.buffer.buffer().slice(..));
-        render_pass.draw(0..gpu_mesh.len, i as u32..i as u32 + 1);
+        for (i, (mesh, material, layers)) in meshes.iter().enumerate() {
+            if !camera.draws(layers) {
+                continue;
+            }
+
+            let gpu_mesh = gpu_meshes.get(mesh).unwrap();
+            render_pass.set_vertex_buffer(0, gpu_mesh.buffer.buffer().slice(..));
+            render_pass.draw(0..gpu_mesh.len, i as u32..i as u32 + 1);
+        }
     }
 }
@@ -1,6 +1,6 @@
 use crate::{
-    camera::{Camera, CameraUniform},
-    render::{RenderEncoder, RenderView},
+    camera::{CameraUniform, RenderCamera, RenderCameras},
+    render::{RenderEncoder, RenderLayers, RenderView},
     render_pipeline::{
         bind_group::{self, AsBindGroup, BindGroup},
         buffer::AsGpuBuffer,
@@ -714,7 +714,7 @@ fn bind_new_cpu_particle_bundles<M: Material>(
         if let Some(vert_shader_handle) = &particle_vert_shader_handle {
             if let Some(image) = images.get(handle) {
                 let texture = textures
-                    .entry(handle.clone())
+                    .entry(handle.clone_weak())
                     .or_insert_with(|| Texture::prepare_asset(image, &texture_params));
 
                 if let Some(vert_shader) = vert_shaders.get_mut(&vert_shader_handle.0) {
@@ -779,8 +779,6 @@ fn update_uniforms<M: Material>(
     )>,
     dt: Res<DeltaTime>,
     context: Res<RenderContext>,
-    camera: Query<(Camera, Transform)>,
-    window: Res<Window>,
 ) {
     for (pipeline, transform, emitter, dimensions) in emitters.iter_mut() {
         let vertex_emitter = VertexEmitterUniform::new(emitter, &context, transform);
@@ -796,16 +794,6 @@ fn update_uniforms<M: Material>(
             &pipeline.compute_emitter_resources.single_buffer(),
             &[compute_emitter],
         );
-
-        let Ok((camera, transform)) = camera.get_single() else {
-            continue;
-        };
-
-        CameraUniform::write_buffer(
-            &context,
-            pipeline.camera_binding.single_buffer(),
-            &[CameraUniform::from_camera(camera, transform, &window)],
-        );
     }
 }
 
@@ -818,8 +806,6 @@ fn update_cpu_uniforms<M: Material>(
     )>,
     dt: Res<DeltaTime>,
     context: Res<RenderContext>,
-    camera: Query<(Camera, Transform)>,
-    window: Res<Window>,
 ) {
     for (pipeline, transform, emitter, dimensions) in emitters.iter_mut() {
         pipeline.update_particles(&dt);
@@ -829,16 +815,6 @@ fn update_cpu_uniforms<M: Material>(
             &pipeline.particle_transform_buffer.buffer(),
             &pipeline.particle_transforms(&context, emitter, transform),
         );
-
-        let Ok((camera, transform)) = camera.get_single() else {
-            continue;
-        };
-
-        CameraUniform::write_buffer(
-            &context,
-            pipeline.camera_binding.single_buffer(),
-            &[CameraUniform::from_camera(camera, transform, &window)],
-        );
     }
 }
 
@@ -874,35 +850,65 @@ fn compute_emitters<M: Material>(
     }
 }
 
+/// The target cannot be sampled while it is drawn to.
+fn draws_emitter<M: Material>(
+    camera: &RenderCamera,
+    material: &M,
+    layers: Option<&RenderLayers>,
+) -> bool {
+    camera.draws(layers)
+        && !material
+            .texture()
+            .is_some_and(|texture| camera.is_target(texture.id()))
+}
+
 fn render_emitters<M: Material>(
     mut encoder: ResMut<RenderEncoder>,
     view: Res<RenderView>,
-    emitters: Query<(ParticlePipeline<M>, ParticleEmitter), With<Transform>>,
+    cameras: Res<RenderCameras>,
+    emitters: Query<
+        (
+            ParticlePipeline<M>,
+            M,
+            ParticleEmitter,
+            Option<RenderLayers>,
+        ),
+        With<Transform>,
+    >,
 ) {
-    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-        label: Some("particles"),
-        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-            view: &view,
-            resolve_target: None,
-            ops: wgpu::Operations {
-                load: wgpu::LoadOp::Load,
-                store: wgpu::StoreOp::Store,
-            },
-        })],
-        depth_stencil_attachment: None,
-        occlusion_query_set: None,
-        timestamp_writes: None,
-    });
-
-    for (pipeline, _) in emitters.iter().filter(|(_, e)| e.is_emitting) {
-        render_pass.set_pipeline(&pipeline.render_pipeline.0);
-        render_pass.set_vertex_buffer(0, pipeline.particle_vertex_buffer.buffer().slice(..));
-        render_pass.set_vertex_buffer(1, pipeline.alive_index_buffer.buffer().slice(..));
-        render_pass.set_bind_group(0, &pipeline.vertex_emitter_resources.binding(), &[]);
-        render_pass.set_bind_group(1, &pipeline.vertex_particle_resources.binding(), &[]);
-        render_pass.set_bind_group(2, &pipeline.camera_binding.binding(), &[]);
-        render_pass.set_bind_group(3, &pipeline.material_resources.binding(), &[]);
-        render_pass.draw(0..6, 0..pipeline.buffer_len);
+    for camera in cameras.iter() {
+        let mut render_pass =
+            encoder
+                .camera(camera)
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("particles"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: camera.view(&view),
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+        camera.set_viewport(&mut render_pass);
+
+        for (pipeline, _, _, _) in emitters
+            .iter()
+            .filter(|(_, mat, e, layers)| e.is_emitting && draws_emitter(camera, *mat, *layers))
+        {
+            render_pass.set_pipeline(&pipeline.render_pipeline.0);
+            render_pass.set_vertex_buffer(0, pipeline.particle_vertex_buffer.buffer().slice(..));
+            render_pass.set_vertex_buffer(1, pipeline.alive_index_buffer.buffer().slice(..));
+            render_pass.set_bind_group(0, &pipeline.vertex_emitter_resources.binding(), &[]);
+            render_pass.set_bind_group(1, &pipeline.vertex_particle_resources.binding(), &[]);
+            render_pass.set_bind_group(2, camera.binding(), &[]);
+            render_pass.set_bind_group(3, &pipeline.material_resources.binding(), &[]);
+            render_pass.draw(0..6, 0..pipeline.buffer_len);
+        }
     }
 }
 
@@ -910,30 +916,48 @@ fn render_cpu_emitters<M: Material>(
     mut encoder: ResMut<RenderEncoder>,
     context: Res<RenderContext>,
     view: Res<RenderView>,
-    emitters: Query<(CpuParticlePipeline<M>, M, ParticleEmitter), With<Transform>>,
+    cameras: Res<RenderCameras>,
+    emitters: Query<
+        (
+            CpuParticlePipeline<M>,
+            M,
+            ParticleEmitter,
+            Option<RenderLayers>,
+        ),
+        With<Transform>,
+    >,
 ) {
-    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-        label: Some("cpu particles"),
-        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-            view: &view,
-            resolve_target: None,
-            ops: wgpu::Operations {
-                load: wgpu::LoadOp::Load,
-                store: wgpu::StoreOp::Store,
-            },
-        })],
-        depth_stencil_attachment: None,
-        occlusion_query_set: None,
-        timestamp_writes: None,
-    });
-
-    for (pipeline, mat, _) in emitters.iter().filter(|(_, _, e)| e.is_emitting) {
-        // mat.update(&context, &pipeline.material_resources);
-        render_pass.set_pipeline(&pipeline.render_pipeline.0);
-        render_pass.set_vertex_buffer(0, pipeline.particle_vertex_buffer.buffer().slice(..));
-        render_pass.set_vertex_buffer(1, pipeline.particle_transform_buffer.buffer().slice(..));
-        render_pass.set_bind_group(0, &pipeline.camera_binding.binding(), &[]);
-        render_pass.set_bind_group(1, &pipeline.material_resources.binding(), &[]);
-        render_pass.draw(0..6, 0..pipeline.particles.len() as u32);
+    for camera in cameras.iter() {
+        let mut render_pass =
+            encoder
+                .camera(camera)
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("cpu particles"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: camera.view(&view),
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+        camera.set_viewport(&mut render_pass);
+
+        for (pipeline, mat, _, _) in emitters
+            .iter()
+            .filter(|(_, mat, e, layers)| e.is_emitting && draws_emitter(camera, *mat, *layers))
+        {
+            // mat.update(&context, &pipeline.material_resources);
+            render_pass.set_pipeline(&pipeline.render_pipeline.0);
+            render_pass.set_vertex_buffer(0, pipeline.particle_vertex_buffer.buffer().slice(..));
+            render_pass.set_vertex_buffer(1, pipeline.particle_transform_buffer.buffer().slice(..));
+            render_pass.set_bind_group(0, camera.binding(), &[]);
+            render_pass.set_bind_group(1, &pipeline.material_resources.binding(), &[]);
+            render_pass.draw(0..6, 0..pipeline.particles.len() as u32);
+        }
     }
 }
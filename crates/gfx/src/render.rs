@@ -1,5 +1,6 @@
 use app::prelude::*;
-use ecs::{AsEgui, Commands, Res, ResMut, Take, WinnyAsEgui, WinnyResource};
+use ecs::egui_widget::Widget;
+use ecs::{AsEgui, Commands, Res, ResMut, Take, WinnyAsEgui, WinnyComponent, WinnyResource};
 use math::vector::Vec4f;
 use std::{
     fmt::Debug,
@@ -7,7 +8,10 @@ use std::{
 };
 use util::{info, tracing::trace};
 
-use crate::Modulation;
+use crate::{
+    camera::{RenderCamera, RenderCameras, RenderTarget},
+    Modulation,
+};
 
 #[derive(Debug)]
 pub struct RendererPlugin;
@@ -18,6 +22,7 @@ impl Plugin for RendererPlugin {
         std::panic::set_hook(Box::new(console_error_panic_hook::hook));
 
         app.egui_resource::<ClearColor>()
+            .egui_component::<RenderLayers>()
             .register_resource::<Renderer>()
             .register_resource::<RenderView>()
             .register_resource::<RenderContext>()
@@ -29,6 +34,7 @@ impl Plugin for RendererPlugin {
             .add_systems(AppSchedule::RenderStartup, startup)
             .add_systems(AppSchedule::PrepareRender, start_render)
             .add_systems(AppSchedule::PreRender, clear_screen)
+            .add_systems(AppSchedule::PostRender, submit_cameras)
             .add_systems(AppSchedule::Present, present);
     }
 }
@@ -37,30 +43,148 @@ impl Plugin for RendererPlugin {
 #[derive(WinnyResource, WinnyAsEgui)]
 pub struct ClearColor(pub Modulation);
 
-fn clear_screen(mut encoder: ResMut<RenderEncoder>, view: Res<RenderView>, clear: Res<ClearColor>) {
-    {
-        let _ = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("downscale pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: clear.0 .0.x as f64,
-                        g: clear.0 .0.y as f64,
-                        b: clear.0 .0.z as f64,
-                        a: clear.0 .0.w as f64,
-                    }),
-                    store: wgpu::StoreOp::Store,
-                },
-                resolve_target: None,
-            })],
-            depth_stencil_attachment: None,
-            occlusion_query_set: None,
-            timestamp_writes: None,
-        });
+/// How a [`crate::camera::Camera`] clears its [`crate::camera::RenderTarget`] before drawing.
+///
+/// Only the camera with the lowest order of each target clears it, so overlays drawn by later
+/// cameras do not erase earlier ones.
+#[derive(Debug, Default, Clone, Copy)]
+pub enum ClearColorConfig {
+    /// Clears with the [`ClearColor`] resource.
+    #[default]
+    Default,
+    Color(Modulation),
+    /// Draws over the previous contents of the target.
+    None,
+}
+
+impl ecs::egui_widget::Widget for ClearColorConfig {
+    fn display(&mut self, ui: &mut ecs::egui::Ui) {
+        match self {
+            Self::Default => {
+                ui.label("Default");
+            }
+            Self::Color(color) => color.display(ui),
+            Self::None => {
+                ui.label("None");
+            }
+        }
+    }
+}
+
+/// Bitmask of the layers an entity is drawn on. A camera only draws entities which share a layer
+/// with its own [`RenderLayers`]. Entities and cameras without one are on layer 0.
+#[derive(WinnyComponent, WinnyAsEgui, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderLayers(pub u32);
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        Self::layer(0)
     }
 }
 
+impl RenderLayers {
+    pub const ALL: Self = Self(u32::MAX);
+
+    /// Only `layer`. Panics if `layer` is not less than 32.
+    pub const fn layer(layer: u8) -> Self {
+        Self(Self::bit(layer))
+    }
+
+    pub const fn with(self, layer: u8) -> Self {
+        Self(self.0 | Self::bit(layer))
+    }
+
+    pub const fn intersects(&self, other: &Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Whether a camera on these layers draws an entity with `layers`.
+    pub fn draws(&self, layers: Option<&RenderLayers>) -> bool {
+        self.intersects(layers.unwrap_or(&RenderLayers::default()))
+    }
+
+    const fn bit(layer: u8) -> u32 {
+        match 1u32.checked_shl(layer as u32) {
+            Some(bit) => bit,
+            None => panic!("render layers must be less than 32"),
+        }
+    }
+}
+
+/// A clear pass of [`clear_screen`].
+#[derive(Debug, PartialEq, Eq)]
+enum Clear {
+    /// The target of the camera at this index.
+    Camera(usize),
+    /// The window, which no camera draws to.
+    Window,
+}
+
+/// Each target is cleared by its first camera, which are sorted by order.
+fn clears<'a>(
+    cameras: impl IntoIterator<Item = (&'a RenderTarget, ClearColorConfig)>,
+) -> Vec<Clear> {
+    let mut cleared = Vec::new();
+    let mut clears = Vec::new();
+    for (i, (target, clear_color)) in cameras.into_iter().enumerate() {
+        if cleared.contains(&target) {
+            continue;
+        }
+        cleared.push(target);
+
+        if !matches!(clear_color, ClearColorConfig::None) {
+            clears.push(Clear::Camera(i));
+        }
+    }
+
+    if !cleared.contains(&&RenderTarget::Window) {
+        clears.push(Clear::Window);
+    }
+
+    clears
+}
+
+fn clear_screen(
+    mut encoder: ResMut<RenderEncoder>,
+    view: Res<RenderView>,
+    cameras: Res<RenderCameras>,
+    clear: Res<ClearColor>,
+) {
+    let cameras = cameras.iter().collect::<Vec<_>>();
+    let targets = cameras
+        .iter()
+        .map(|camera| (&camera.target, camera.clear_color));
+    for target in clears(targets) {
+        match target {
+            Clear::Camera(i) => {
+                let color = match cameras[i].clear_color {
+                    ClearColorConfig::Color(color) => color,
+                    _ => clear.0,
+                };
+                clear_target(&mut encoder, cameras[i].view(&view), color);
+            }
+            Clear::Window => clear_target(&mut encoder, &view, clear.0),
+        }
+    }
+}
+
+fn clear_target(encoder: &mut RenderEncoder, view: &wgpu::TextureView, color: Modulation) {
+    let _ = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("clear"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(color.wgpu_color()),
+                store: wgpu::StoreOp::Store,
+            },
+            resolve_target: None,
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+}
+
 fn startup(mut commands: Commands, window: Res<Window>) {
     let (device, queue, renderer) = Renderer::new(&window);
 
@@ -178,9 +302,23 @@ pub fn start_render(mut commands: Commands, renderer: Res<Renderer>, context: Re
         context
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor::default()),
+        context.device.clone(),
     ));
 }
 
+/// Submits the passes recorded before the end of [`AppSchedule::Render`], followed by the passes
+/// of each camera in [`RenderCameras`] order. Later passes are recorded into a new encoder.
+fn submit_cameras(context: Res<RenderContext>, mut encoder: ResMut<RenderEncoder>) {
+    let next = RenderEncoder::new(
+        context
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default()),
+        context.device.clone(),
+    );
+    let encoder = std::mem::replace(&mut *encoder, next);
+    context.queue.submit(encoder.finish_cameras());
+}
+
 pub fn present(
     context: Res<RenderContext>,
     encoder: Option<Take<RenderEncoder>>,
@@ -205,29 +343,70 @@ pub fn present(
 }
 
 /// Handle to the active [`wgpu::CommandEncoder`] in the render app schedule
+///
+/// Passes drawn by a camera are recorded with [`RenderEncoder::camera`]. They are submitted after
+/// the passes recorded into this encoder during [`AppSchedule::Render`], one camera at a time, so
+/// that an [`Image`] drawn by a camera is up to date when the cameras after it sample it.
+///
+/// [`Image`]: crate::Image
 #[derive(Debug, WinnyResource)]
-pub struct RenderEncoder(pub wgpu::CommandEncoder);
+pub struct RenderEncoder {
+    encoder: wgpu::CommandEncoder,
+    device: RenderDevice,
+    /// Encoders of the [`RenderCameras`], by index.
+    cameras: Vec<Option<wgpu::CommandEncoder>>,
+}
 
 impl Deref for RenderEncoder {
     type Target = wgpu::CommandEncoder;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.encoder
     }
 }
 
 impl DerefMut for RenderEncoder {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.encoder
     }
 }
 
 impl RenderEncoder {
-    pub fn new(encoder: wgpu::CommandEncoder) -> Self {
-        Self(encoder)
+    pub fn new(encoder: wgpu::CommandEncoder, device: RenderDevice) -> Self {
+        Self {
+            encoder,
+            device,
+            cameras: Vec::new(),
+        }
+    }
+
+    /// Encoder of the passes drawn by `camera`.
+    pub fn camera(&mut self, camera: &RenderCamera) -> &mut wgpu::CommandEncoder {
+        if self.cameras.len() <= camera.index() {
+            self.cameras.resize_with(camera.index() + 1, || None);
+        }
+
+        self.cameras[camera.index()].get_or_insert_with(|| {
+            self.device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("camera"),
+                })
+        })
     }
 
     pub(crate) fn finish(self) -> wgpu::CommandBuffer {
-        self.0.finish()
+        self.encoder.finish()
+    }
+
+    /// The command buffers of this encoder, followed by those of the cameras.
+    fn finish_cameras(self) -> Vec<wgpu::CommandBuffer> {
+        std::iter::once(self.encoder.finish())
+            .chain(
+                self.cameras
+                    .into_iter()
+                    .flatten()
+                    .map(wgpu::CommandEncoder::finish),
+            )
+            .collect()
     }
 }
 
@@ -269,5 +448,50 @@ impl RenderOutput {
     }
 }
 
-// #[derive(WinnyComponent, PartialEq, Eq)]
-// pub struct RenderLayer(pub u8);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{texture::SamplerFilterType, Image};
+    use asset::Assets;
+
+    #[test]
+    fn layers() {
+        let camera = RenderLayers::layer(1).with(31);
+        assert_eq!(camera, RenderLayers(1 << 1 | 1 << 31));
+        assert!(camera.intersects(&RenderLayers::layer(31)));
+        assert!(!camera.intersects(&RenderLayers::layer(0)));
+        assert!(RenderLayers::ALL.intersects(&RenderLayers::layer(7)));
+
+        // Entities without layers are on layer 0
+        assert!(!camera.draws(None));
+        assert!(RenderLayers::default().draws(None));
+        assert!(camera.draws(Some(&RenderLayers::layer(1))));
+    }
+
+    #[test]
+    #[should_panic]
+    fn layer_out_of_range() {
+        RenderLayers::layer(32);
+    }
+
+    #[test]
+    fn clear_each_target_once() {
+        let mut images = Assets::default();
+        let image =
+            RenderTarget::Image(images.add(Image::render_target(1, 1, SamplerFilterType::Nearest)));
+        let window = RenderTarget::Window;
+
+        let targets = [
+            (&image, ClearColorConfig::None),
+            (&window, ClearColorConfig::Default),
+            (&image, ClearColorConfig::Default),
+            (&window, ClearColorConfig::Default),
+        ];
+        assert_eq!(clears(targets), [Clear::Camera(1)]);
+
+        // The window is cleared when no camera draws to it
+        let targets = [(&image, ClearColorConfig::Default)];
+        assert_eq!(clears(targets), [Clear::Camera(0), Clear::Window]);
+        assert_eq!(clears([]), [Clear::Window]);
+    }
+}
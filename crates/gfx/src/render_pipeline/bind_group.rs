@@ -7,6 +7,7 @@ use asset::*;
 use ecs::system_param::SystemParam;
 use ecs::{SparseArrayIndex, SparseSet, WinnyAsEgui, WinnyComponent, WinnyResource};
 use fxhash::FxHashMap;
+use std::any::TypeId;
 use wgpu::BufferUsages;
 
 #[derive(Debug)]
@@ -455,6 +456,10 @@ impl BindGroupHandle {
     pub fn id(&self) -> BindGroupId {
         self.0
     }
+
+    pub fn asset_id(&self) -> AssetId {
+        self.1
+    }
 }
 
 #[derive(WinnyAsEgui, Debug, Clone, Copy, PartialEq, Eq)]
@@ -468,10 +473,18 @@ impl SparseArrayIndex for BindGroupId {
 
 /// Stores [`RenderBindGroup`]s. Register and retrieve a RenderBindGroup with a
 /// [`BindGroupHandle`].
+///
+/// Entries are removed when their [`Asset`] is unloaded, see
+/// [`crate::render_pipeline::render_assets::RenderAssetApp`].
 #[derive(WinnyResource, Default)]
 pub struct AssetBindGroups {
     bindings: SparseSet<BindGroupId, RenderBindGroup>,
-    stored_bindings: FxHashMap<AssetId, BindGroupId>,
+    /// Ids of different [`Asset`] types may be equal.
+    stored_bindings: FxHashMap<(TypeId, AssetId), BindGroupId>,
+}
+
+fn key<A: Asset>(handle: &Handle<A>) -> (TypeId, AssetId) {
+    (TypeId::of::<A>(), handle.id())
 }
 
 impl AssetBindGroups {
@@ -483,22 +496,28 @@ impl AssetBindGroups {
         self.bindings.get(&id)
     }
 
-    pub fn contains(&self, id: AssetId) -> bool {
-        self.stored_bindings.contains_key(&id)
+    pub fn contains<A: Asset>(&self, handle: &Handle<A>) -> bool {
+        self.stored_bindings.contains_key(&key(handle))
     }
 
     pub fn get_from_handle<A: Asset>(&self, handle: &Handle<A>) -> Option<&RenderBindGroup> {
         self.stored_bindings
-            .get(&handle.id())
+            .get(&key(handle))
             .and_then(|b| self.bindings.get(b))
     }
 
     pub fn get_handle<A: Asset>(&self, handle: &Handle<A>) -> Option<BindGroupHandle> {
         self.stored_bindings
-            .get(&handle.id())
+            .get(&key(handle))
             .map(|id| BindGroupHandle::new(*id, handle.id()))
     }
 
+    /// Drops the [`RenderBindGroup`] of `handle`, and the GPU resources it holds.
+    pub fn remove<A: Asset>(&mut self, handle: &Handle<A>) -> Option<RenderBindGroup> {
+        let bind_id = self.stored_bindings.remove(&key(handle))?;
+        Some(self.bindings.remove(&bind_id))
+    }
+
     pub fn insert<A: Asset>(
         &mut self,
         handle: Handle<A>,
@@ -507,7 +526,7 @@ impl AssetBindGroups {
         let bind_id = self
             .bindings
             .insert_in_first_empty(bind_group, |index| BindGroupId(index));
-        self.stored_bindings.insert(handle.id(), bind_id);
+        self.stored_bindings.insert(key(&handle), bind_id);
         BindGroupHandle::new(bind_id, handle.id())
     }
 
@@ -516,7 +535,7 @@ impl AssetBindGroups {
         handle: Handle<A>,
         bind_group: impl FnOnce() -> RenderBindGroup,
     ) -> BindGroupHandle {
-        if let Some(bind_id) = self.stored_bindings.get(&handle.id()) {
+        if let Some(bind_id) = self.stored_bindings.get(&key(&handle)) {
             BindGroupHandle::new(*bind_id, handle.id())
         } else {
             self.insert(handle, bind_group())
@@ -11,10 +11,8 @@ use crate::{
     mesh2d::Mesh2dMatPlugin, particle::CpuParticlePlugin, Image, RenderAsset, RenderAssets,
 };
 use crate::{
-    particle::ParticlePlugin,
-    sprite::SpriteMaterialPlugin,
-    texture::{SamplerFilterType, Texture},
-    BindGroup, CpuParticlePipeline, MaterialMarker, ParticlePipeline, SpritePipeline,
+    particle::ParticlePlugin, sprite::SpriteMaterialPlugin, texture::Texture, BindGroup,
+    CpuParticlePipeline, MaterialMarker, ParticlePipeline, SpritePipeline,
 };
 use app::render_util::RenderContext;
 use app::{core::App, plugins::Plugin};
@@ -85,6 +83,11 @@ pub trait Material: AsBindGroup + Clone + Component {
         Handle::dangling()
     }
 
+    /// The [`Image`] sampled by the material. Not drawn by cameras which target it.
+    fn texture(&self) -> Option<&Handle<Image>> {
+        None
+    }
+
     fn is_init(&self, server: &AssetServer, shaders: &Assets<FragmentShaderSource>) -> bool {
         shaders
             .get(&self.particle_fragment_shader(server))
@@ -110,7 +113,7 @@ impl Material for Material2d {
         if let Some(image) = images.get(&self.texture) {
             Some(
                 textures
-                    .entry(self.texture.clone())
+                    .entry(self.texture.clone_weak())
                     .or_insert_with(|| Texture::prepare_asset(image, &context)),
             )
         } else {
@@ -133,6 +136,10 @@ impl Material for Material2d {
     fn mesh_2d_fragment_shader(&self, server: &AssetServer) -> Handle<FragmentShaderSource> {
         server.load("winny/res/shaders/material2d_mesh.wgsl")
     }
+
+    fn texture(&self) -> Option<&Handle<Image>> {
+        Some(&self.texture)
+    }
 }
 
 impl AsWgpuResources for Material2d {
@@ -146,7 +153,7 @@ impl AsWgpuResources for Material2d {
         _buffer_type: Option<BufferType>,
     ) -> Vec<super::bind_group::WgpuResource> {
         let texture_resources =
-            state.as_wgpu_resources(context, label, SamplerFilterType::Nearest, None);
+            state.as_wgpu_resources(context, label, state.sampler_filter(), None);
         let uniform_resources = <&[RawMaterial2d] as AsWgpuResources>::as_wgpu_resources(
             &[self.as_raw()],
             context,
@@ -1,9 +1,10 @@
 use app::prelude::*;
-use asset::{handle::Handle, Asset};
-use ecs::{system_param::SystemParam, Res, WinnyResource};
+use asset::{handle::Handle, Asset, AssetLoaderEvent};
+use ecs::{system_param::SystemParam, EventReader, Res, ResMut, WinnyResource};
 use fxhash::FxHashMap;
 use std::ops::{Deref, DerefMut};
 
+use crate::render_pipeline::bind_group::AssetBindGroups;
 use crate::texture::{Image, Texture, TextureAtlas};
 
 pub trait RenderAssetApp {
@@ -12,12 +13,36 @@ pub trait RenderAssetApp {
 
 impl RenderAssetApp for App {
     fn register_render_asset<R: RenderAsset>(&mut self) -> &mut Self {
-        self.insert_resource(RenderAssets::<R>::default());
+        self.insert_resource(RenderAssets::<R>::default())
+            .add_systems(AppSchedule::PrepareRender, free_unloaded_render_assets::<R>);
         self
     }
 }
 
+/// Drops the [`RenderAsset`]s and bind groups of [`Asset`]s which loaded again or unloaded.
+/// Bind groups are recreated by the systems that use them, such as for sprites.
+pub(crate) fn free_unloaded_render_assets<R: RenderAsset>(
+    mut render_assets: ResMut<RenderAssets<R>>,
+    mut bind_groups: Option<ResMut<AssetBindGroups>>,
+    reader: EventReader<AssetLoaderEvent<R::Asset>>,
+) {
+    for event in reader.peak_read() {
+        match event {
+            // Loaded covers reloads, and loads after the fallback was prepared
+            AssetLoaderEvent::Loaded { handle } | AssetLoaderEvent::Unloaded { handle } => {
+                render_assets.remove(handle);
+                if let Some(bind_groups) = &mut bind_groups {
+                    bind_groups.remove(handle);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
 /// Collection of type R [`RenderAsset`].
+///
+/// Keyed by weak [`Handle`]s, so that the source [`Asset`] can be unloaded.
 #[derive(WinnyResource, Debug)]
 pub struct RenderAssets<R: RenderAsset>(pub FxHashMap<Handle<R::Asset>, R>);
 
@@ -54,6 +79,10 @@ impl RenderAsset for Texture {
     type Params<'w> = Res<'w, RenderContext>;
 
     fn prepare_asset<'w>(asset: &Self::Asset, context: &Self::Params<'w>) -> Self {
+        if asset.is_render_target() {
+            return Texture::render_target(context, asset);
+        }
+
         Texture::from_image(&context.device, &context.queue, asset)
     }
 }
@@ -12,7 +12,16 @@ impl Plugin for ShaderPlugin {
         app.register_asset::<VertexShaderSource>()
             .register_asset::<FragmentShaderSource>()
             .register_asset_loader::<VertexShaderSource>(vert_loader)
-            .register_asset_loader::<FragmentShaderSource>(frag_loader);
+            .register_asset_loader::<FragmentShaderSource>(frag_loader)
+            .register_asset_processor(ShaderProcessor);
+
+        // Materials request their shaders by path every frame
+        app.world_mut()
+            .resource_mut::<Assets<VertexShaderSource>>()
+            .retain_unreferenced(true);
+        app.world_mut()
+            .resource_mut::<Assets<FragmentShaderSource>>()
+            .retain_unreferenced(true);
     }
 }
 
@@ -97,6 +106,7 @@ impl AssetLoader for VertexShaderLoader {
         _settings: Self::Settings,
         _path: String,
         _ext: &str,
+        _context: &mut asset::LoadContext,
     ) -> Result<Self::Asset, asset::AssetLoaderError> {
         let string = reader.read_all_to_string()?;
         Ok(VertexShaderSource(string, None))
@@ -118,8 +128,42 @@ impl AssetLoader for FragmentShaderLoader {
         _settings: Self::Settings,
         _path: String,
         _ext: &str,
+        _context: &mut asset::LoadContext,
     ) -> Result<Self::Asset, asset::AssetLoaderError> {
         let string = reader.read_all_to_string()?;
         Ok(FragmentShaderSource(string, None))
     }
 }
+
+/// Validates WGSL shaders while processing, so that invalid shaders are reported before the
+/// application creates a pipeline with them.
+pub struct ShaderProcessor;
+
+impl AssetProcessor for ShaderProcessor {
+    type Settings = ();
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["wgsl"]
+    }
+
+    fn process(
+        &self,
+        bytes: Vec<u8>,
+        _settings: &Self::Settings,
+        _ext: &str,
+    ) -> Result<Vec<u8>, ProcessError> {
+        use wgpu::naga;
+
+        let source = String::from_utf8(bytes).map_err(|e| ProcessError::Failed(e.to_string()))?;
+        let module = naga::front::wgsl::parse_str(&source)
+            .map_err(|e| ProcessError::Failed(e.emit_to_string(&source)))?;
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .map_err(|e| ProcessError::Failed(e.emit_to_string(&source)))?;
+
+        Ok(source.into_bytes())
+    }
+}
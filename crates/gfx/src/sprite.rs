@@ -1,5 +1,5 @@
-use crate::camera::{Camera, CameraUniform};
-use crate::render::{RenderEncoder, RenderView};
+use crate::camera::{CameraUniform, RenderCameras};
+use crate::render::{RenderEncoder, RenderLayers, RenderView};
 use crate::render_pipeline::bind_group::{
     self, AsBindGroup, AssetBindGroups, BindGroup, BindGroupHandle, RenderBindGroup,
 };
@@ -19,7 +19,7 @@ use asset::*;
 use cgmath::{Quaternion, Rad, Rotation3};
 use ecs::system_param::SystemParam;
 use ecs::*;
-use ecs::{WinnyBundle, WinnyComponent, WinnyResource};
+use ecs::{WinnyBundle, WinnyComponent, WinnyResource, WinnySystemParam};
 use math::angle::{Degrees, Radf};
 use math::matrix::{scale_matrix4x4f, Matrix4x4f};
 use math::vector::{Vec2f, Vec3f};
@@ -307,18 +307,12 @@ fn bind_new_sprite_bundles<M: Material>(
             if let Some(vert_shader) = vert_shaders.get_mut(&vert_shader_handle.0) {
                 if let Some(image) = images.get(image_handle) {
                     let texture = textures
-                        .entry(image_handle.clone())
+                        .entry(image_handle.clone_weak())
                         .or_insert_with(|| Texture::prepare_asset(image, &texture_params));
                     let dimensions = TextureDimensions::from_texture(&texture);
 
-                    let binding = if !bind_groups.contains(image_handle.id()) {
-                        let binding = RenderBindGroup(<M as AsBindGroup>::as_entire_binding(
-                            &context,
-                            material.clone(),
-                            material
-                                .resource_state(&mut textures, &images, &context)
-                                .expect("material is initialized"),
-                        ));
+                    let binding = if !bind_groups.contains(image_handle) {
+                        let binding = material_binding(material, &context, &mut textures, &images);
                         bind_groups.insert(image_handle.clone(), binding)
                     } else {
                         bind_groups.get_handle(image_handle).unwrap()
@@ -375,10 +369,12 @@ fn bind_new_sprite_bundles<M: Material>(
     }
 }
 
+/// Rebinds sprites whose bind group was dropped because their [`Image`] loaded again, such as
+/// after a reload or a successful retry. The dropped bind group may hold the fallback texture.
 fn bind_updated_texture_handles<M: Material>(
     mut bind_groups: ResMut<AssetBindGroups>,
     mut sprites: Query<(
-        Mut<Handle<Image>>,
+        Handle<Image>,
         Mut<BindGroupHandle>,
         Mut<TextureDimensions>,
         M,
@@ -386,37 +382,41 @@ fn bind_updated_texture_handles<M: Material>(
     context: Res<RenderContext>,
     images: Res<Assets<Image>>,
     mut textures: ResMut<RenderAssets<Texture>>,
-    exture_params: <Texture as RenderAsset>::Params<'_>,
-    // params: <M as Material>::BindingState<'_>,
 ) {
-    // for (texture_handle, bind_group_handle, texture_dimensions, material) in sprites.iter_mut() {
-    //     if texture_handle.is_changed() {
-    //         if let Some(image) = images.get(texture_handle) {
-    //             let texture = textures
-    //                 .entry(texture_handle.clone())
-    //                 .or_insert_with(|| Texture::prepare_asset(image, &texture_params));
-    //             let dimensions = TextureDimensions::from_texture(texture);
-    //             if !bind_groups.contains(texture_handle.id()) {
-    //                 let binding = RenderBindGroup(<M as AsBindGroup>::as_entire_binding(
-    //                     &context,
-    //                     material.clone(),
-    //                     material
-    //                         .resource_state(&params)
-    //                         .expect("material is not initialized"),
-    //                 ));
-    //                 bind_groups.insert(texture_handle.clone(), binding);
-    //             }
-    //
-    //             let binding = bind_groups.get_from_id(texture_handle.id()).unwrap();
-    //             *bind_group_handle = binding;
-    //             *texture_dimensions = dimensions;
-    //         } else {
-    //             // Image is not yet created, so we mark the Handle<Image> as `changed` to repeat
-    //             // this operation.
-    //             texture_handle.mark_changed();
-    //         }
-    //     }
-    // }
+    for (image_handle, bind_group_handle, texture_dimensions, material) in sprites.iter_mut() {
+        // Still bound, or rebound by another sprite with the same image
+        if let Some(binding) = bind_groups.get_handle(image_handle) {
+            if binding.id() != bind_group_handle.id() {
+                *bind_group_handle = binding;
+            }
+            continue;
+        }
+        let Some(image) = images.get(image_handle) else {
+            continue;
+        };
+
+        let texture = textures
+            .entry(image_handle.clone_weak())
+            .or_insert_with(|| Texture::prepare_asset(image, &context));
+        *texture_dimensions = TextureDimensions::from_texture(texture);
+        let binding = material_binding(material, &context, &mut textures, &images);
+        *bind_group_handle = bind_groups.insert(image_handle.clone(), binding);
+    }
+}
+
+fn material_binding<M: Material>(
+    material: &M,
+    context: &Res<RenderContext>,
+    textures: &mut RenderAssets<Texture>,
+    images: &Assets<Image>,
+) -> RenderBindGroup {
+    RenderBindGroup(<M as AsBindGroup>::as_entire_binding(
+        context,
+        material.clone(),
+        material
+            .resource_state(textures, images, context)
+            .expect("material is initialized"),
+    ))
 }
 
 #[repr(C)]
@@ -618,23 +618,10 @@ fn prepare_for_render_pass<M: Material>(
         (Sprite, Transform, TextureDimensions, Option<AnimatedSprite>),
         With<(M, SpritePipelineEntity, BindGroupHandle)>,
     >,
-    context: Res<RenderContext>,
-    camera: Query<(Camera, Transform)>,
-    window: Res<Window>,
 ) {
-    let Ok(pipeline) = sprite_pipeline.get_single() else {
-        return;
-    };
-
-    let Ok((camera, transform)) = camera.get_single() else {
+    if sprite_pipeline.get_single().is_err() {
         return;
-    };
-
-    CameraUniform::write_buffer(
-        &context,
-        pipeline.camera_binding.single_buffer(),
-        &[CameraUniform::from_camera(camera, transform, &window)],
-    );
+    }
 
     buffers.append_sprites(
         sprites
@@ -644,68 +631,197 @@ fn prepare_for_render_pass<M: Material>(
     );
 }
 
-fn render_sprites(
-    mut buffers: ResMut<SpriteBuffers>,
-    mut encoder: ResMut<RenderEncoder>,
-    context: Res<RenderContext>,
-    sprite_pipelines: Query<(Entity, SpritePipeline)>,
-    sprites: Query<
-        (SpritePipelineEntity, Sprite, BindGroupHandle),
+#[derive(WinnySystemParam)]
+pub struct SpriteRenderParams<'w, 's> {
+    pub buffers: ResMut<'w, SpriteBuffers>,
+    pub encoder: ResMut<'w, RenderEncoder>,
+    pub context: Res<'w, RenderContext>,
+    pub sprite_pipelines: Query<'w, 's, (Entity, SpritePipeline)>,
+    pub sprites: Query<
+        'w,
+        's,
+        (
+            SpritePipelineEntity,
+            Sprite,
+            BindGroupHandle,
+            Option<RenderLayers>,
+        ),
         With<(Transform, TextureDimensions)>,
     >,
-    bind_groups: Res<AssetBindGroups>,
-    view: Res<RenderView>,
-    window: Res<Window>,
-) {
+    pub bind_groups: Res<'w, AssetBindGroups>,
+    pub view: Res<'w, RenderView>,
+    pub window: Res<'w, Window>,
+    pub cameras: Res<'w, RenderCameras>,
+}
+
+fn render_sprites(params: SpriteRenderParams) {
+    let SpriteRenderParams {
+        mut buffers,
+        mut encoder,
+        context,
+        sprite_pipelines,
+        sprites,
+        bind_groups,
+        view,
+        window,
+        cameras,
+    } = params;
+
     let num_sprites_in_buffer = buffers.sprites.len();
     buffers.write_buffers(&context, &window);
 
-    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-        label: Some("sprites"),
-        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-            view: &view,
-            resolve_target: None,
-            ops: wgpu::Operations {
-                load: wgpu::LoadOp::Load,
-                store: wgpu::StoreOp::Store,
-            },
-        })],
-        depth_stencil_attachment: None,
-        occlusion_query_set: None,
-        timestamp_writes: None,
-    });
-
     let mut sprites = sprites.iter().collect::<Vec<_>>();
-    sprites.sort_by(|(_, s1, _), (_, s2, _)| s1.z.cmp(&s2.z));
-
-    render_pass.set_vertex_buffer(0, buffers.vertex_buffer.buffer().slice(..));
-    render_pass.set_vertex_buffer(1, buffers.sprite_buffer.buffer().slice(..));
-    render_pass.set_vertex_buffer(2, buffers.transform_buffer.buffer().slice(..));
+    sprites.sort_by(|(_, s1, _, _), (_, s2, _, _)| s1.z.cmp(&s2.z));
 
     if sprites.len() != num_sprites_in_buffer {
         // println!("{}, {}", sprites.len(), num_sprites_in_buffer);
         return;
     }
 
-    let mut last_pipeline_entity = None;
-    let mut last_material_id = None;
-    let mut offset = 0;
-    for (pipeline_entity, _, material_binding) in sprites.iter() {
-        let (_, pipeline) = sprite_pipelines.get(pipeline_entity.0).unwrap();
+    for camera in cameras.iter() {
+        let mut render_pass =
+            encoder
+                .camera(camera)
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("sprites"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: camera.view(&view),
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+        camera.set_viewport(&mut render_pass);
+
+        render_pass.set_vertex_buffer(0, buffers.vertex_buffer.buffer().slice(..));
+        render_pass.set_vertex_buffer(1, buffers.sprite_buffer.buffer().slice(..));
+        render_pass.set_vertex_buffer(2, buffers.transform_buffer.buffer().slice(..));
+
+        let mut last_pipeline_entity = None;
+        let mut last_material_id = None;
+        for (offset, (pipeline_entity, _, material_binding, layers)) in sprites.iter().enumerate() {
+            // The target cannot be sampled while it is drawn to
+            if !camera.draws(*layers) || camera.is_target(material_binding.asset_id()) {
+                continue;
+            }
 
-        if last_pipeline_entity != Some(pipeline_entity) {
-            render_pass.set_pipeline(&pipeline.pipeline.0);
-            last_pipeline_entity = Some(pipeline_entity);
-        }
+            let (_, pipeline) = sprite_pipelines.get(pipeline_entity.0).unwrap();
+
+            if last_pipeline_entity != Some(pipeline_entity) {
+                render_pass.set_pipeline(&pipeline.pipeline.0);
+                render_pass.set_bind_group(0, camera.binding(), &[]);
+                last_pipeline_entity = Some(pipeline_entity);
+            }
+
+            if last_material_id != Some(material_binding.id()) {
+                let material = bind_groups.get_from_id(material_binding.id()).unwrap();
+                render_pass.set_bind_group(1, &material.0.binding(), &[]);
+                last_material_id = Some(material_binding.id());
+            }
 
-        if last_material_id != Some(material_binding.id()) {
-            let material = bind_groups.get_from_id(material_binding.id()).unwrap();
-            render_pass.set_bind_group(0, pipeline.camera_binding.binding(), &[]);
-            render_pass.set_bind_group(1, &material.0.binding(), &[]);
-            last_material_id = Some(material_binding.id());
+            let offset = offset as u32;
+            render_pass.draw(offset * 6..offset * 6 + 6, offset..offset + 1);
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_pipeline::render_assets::free_unloaded_render_assets;
+    use crate::texture::SamplerFilterType;
+    use app::render_util::{RenderConfig, RenderDevice, RenderQueue};
+
+    /// None on machines without an adapter.
+    fn headless_context() -> Option<RenderContext> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&Default::default(), None)).ok()?;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: 64,
+            height: 64,
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: Vec::new(),
+        };
+
+        Some(RenderContext {
+            queue: RenderQueue::new(queue),
+            device: RenderDevice::new(device),
+            config: RenderConfig::from_config(&config),
+        })
+    }
 
-        render_pass.draw(offset * 6..offset * 6 + 6, offset..offset + 1);
-        offset += 1;
+    #[test]
+    fn rebind_after_retry() {
+        let Some(context) = headless_context() else {
+            return;
+        };
+        let mut world = World::default();
+        world.insert_resource(context);
+        world.insert_resource(AssetBindGroups::default());
+        world.register_event::<AssetLoaderEvent<Image>>();
+
+        // Stands in for the fallback returned by `Assets::get` while the image is failed
+        let mut images = Assets::<Image>::default();
+        let image = images.add(Image::checker());
+        world.insert_resource(images);
+
+        // Bound to the fallback, as by `bind_new_sprite_bundles`
+        let material = Material2d {
+            texture: image.clone(),
+            ..Default::default()
+        };
+        let mut textures = RenderAssets::<Texture>::default();
+        let binding = material_binding(
+            &material,
+            &world.resource::<RenderContext>(),
+            &mut textures,
+            &world.resource::<Assets<Image>>(),
+        );
+        let dimensions = TextureDimensions::from_texture(&textures[&image]);
+        world.insert_resource(textures);
+        let binding = world
+            .resource_mut::<AssetBindGroups>()
+            .insert(image.clone(), binding);
+        world.spawn((image.clone(), binding, dimensions, material));
+
+        // The retry loads the real image
+        *world
+            .resource_mut::<Assets<Image>>()
+            .get_mut(&image)
+            .unwrap() = Image::render_target(32, 8, SamplerFilterType::Nearest);
+        world.push_event(AssetLoaderEvent::Loaded {
+            handle: image.clone_weak(),
+        });
+
+        let free = world.register_system(free_unloaded_render_assets::<Texture>);
+        let rebind = world.register_system(bind_updated_texture_handles::<Material2d>);
+        world.run_system(free);
+        world.run_system(rebind);
+
+        let check = world.register_system(
+            |sprites: Query<(BindGroupHandle, TextureDimensions)>,
+             bind_groups: Res<AssetBindGroups>,
+             textures: Res<RenderAssets<Texture>>| {
+                let (binding, dimensions) = sprites.get_single().unwrap();
+                assert_eq!((dimensions.width(), dimensions.height()), (32.0, 8.0));
+                assert!(bind_groups.get(*binding).is_some());
+                assert_eq!(textures.len(), 1);
+                assert!(textures
+                    .values()
+                    .all(|texture| texture.texture().width() == 32));
+            },
+        );
+        world.run_system(check);
     }
 }
@@ -1,4 +1,4 @@
-use crate::camera::Camera;
+use crate::camera::{Camera, RenderTarget};
 use crate::render::{RenderEncoder, RenderView};
 use app::prelude::*;
 use asset::server::AssetServer;
@@ -55,6 +55,7 @@ impl AssetLoader for TextAssetLoader {
         _settings: Self::Settings,
         _path: String,
         _ext: &str,
+        _context: &mut asset::LoadContext,
     ) -> Result<Self::Asset, asset::AssetLoaderError> {
         match reader.read_all() {
             Ok(bytes) => Ok(Ttf {
@@ -89,7 +90,10 @@ fn text_setup(
     camera: Query<Camera>,
     window: Res<Window>,
 ) {
-    let Ok(camera) = camera.get_single() else {
+    let Some(camera) = camera
+        .iter()
+        .find(|camera| camera.target == RenderTarget::Window)
+    else {
         return;
     };
 
@@ -1,6 +1,9 @@
 use crate::render_pipeline::render_assets::RenderAssetApp;
 use app::render_util::{Dimensions, RenderConfig, RenderContext, RenderDevice, RenderQueue};
-use asset::{reader::ByteReader, Asset, AssetApp, AssetLoaderError};
+use asset::{
+    reader::ByteReader, Asset, AssetApp, AssetLoaderError, AssetProcessor, Assets, ProcessError,
+};
+use cereal::{FromValue, ToValue, Value, ValueError, ValuePath, WinnyFromValue, WinnyToValue};
 use ecs::WinnyComponent;
 use image::{DynamicImage, GenericImageView};
 use std::{
@@ -21,16 +24,49 @@ impl app::plugins::Plugin for TexturePlugin {
         app.register_asset::<Image>()
             .register_render_asset::<Texture>()
             .register_render_asset::<TextureAtlas>()
-            .register_asset_loader::<Image>(image_loader);
+            .register_asset_loader::<Image>(image_loader)
+            .register_asset_processor(ImageProcessor);
+        app.world_mut()
+            .resource_mut::<Assets<Image>>()
+            .set_fallback(Image::checker());
     }
 }
 
-struct ImageAssetLoader;
+pub struct ImageAssetLoader;
 
 /// Describes the number of sprites within an [`Image`].
+///
+/// Written as `{ width = 4, height = 2 }` in an image's `.meta` file.
 #[derive(Debug)]
 pub struct AtlasDimensions(Dimensions<u32>);
 
+impl AtlasDimensions {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self(Dimensions::new(width, height))
+    }
+}
+
+impl ToValue for AtlasDimensions {
+    fn to_value(&self) -> Value {
+        Value::Table(vec![
+            ("width".into(), self.width().to_value()),
+            ("height".into(), self.height().to_value()),
+        ])
+    }
+}
+
+impl FromValue for AtlasDimensions {
+    fn from_value(value: &Value) -> Result<Self, ValueError> {
+        value.as_table()?;
+        let dimension = |key: &str| match value.get(key) {
+            Some(v) => u32::from_value(v).map_err(|e| e.within(ValuePath::Key(key.into()))),
+            None => Ok(1),
+        };
+
+        Ok(Self::new(dimension("width")?, dimension("height")?))
+    }
+}
+
 impl Default for AtlasDimensions {
     fn default() -> Self {
         Self(Dimensions::new(1, 1))
@@ -45,10 +81,19 @@ impl Deref for AtlasDimensions {
     }
 }
 
-/// [`Image`] settings for asset loading.
-#[derive(Debug, Default)]
+/// [`Image`] settings for asset loading, read from the `[loader]` table of an image's `.meta`
+/// file.
+///
+/// ```toml
+/// [loader]
+/// sampler = "linear"
+/// atlas_dimensions = { width = 4, height = 1 }
+/// ```
+#[derive(WinnyToValue, WinnyFromValue, Debug, Default)]
 pub struct ImageSettings {
-    atlas_dimensions: AtlasDimensions,
+    pub atlas_dimensions: AtlasDimensions,
+    /// Filtering of the [`Texture`] created from the [`Image`].
+    pub sampler: SamplerFilterType,
 }
 
 impl asset::AssetLoader for ImageAssetLoader {
@@ -64,18 +109,110 @@ impl asset::AssetLoader for ImageAssetLoader {
         settings: Self::Settings,
         _path: String,
         ext: &str,
+        context: &mut asset::LoadContext,
     ) -> Result<Self::Asset, AssetLoaderError> {
         match ext {
-            "png" => Image::new(reader, settings),
+            "png" => {
+                context
+                    .compute(move || Image::new(reader, settings))
+                    .await?
+            }
             _ => Err(AssetLoaderError::UnsupportedFileExtension),
         }
     }
 }
 
+/// Packs sprite sheets while processing.
+pub struct ImageProcessor;
+
+/// [`ImageProcessor`] settings, read from the `[processor]` table of an image's `.meta` file.
+#[derive(WinnyToValue, WinnyFromValue, Debug, Default)]
+pub struct ImageProcessorSettings {
+    pub atlas: Option<AtlasPacking>,
+}
+
+/// Layout of a sprite sheet with uniformly sized tiles.
+///
+/// The tiles are packed into a grid without margin or padding, so that every tile of the
+/// resulting [`TextureAtlas`] has the same size in texture space.
+#[derive(WinnyToValue, WinnyFromValue, Debug, Default)]
+pub struct AtlasPacking {
+    pub tile_width: u32,
+    pub tile_height: u32,
+    /// Pixels between neighbouring tiles.
+    pub padding: u32,
+    /// Pixels around the edge of the sheet.
+    pub margin: u32,
+}
+
+impl AssetProcessor for ImageProcessor {
+    type Settings = ImageProcessorSettings;
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["png"]
+    }
+
+    fn process(
+        &self,
+        bytes: Vec<u8>,
+        settings: &Self::Settings,
+        _ext: &str,
+    ) -> Result<Vec<u8>, ProcessError> {
+        let Some(atlas) = &settings.atlas else {
+            return Ok(bytes);
+        };
+        if atlas.tile_width == 0 || atlas.tile_height == 0 {
+            return Err(ProcessError::Failed("atlas tiles must not be empty".into()));
+        }
+
+        let image =
+            image::load_from_memory(&bytes).map_err(|e| ProcessError::Failed(e.to_string()))?;
+        let (width, height) = image.dimensions();
+        let stride = |tile: u32| tile + atlas.padding;
+        let columns =
+            (width.saturating_sub(2 * atlas.margin) + atlas.padding) / stride(atlas.tile_width);
+        let rows =
+            (height.saturating_sub(2 * atlas.margin) + atlas.padding) / stride(atlas.tile_height);
+        if columns == 0 || rows == 0 {
+            return Err(ProcessError::Failed(
+                "atlas tiles do not fit within the image".into(),
+            ));
+        }
+
+        let mut packed =
+            image::RgbaImage::new(columns * atlas.tile_width, rows * atlas.tile_height);
+        for row in 0..rows {
+            for column in 0..columns {
+                let tile = image.view(
+                    atlas.margin + column * stride(atlas.tile_width),
+                    atlas.margin + row * stride(atlas.tile_height),
+                    atlas.tile_width,
+                    atlas.tile_height,
+                );
+                image::imageops::replace(
+                    &mut packed,
+                    &tile.to_image(),
+                    (column * atlas.tile_width) as i64,
+                    (row * atlas.tile_height) as i64,
+                );
+            }
+        }
+
+        let mut png = Vec::new();
+        packed
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .map_err(|e| ProcessError::Failed(e.to_string()))?;
+
+        Ok(png)
+    }
+}
+
 /// Source for a [`Texture`] and [`TextureAtlas`].
 pub struct Image {
     image: DynamicImage,
     atlas_dimensions: AtlasDimensions,
+    sampler: SamplerFilterType,
+    render_target: bool,
 }
 
 impl Asset for Image {}
@@ -94,13 +231,57 @@ impl Image {
         let data = reader
             .read_all()
             .map_err(|_| AssetLoaderError::FailedToParse)?;
-        let image = image::load_from_memory(&data).map_err(|_| AssetLoaderError::FailedToParse)?;
+        let image =
+            image::load_from_memory(&data).map_err(|e| AssetLoaderError::Failed(e.to_string()))?;
 
         Ok(Self {
             image,
             atlas_dimensions: settings.atlas_dimensions,
+            sampler: settings.sampler,
+            render_target: false,
         })
     }
+
+    /// Empty image which a [`crate::camera::Camera`] can draw to with
+    /// [`crate::camera::RenderTarget::Image`]. Its [`Texture`] can be used by any [`crate::Material`].
+    pub fn render_target(width: u32, height: u32, sampler: SamplerFilterType) -> Self {
+        Self {
+            image: DynamicImage::new_rgba8(width, height),
+            atlas_dimensions: AtlasDimensions::default(),
+            sampler,
+            render_target: true,
+        }
+    }
+
+    pub fn is_render_target(&self) -> bool {
+        self.render_target
+    }
+
+    pub fn dimensions(&self) -> Dimensions<u32> {
+        let (width, height) = self.image.dimensions();
+        Dimensions::new(width, height)
+    }
+
+    /// Magenta and black checker pattern. The fallback for [`Image`]s which failed to load.
+    pub fn checker() -> Self {
+        const MAGENTA: image::Rgba<u8> = image::Rgba([255, 0, 255, 255]);
+        const BLACK: image::Rgba<u8> = image::Rgba([0, 0, 0, 255]);
+
+        let image = image::RgbaImage::from_fn(16, 16, |x, y| {
+            if (x / 8 + y / 8) % 2 == 0 {
+                MAGENTA
+            } else {
+                BLACK
+            }
+        });
+
+        Self {
+            image: DynamicImage::ImageRgba8(image),
+            atlas_dimensions: AtlasDimensions::default(),
+            sampler: SamplerFilterType::Nearest,
+            render_target: false,
+        }
+    }
 }
 
 /// Dimensions of a [`Texture`] in pixels.
@@ -132,6 +313,7 @@ impl TextureDimensions {
 #[derive(Debug)]
 pub struct Texture {
     texture: wgpu::Texture,
+    sampler: SamplerFilterType,
 }
 
 #[cfg(feature = "widgets")]
@@ -144,7 +326,10 @@ impl Texture {
         let rgba = img.image.to_rgba8();
         let dimensions = img.image.dimensions();
 
-        Self::from_bytes(&rgba, dimensions, device, queue)
+        let mut texture = Self::from_bytes(&rgba, dimensions, device, queue);
+        texture.sampler = img.sampler;
+
+        texture
     }
 
     pub fn from_bytes(
@@ -175,7 +360,24 @@ impl Texture {
             bytes,
         );
 
-        Self { texture }
+        Self {
+            texture,
+            sampler: SamplerFilterType::default(),
+        }
+    }
+
+    /// Texture of a render target [`Image`]. It uses the surface format, so that every pipeline can
+    /// draw to it.
+    pub fn render_target(context: &RenderContext, img: &Image) -> Self {
+        let mut texture = Self::empty(
+            img.dimensions(),
+            context,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            context.config.format(),
+        );
+        texture.sampler = img.sampler;
+
+        texture
     }
 
     pub fn empty(
@@ -201,7 +403,10 @@ impl Texture {
             view_formats: &[],
         });
 
-        Self { texture }
+        Self {
+            texture,
+            sampler: SamplerFilterType::default(),
+        }
     }
 
     pub fn create_view(&self) -> wgpu::TextureView {
@@ -239,6 +444,12 @@ impl Texture {
         &self.texture
     }
 
+    /// Filtering of the sampler created for this [`Texture`] by materials. Set from the
+    /// [`ImageSettings`] of the source [`Image`].
+    pub fn sampler_filter(&self) -> SamplerFilterType {
+        self.sampler
+    }
+
     pub fn width(&self) -> u32 {
         self.texture.width()
     }
@@ -248,7 +459,9 @@ impl Texture {
     }
 }
 
+#[derive(WinnyToValue, WinnyFromValue, Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum SamplerFilterType {
+    #[default]
     Nearest,
     Linear,
 }
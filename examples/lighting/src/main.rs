@@ -4,7 +4,7 @@ use winny::{
     app::input::mouse_and_key::MouseState,
     asset::server::AssetServer,
     gfx::{
-        camera::Camera2dBundle,
+        camera::{Camera, Camera2dBundle, OrthographicProjection},
         mesh2d::{Mesh2d, Mesh2dPlugin, Points, Triangle},
     },
     math::vector::Vec2f,
@@ -44,9 +44,9 @@ struct PlayerPoints {
     mesh: Mesh2d,
 }
 
-#[derive(Resource, Serialize, Deserialize, Default)]
+#[derive(Resource, Default)]
 struct GlobalMesh {
-    mesh: Mesh2d,
+    mesh: Option<Handle<Mesh2d>>,
 }
 
 #[derive(Resource)]
@@ -80,12 +80,22 @@ fn update(
     mut global_mesh: ResMut<GlobalMesh>,
     key_input: EventReader<KeyInput>,
     mesh_entities: Query<Entity, With<Handle<Mesh2d>>>,
-    window: Res<Window>,
+    camera: Query<(Camera, Transform, OrthographicProjection)>,
     save_path: Res<SavePath>,
+    server: Res<AssetServer>,
 ) {
+    let Ok((camera, camera_transform, projection)) = camera.get_single() else {
+        return;
+    };
+
     for input in mouse_motion.read() {
-        state.0 = input.0 as f32 - window.viewport.width() / 2.0;
-        state.1 = input.1 as f32 - window.viewport.height() / 2.0;
+        let position = camera.viewport_to_world(
+            camera_transform,
+            projection,
+            Vec2f::new(input.0 as f32, input.1 as f32),
+        );
+        state.0 = position.x;
+        state.1 = position.y;
     }
 
     for input in mouse_input.read() {
@@ -106,8 +116,8 @@ fn update(
 
             if let Some(mesh) = Mesh2d::from_points(global_points.0.clone()) {
                 println!("{:#?}", mesh);
-                global_mesh.mesh = mesh.clone();
                 let handle = meshes.add(mesh);
+                global_mesh.mesh = Some(handle.clone());
                 commands.spawn((Transform::default(), handle, ColorMaterial::default()));
                 for entity in mesh_entities.iter() {
                     commands.get_entity(entity).despawn();
@@ -119,7 +129,9 @@ fn update(
     for KeyInput { code, state, .. } in key_input.peak_read() {
         if *state == KeyState::Pressed {
             if *code == KeyCode::S {
-                save_struct(&global_mesh.mesh, &save_path.0);
+                if let Some(mesh) = &global_mesh.mesh {
+                    server.save(mesh, &save_path.0);
+                }
             }
         }
     }
@@ -165,14 +177,3 @@ fn save_resource<R: Serialize + Deserialize + Resource + Default>(resource: Res<
         Err(e) => error!("failed to serialize [{}]: {e}", std::any::type_name::<R>()),
     }
 }
-
-fn save_struct<R: Serialize + Deserialize + Default>(resource: &R, path: &String) {
-    info!("saving struct to: {path}");
-    let mut bytes = Vec::new();
-    let mut s = Serializer::new(&mut bytes);
-    resource.serialize(&mut s);
-    match std::fs::write(format!("{}", path), &bytes) {
-        Ok(_) => (),
-        Err(e) => error!("failed to serialize [{}]: {e}", std::any::type_name::<R>()),
-    }
-}
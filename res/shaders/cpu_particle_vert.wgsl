@@ -66,7 +66,6 @@ fn vs_main(vert: VertexInput, transform: ParticleTransform) -> VertexOutput {
 
     out.clip_position = vert.position * particle_transformation;
     out.clip_position *= camera_matrix;
-    out.clip_position.z = 0.0;
     out.uv = vert.uv;
     return out;
 }
@@ -78,7 +78,6 @@ fn vs_main(vert: VertexInput, instance: InstanceInput) -> VertexOutput {
     out.clip_position = vert.position * particle_transformation;
     out.clip_position += vec4<f32>(particle.translation.xy, 0.0, 0.0);
     out.clip_position *= camera_matrix;
-    out.clip_position.z = 0.0;
     out.uv = vert.uv;
     return out;
 }
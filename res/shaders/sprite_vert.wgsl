@@ -74,7 +74,6 @@ fn vs_main(
     camera_matrix[1][1] *= camera.viewport_dimensions.y / camera.window_dimensions.y;
 
     out.clip_position = vert.position * transformation_matrix * camera_matrix;
-    out.clip_position.z = 0.0;
 
     return out;
 }